@@ -0,0 +1,133 @@
+use ed25519_dalek::SigningKey;
+use serde::{Deserialize, Serialize};
+
+use super::crypto;
+use super::{Block, Transaction};
+
+/// Fields covered by the block hash (everything except `hash` and `signature`)
+#[derive(Serialize)]
+struct BlockHeader<'a> {
+    number: u64,
+    parent_hash: &'a str,
+    timestamp: &'a chrono::DateTime<chrono::Utc>,
+    transactions_root: String,
+    state_root: &'a str,
+    validator: &'a str,
+}
+
+/// Block header with its hash and author signature, as served to syncing peers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedHeader {
+    pub number: u64,
+    pub parent_hash: String,
+    pub hash: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub transactions_root: String,
+    pub state_root: String,
+    pub validator: String,
+    pub signature: String,
+}
+
+impl SignedHeader {
+    /// blake3 hash over the header fields, hex-encoded
+    pub fn compute_hash(&self) -> String {
+        BlockHeader {
+            number: self.number,
+            parent_hash: &self.parent_hash,
+            timestamp: &self.timestamp,
+            transactions_root: self.transactions_root.clone(),
+            state_root: &self.state_root,
+            validator: &self.validator,
+        }.hash()
+    }
+}
+
+impl BlockHeader<'_> {
+    fn hash(&self) -> String {
+        let bytes = bincode::serialize(self).expect("block header serialization cannot fail");
+        blake3::hash(&bytes).to_hex().to_string()
+    }
+}
+
+impl Block {
+    /// Build and sign a new block on top of `parent_hash`
+    pub fn new_signed(
+        number: u64,
+        parent_hash: String,
+        timestamp: chrono::DateTime<chrono::Utc>,
+        transactions: Vec<Transaction>,
+        state_root: String,
+        key: &SigningKey,
+    ) -> Self {
+        let mut block = Self {
+            number,
+            parent_hash,
+            hash: String::new(),
+            timestamp,
+            transactions,
+            state_root,
+            validator: crypto::address_from_public_key(&key.verifying_key()),
+            signature: String::new(),
+        };
+
+        block.hash = block.compute_hash();
+        block.signature = crypto::sign(key, block.hash.as_bytes());
+        block
+    }
+
+    /// Unsigned genesis block committing to the initial state
+    ///
+    /// Its parent hash is the hash of the genesis spec, so chains that differ
+    /// only in consensus or protocol parameters still get distinct genesis hashes.
+    pub fn genesis(timestamp: chrono::DateTime<chrono::Utc>, state_root: String, spec_hash: String) -> Self {
+        let mut block = Self {
+            number: 0,
+            parent_hash: spec_hash,
+            hash: String::new(),
+            timestamp,
+            transactions: Vec::new(),
+            state_root,
+            validator: String::new(),
+            signature: String::new(),
+        };
+
+        block.hash = block.compute_hash();
+        block
+    }
+
+    /// blake3 hash over the block header, hex-encoded
+    pub fn compute_hash(&self) -> String {
+        BlockHeader {
+            number: self.number,
+            parent_hash: &self.parent_hash,
+            timestamp: &self.timestamp,
+            transactions_root: Self::transactions_root(&self.transactions),
+            state_root: &self.state_root,
+            validator: &self.validator,
+        }.hash()
+    }
+
+    /// Header of this block without the transaction bodies
+    pub fn header(&self) -> SignedHeader {
+        SignedHeader {
+            number: self.number,
+            parent_hash: self.parent_hash.clone(),
+            hash: self.hash.clone(),
+            timestamp: self.timestamp,
+            transactions_root: Self::transactions_root(&self.transactions),
+            state_root: self.state_root.clone(),
+            validator: self.validator.clone(),
+            signature: self.signature.clone(),
+        }
+    }
+
+    /// blake3 hash over the ordered transaction list, hex-encoded
+    pub fn transactions_root(transactions: &[Transaction]) -> String {
+        let mut hasher = blake3::Hasher::new();
+        for tx in transactions {
+            let bytes = bincode::serialize(tx).expect("transaction serialization cannot fail");
+            hasher.update(blake3::hash(&bytes).as_bytes());
+        }
+        hasher.finalize().to_hex().to_string()
+    }
+}
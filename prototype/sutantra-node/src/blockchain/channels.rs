@@ -0,0 +1,291 @@
+// Unidirectional payment channels
+//
+// A viewer opens a channel to a stream's creator by locking a deposit
+// on-chain. While watching, the viewer sends the creator signed updates off
+// the chain, each carrying the cumulative amount owed so far; only the latest
+// update matters, and every update buys watch time at the stream's
+// `price_per_minute`, so only per-minute streams take channels. The creator
+// closes the channel with the latest update and is paid at once. The viewer
+// may close too, but then the channel waits out a dispute window during which
+// the creator can answer a stale close with a newer update. When the window
+// passes, the channel settles at the amount the viewer's close named, and the
+// rest of the deposit goes back.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::crypto;
+use super::engine::BlockchainState;
+use super::pricing::PricingModel;
+
+/// Domain separator for channel update signatures
+const UPDATE_SIGNING_DOMAIN: &str = "sutantra-channel-update-v1";
+
+/// Domain separator for channel ids
+const CHANNEL_ID_DOMAIN: &[u8] = b"sutantra-channel-v1";
+
+/// A channel's on-chain record
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaymentChannel {
+    pub channel_id: String,
+    pub viewer: String,
+    pub creator: String,
+    pub stream_id: String,
+    /// Funds locked by the viewer, the most the creator can be paid
+    pub deposit: u64,
+    pub opened_at: chrono::DateTime<chrono::Utc>,
+    /// Set once the viewer starts a close
+    pub closing: Option<ChannelClosing>,
+}
+
+/// A viewer's close waiting out the dispute window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelClosing {
+    /// Amount the channel settles at unless the creator shows a newer update
+    pub amount: u64,
+    pub settles_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Viewer-signed claim that `amount` in total is owed to the creator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelUpdate {
+    pub channel_id: String,
+    pub amount: u64,
+    /// Hex ed25519 signature by the viewer over [`ChannelUpdate::signing_payload`]
+    pub signature: String,
+}
+
+/// Id of the channel a viewer opens with the transaction at `nonce`
+pub fn channel_id(viewer: &str, nonce: u64) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(CHANNEL_ID_DOMAIN);
+    hasher.update(viewer.as_bytes());
+    hasher.update(&nonce.to_be_bytes());
+    hasher.finalize().to_hex().to_string()
+}
+
+impl ChannelUpdate {
+    /// Canonical bytes covered by the signature
+    pub fn signing_payload(channel_id: &str, amount: u64, chain_id: &str) -> Vec<u8> {
+        bincode::serialize(&(UPDATE_SIGNING_DOMAIN, chain_id, channel_id, amount))
+            .expect("channel update serialization cannot fail")
+    }
+
+    /// Check the update is signed by the channel's viewer and within its deposit
+    pub fn verify(&self, channel: &PaymentChannel, chain_id: &str) -> Result<()> {
+        if self.channel_id != channel.channel_id {
+            return Err(anyhow!("Update is for channel {}, not {}", self.channel_id, channel.channel_id));
+        }
+        if self.amount > channel.deposit {
+            return Err(anyhow!(
+                "Update amount {} exceeds the deposit of {} in channel {}",
+                self.amount, channel.deposit, channel.channel_id
+            ));
+        }
+        crypto::verify(
+            &channel.viewer,
+            &Self::signing_payload(&self.channel_id, self.amount, chain_id),
+            &self.signature,
+        )
+    }
+}
+
+/// Latest accepted update of a channel and the watch time it bought
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelState {
+    pub viewer: String,
+    pub stream_id: String,
+    pub latest: ChannelUpdate,
+    pub paid_until: chrono::DateTime<chrono::Utc>,
+}
+
+/// Off-chain updates this node has received, by channel id
+///
+/// Node-local and not part of consensus: a creator's node keeps the latest
+/// update so it can close the channel with it, and any node uses it to grant
+/// access between on-chain payments.
+#[derive(Default)]
+pub struct ChannelBook {
+    channels: HashMap<String, ChannelState>,
+}
+
+impl ChannelBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept an update that raises the amount owed on an open channel
+    ///
+    /// The increase buys access at the stream's price, extending any paid time
+    /// that is left. Returns the new end of the viewer's paid time.
+    pub fn accept(
+        &mut self,
+        state: &BlockchainState,
+        update: ChannelUpdate,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<chrono::DateTime<chrono::Utc>> {
+        let channel = state.channels.get(&update.channel_id)
+            .ok_or_else(|| anyhow!("Channel {} is not open", update.channel_id))?;
+        if channel.closing.is_some() {
+            return Err(anyhow!("Channel {} is closing", channel.channel_id));
+        }
+        update.verify(channel, &state.chain_id)?;
+
+        let previous = self.channels.get(&channel.channel_id);
+        let paid = previous.map_or(0, |known| known.latest.amount);
+        if update.amount <= paid {
+            return Err(anyhow!(
+                "Stale update for channel {}: {} is not above {}", channel.channel_id, update.amount, paid
+            ));
+        }
+
+        let price_per_minute = match state.streams.get(&channel.stream_id) {
+            Some(stream) if stream.pricing == PricingModel::PerMinute => stream.price_per_minute.max(1),
+            _ => return Err(anyhow!("Stream {} is not priced per minute", channel.stream_id)),
+        };
+        let seconds = i64::try_from((update.amount - paid) as u128 * 60 / price_per_minute as u128).ok();
+        let start = previous.map_or(now, |known| known.paid_until.max(now));
+        let paid_until = seconds
+            .and_then(chrono::TimeDelta::try_seconds)
+            .and_then(|bought| start.checked_add_signed(bought))
+            .ok_or_else(|| anyhow!("Update for channel {} buys more time than can be recorded", channel.channel_id))?;
+
+        self.channels.insert(channel.channel_id.clone(), ChannelState {
+            viewer: channel.viewer.clone(),
+            stream_id: channel.stream_id.clone(),
+            latest: update,
+            paid_until,
+        });
+        Ok(paid_until)
+    }
+
+    /// Latest accepted state of a channel
+    pub fn get(&self, channel_id: &str) -> Option<&ChannelState> {
+        self.channels.get(channel_id)
+    }
+
+    /// End of the time a viewer paid for through their open channels to a stream
+    ///
+    /// Channels that `state` shows as closed or closing no longer count.
+    pub fn paid_until(&self, state: &BlockchainState, viewer: &str, stream_id: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.open(state)
+            .filter(|known| known.viewer == viewer && known.stream_id == stream_id)
+            .map(|known| known.paid_until)
+            .max()
+    }
+
+    /// Accepted states of the channels that `state` shows as open
+    pub fn open<'a>(&'a self, state: &'a BlockchainState) -> impl Iterator<Item = &'a ChannelState> + 'a {
+        self.channels.iter()
+            .filter(move |(channel_id, _)| is_open(state, channel_id))
+            .map(|(_, known)| known)
+    }
+
+    /// Forget channels that are settled on the canonical chain
+    ///
+    /// Closing channels are kept: their latest update answers a stale close.
+    pub fn prune(&mut self, state: &BlockchainState) {
+        self.channels.retain(|channel_id, _| state.channels.contains_key(channel_id));
+    }
+}
+
+fn is_open(state: &BlockchainState, channel_id: &str) -> bool {
+    state.channels.get(channel_id).is_some_and(|channel| channel.closing.is_none())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::genesis::GenesisSpec;
+    use crate::blockchain::StreamRegistration;
+    use ed25519_dalek::SigningKey;
+
+    /// Chain with a per-minute stream and a channel to it from the viewer `key` signs for
+    fn state(key: &SigningKey, price_per_minute: u64, deposit: u64) -> BlockchainState {
+        let viewer = crypto::address_from_public_key(&key.verifying_key());
+        let now = chrono::Utc::now();
+        let mut state = GenesisSpec::default().build().0;
+        state.streams.insert("stream".to_string(), StreamRegistration {
+            stream_id: "stream".to_string(),
+            creator: "creator".to_string(),
+            title: "Stream".to_string(),
+            description: None,
+            price_per_minute,
+            pricing: PricingModel::PerMinute,
+            preview_minutes: 0,
+            created_at: now,
+            total_earnings: 0,
+            split: Default::default(),
+            paid_out: Default::default(),
+            recent_payouts: Vec::new(),
+        });
+        state.channels.insert("channel".to_string(), PaymentChannel {
+            channel_id: "channel".to_string(),
+            viewer,
+            creator: "creator".to_string(),
+            stream_id: "stream".to_string(),
+            deposit,
+            opened_at: now,
+            closing: None,
+        });
+        state
+    }
+
+    fn update(key: &SigningKey, state: &BlockchainState, amount: u64) -> ChannelUpdate {
+        ChannelUpdate {
+            channel_id: "channel".to_string(),
+            amount,
+            signature: crypto::sign(key, &ChannelUpdate::signing_payload("channel", amount, &state.chain_id)),
+        }
+    }
+
+    #[test]
+    fn updates_buy_time_from_the_amount_they_raise() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let state = state(&key, 10, 1_000);
+        let viewer = crypto::address_from_public_key(&key.verifying_key());
+        let now = chrono::Utc::now();
+        let mut book = ChannelBook::new();
+
+        assert_eq!(book.accept(&state, update(&key, &state, 20), now).unwrap(), now + chrono::Duration::minutes(2));
+        let paid_until = book.accept(&state, update(&key, &state, 50), now).unwrap();
+        assert_eq!(paid_until, now + chrono::Duration::minutes(5));
+        assert_eq!(book.paid_until(&state, &viewer, "stream"), Some(paid_until));
+
+        // Stale, over-deposit and wrongly signed updates are refused
+        assert!(book.accept(&state, update(&key, &state, 50), now).is_err());
+        assert!(book.accept(&state, update(&key, &state, 1_001), now).is_err());
+        let forged = update(&SigningKey::from_bytes(&[2; 32]), &state, 60);
+        assert!(book.accept(&state, forged, now).is_err());
+    }
+
+    #[test]
+    fn refuses_updates_that_buy_more_time_than_can_be_recorded() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let state = state(&key, 1, u64::MAX);
+        let err = ChannelBook::new().accept(&state, update(&key, &state, u64::MAX), chrono::Utc::now()).unwrap_err();
+        assert!(err.to_string().contains("more time than can be recorded"));
+    }
+
+    #[test]
+    fn closing_channels_take_no_updates_and_stop_counting() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let mut state = state(&key, 10, 1_000);
+        let viewer = crypto::address_from_public_key(&key.verifying_key());
+        let now = chrono::Utc::now();
+        let mut book = ChannelBook::new();
+        book.accept(&state, update(&key, &state, 20), now).unwrap();
+
+        state.channels.get_mut("channel").unwrap().closing = Some(ChannelClosing { amount: 20, settles_at: now });
+        assert!(book.accept(&state, update(&key, &state, 30), now).is_err());
+        assert_eq!(book.paid_until(&state, &viewer, "stream"), None);
+
+        // A closing channel's update is kept to answer a stale close
+        book.prune(&state);
+        assert!(book.get("channel").is_some());
+        state.channels.clear();
+        book.prune(&state);
+        assert!(book.get("channel").is_none());
+    }
+}
@@ -1,11 +1,353 @@
-// Consensus implementation
-// Placeholder for future implementation
+// Consensus: slot leadership and finality
+//
+// Time is divided into fixed slots of `block_time_secs`. Each slot has one
+// leader that may author a block, and a block's slot is derived from its
+// timestamp so any node can check the author. Finality is decided by the
+// consensus engine: instantly in dev mode, or by a 2/3 stake supermajority of
+// validator votes in BFT mode.
 
-pub struct ConsensusEngine;
+use anyhow::{anyhow, Result};
+use ed25519_dalek::SigningKey;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::{debug, info};
 
-impl ConsensusEngine {
-    pub fn new() -> Self {
-        Self
+use super::crypto;
+use super::Block;
+
+/// Domain separator for finality vote signatures
+const VOTE_DOMAIN: &str = "sutantra-vote-v1";
+
+/// Blocks above finality each validator may have votes pending for
+const MAX_PENDING_VOTES_PER_VALIDATOR: usize = 128;
+
+/// A validator eligible to author blocks and vote
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Validator {
+    pub address: String,
+    pub stake: u64,
+}
+
+/// How slot leaders are picked from the validator set
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LeaderSelection {
+    RoundRobin,
+    StakeWeighted,
+}
+
+/// Which consensus engine a node runs
+#[derive(Debug, Clone)]
+pub enum ConsensusMode {
+    /// Single-node development chain: the local validator leads every slot
+    /// and each block is final as soon as it is produced
+    Dev,
+    /// Multi-validator chain with BFT finality
+    Bft {
+        validators: Vec<Validator>,
+        leader_selection: LeaderSelection,
+    },
+}
+
+/// Consensus configuration
+#[derive(Debug, Clone)]
+pub struct ConsensusConfig {
+    pub block_time_secs: u64,
+    pub mode: ConsensusMode,
+}
+
+impl Default for ConsensusConfig {
+    fn default() -> Self {
+        Self {
+            block_time_secs: 6,
+            mode: ConsensusMode::Dev,
+        }
+    }
+}
+
+/// A validator's signed vote to finalize a block
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Vote {
+    pub block_number: u64,
+    pub block_hash: String,
+    pub validator: String,
+    pub signature: String,
+}
+
+/// A block that reached finality
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FinalizedBlock {
+    pub number: u64,
+    pub hash: String,
+}
+
+/// Pluggable consensus engine
+pub trait ConsensusEngine: Send + Sync {
+    /// Human-readable engine name for logs
+    fn name(&self) -> &'static str;
+
+    /// Slot leader for `slot`, if any validator can lead it
+    fn slot_leader(&self, slot: u64) -> Option<&Validator>;
+
+    /// Whether `address` takes part in consensus
+    fn is_validator(&self, address: &str) -> bool;
+
+    /// Whether finality depends on validator votes
+    fn uses_votes(&self) -> bool;
+
+    /// Record a block added to the block tree, returning any block it finalizes
+    fn on_block(&mut self, block: &Block) -> Option<FinalizedBlock>;
+
+    /// Record a verified vote, returning any block it finalizes
+    ///
+    /// Only a block `known` to the block tree can be finalized; votes for other
+    /// blocks wait for [`ConsensusEngine::on_block`].
+    fn on_vote(&mut self, vote: &Vote, known: bool) -> Option<FinalizedBlock>;
+
+    /// Replace the validator set with the one elected on-chain
+    fn set_validators(&mut self, validators: &[Validator]);
+}
+
+impl Vote {
+    /// Create a vote for `block` signed by the local validator
+    pub fn new_signed(block: &Block, key: &SigningKey, chain_id: &str) -> Self {
+        let validator = crypto::address_from_public_key(&key.verifying_key());
+        let payload = Self::signing_payload(block.number, &block.hash, &validator, chain_id);
+
+        Self {
+            block_number: block.number,
+            block_hash: block.hash.clone(),
+            validator,
+            signature: crypto::sign(key, &payload),
+        }
+    }
+
+    /// Check that the vote was signed by `validator`
+    pub fn verify(&self, chain_id: &str) -> Result<()> {
+        let payload = Self::signing_payload(self.block_number, &self.block_hash, &self.validator, chain_id);
+        crypto::verify(&self.validator, &payload, &self.signature)
     }
+
+    fn signing_payload(block_number: u64, block_hash: &str, validator: &str, chain_id: &str) -> Vec<u8> {
+        bincode::serialize(&(VOTE_DOMAIN, chain_id, block_number, block_hash, validator))
+            .expect("vote serialization cannot fail")
+    }
+}
+
+/// Slot containing `timestamp`
+pub fn slot_at(timestamp: chrono::DateTime<chrono::Utc>, block_time_secs: u64) -> u64 {
+    (timestamp.timestamp_millis().max(0) as u64) / (block_time_secs.max(1) * 1000)
 }
 
+/// Check that a block was authored and signed by the leader of its slot
+pub fn verify_block_author(
+    consensus: &dyn ConsensusEngine,
+    block: &Block,
+    block_time_secs: u64,
+) -> Result<()> {
+    let slot = slot_at(block.timestamp, block_time_secs);
+    let leader = consensus.slot_leader(slot)
+        .ok_or_else(|| anyhow!("No leader for slot {}", slot))?;
+    if leader.address != block.validator {
+        return Err(anyhow!(
+            "Block #{} authored by {} but slot {} belongs to {}",
+            block.number, block.validator, slot, leader.address
+        ));
+    }
+
+    crypto::verify(&block.validator, block.hash.as_bytes(), &block.signature)
+}
+
+/// Build the consensus engine for a configuration
+pub fn build(config: &ConsensusConfig, local_validator: Option<&str>) -> Result<Box<dyn ConsensusEngine>> {
+    match &config.mode {
+        ConsensusMode::Dev => {
+            let author = local_validator
+                .map(|address| Validator { address: address.to_string(), stake: 1 });
+            Ok(Box::new(DevConsensus { author }))
+        }
+        ConsensusMode::Bft { validators, leader_selection } => {
+            Ok(Box::new(BftConsensus::new(validators.clone(), *leader_selection)?))
+        }
+    }
+}
+
+/// Single-node consensus for local development
+pub struct DevConsensus {
+    author: Option<Validator>,
+}
+
+impl ConsensusEngine for DevConsensus {
+    fn name(&self) -> &'static str {
+        "dev"
+    }
+
+    fn slot_leader(&self, _slot: u64) -> Option<&Validator> {
+        self.author.as_ref()
+    }
+
+    fn is_validator(&self, address: &str) -> bool {
+        self.author.as_ref().is_some_and(|author| author.address == address)
+    }
+
+    fn uses_votes(&self) -> bool {
+        false
+    }
+
+    fn on_block(&mut self, block: &Block) -> Option<FinalizedBlock> {
+        Some(FinalizedBlock { number: block.number, hash: block.hash.clone() })
+    }
+
+    fn on_vote(&mut self, _vote: &Vote, _known: bool) -> Option<FinalizedBlock> {
+        None
+    }
+
+    fn set_validators(&mut self, _validators: &[Validator]) {
+        // The dev chain is always authored by the local node
+    }
+}
+
+/// Multi-validator consensus with stake-weighted BFT finality
+///
+/// A block is final once validators holding more than 2/3 of the total stake
+/// have voted for it. Finality never moves backwards.
+pub struct BftConsensus {
+    validators: Vec<Validator>,
+    leader_selection: LeaderSelection,
+    total_stake: u64,
+    /// (block number, block hash) -> validator -> stake
+    votes: HashMap<(u64, String), HashMap<String, u64>>,
+    finalized: u64,
+}
+
+impl BftConsensus {
+    pub fn new(validators: Vec<Validator>, leader_selection: LeaderSelection) -> Result<Self> {
+        let validators = Self::normalize(validators);
+        if validators.is_empty() {
+            return Err(anyhow!("BFT consensus requires at least one staked validator"));
+        }
+
+        let total_stake = validators.iter().map(|validator| validator.stake).sum();
+        info!("🗳️  BFT validator set: {} validators, total stake {}", validators.len(), total_stake);
+
+        Ok(Self {
+            validators,
+            leader_selection,
+            total_stake,
+            votes: HashMap::new(),
+            finalized: 0,
+        })
+    }
+
+    /// Staked validators in the order every node uses for leader selection
+    fn normalize(mut validators: Vec<Validator>) -> Vec<Validator> {
+        validators.retain(|validator| validator.stake > 0);
+        validators.sort_by(|a, b| a.address.cmp(&b.address));
+        validators.dedup_by(|a, b| a.address == b.address);
+        validators
+    }
+
+    fn stake_of(&self, address: &str) -> Option<u64> {
+        self.validators.iter()
+            .find(|validator| validator.address == address)
+            .map(|validator| validator.stake)
+    }
+
+    /// Finalize a block once more than 2/3 of the stake has voted for it
+    fn check_finality(&mut self, number: u64, hash: &str) -> Option<FinalizedBlock> {
+        if number <= self.finalized {
+            return None;
+        }
+
+        let voted_stake: u64 = self.votes.get(&(number, hash.to_string()))?.values().sum();
+        debug!("🗳️  Block #{} has {}/{} stake in votes", number, voted_stake, self.total_stake);
+
+        if voted_stake as u128 * 3 > self.total_stake as u128 * 2 {
+            self.finalized = number;
+            self.votes.retain(|(voted, _), _| *voted > number);
+            return Some(FinalizedBlock { number, hash: hash.to_string() });
+        }
+
+        None
+    }
+}
+
+impl ConsensusEngine for BftConsensus {
+    fn name(&self) -> &'static str {
+        "bft"
+    }
+
+    fn slot_leader(&self, slot: u64) -> Option<&Validator> {
+        match self.leader_selection {
+            LeaderSelection::RoundRobin => {
+                self.validators.get((slot % self.validators.len() as u64) as usize)
+            }
+            LeaderSelection::StakeWeighted => {
+                // Deterministic pseudo-random point in [0, total_stake)
+                let seed = blake3::hash(&slot.to_le_bytes());
+                let mut point_bytes = [0u8; 8];
+                point_bytes.copy_from_slice(&seed.as_bytes()[..8]);
+                let mut point = u64::from_le_bytes(point_bytes) % self.total_stake;
+
+                self.validators.iter().find(|validator| {
+                    if point < validator.stake {
+                        true
+                    } else {
+                        point -= validator.stake;
+                        false
+                    }
+                })
+            }
+        }
+    }
+
+    fn is_validator(&self, address: &str) -> bool {
+        self.stake_of(address).is_some()
+    }
+
+    fn uses_votes(&self) -> bool {
+        true
+    }
+
+    fn on_block(&mut self, block: &Block) -> Option<FinalizedBlock> {
+        // Votes may have arrived before the block did
+        self.check_finality(block.number, &block.hash)
+    }
+
+    fn on_vote(&mut self, vote: &Vote, known: bool) -> Option<FinalizedBlock> {
+        let stake = self.stake_of(&vote.validator)?;
+        if vote.block_number <= self.finalized {
+            return None;
+        }
+
+        // A validator cannot grow the vote table without bound by voting for made-up blocks
+        let key = (vote.block_number, vote.block_hash.clone());
+        let counted = self.votes.get(&key).is_some_and(|voters| voters.contains_key(&vote.validator));
+        if !counted {
+            let pending = self.votes.values().filter(|voters| voters.contains_key(&vote.validator)).count();
+            if pending >= MAX_PENDING_VOTES_PER_VALIDATOR {
+                debug!("🗳️  Dropping vote from {}: {} votes already pending", vote.validator, pending);
+                return None;
+            }
+        }
+        self.votes.entry(key).or_default().insert(vote.validator.clone(), stake);
+
+        if !known {
+            return None;
+        }
+        self.check_finality(vote.block_number, &vote.block_hash)
+    }
+
+    fn set_validators(&mut self, validators: &[Validator]) {
+        let validators = Self::normalize(validators.to_vec());
+        if validators.is_empty() || validators == self.validators {
+            return;
+        }
+
+        self.total_stake = validators.iter().map(|validator| validator.stake).sum();
+        self.validators = validators;
+        // Votes counted with the old stakes no longer add up
+        self.votes.clear();
+        info!("🗳️  BFT validator set updated: {} validators, total stake {}", self.validators.len(), self.total_stake);
+    }
+}
@@ -0,0 +1,63 @@
+use anyhow::{Context, Result};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+const VALIDATOR_KEY_FILE: &str = "validator.key";
+
+/// Load the validator signing key from `data_dir`, generating one on first start
+pub fn load_or_generate_validator_key(data_dir: impl AsRef<Path>) -> Result<SigningKey> {
+    let path = data_dir.as_ref().join(VALIDATOR_KEY_FILE);
+
+    if path.exists() {
+        let bytes = fs::read(&path)
+            .with_context(|| format!("Failed to read validator key {}", path.display()))?;
+        let secret: [u8; 32] = bytes.as_slice().try_into()
+            .map_err(|_| anyhow::anyhow!("Validator key {} must be 32 bytes", path.display()))?;
+        return Ok(SigningKey::from_bytes(&secret));
+    }
+
+    let key = SigningKey::generate(&mut rand::rngs::OsRng);
+    write_secret(&path, &key.to_bytes())
+        .with_context(|| format!("Failed to write validator key {}", path.display()))?;
+
+    Ok(key)
+}
+
+/// Create a file holding secret key bytes, readable by the node's user only
+pub fn write_secret(path: &Path, secret: &[u8]) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(secret)
+}
+
+/// Account address for a public key (hex-encoded key bytes)
+pub fn address_from_public_key(public_key: &VerifyingKey) -> String {
+    hex::encode(public_key.as_bytes())
+}
+
+/// Sign a message and return the hex-encoded signature
+pub fn sign(key: &SigningKey, message: &[u8]) -> String {
+    hex::encode(key.sign(message).to_bytes())
+}
+
+/// Verify a hex signature by the key behind `address`
+pub fn verify(address: &str, message: &[u8], signature: &str) -> Result<()> {
+    let key_bytes: [u8; 32] = hex::decode(address)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow::anyhow!("Address {} is not an ed25519 public key", address))?;
+    let public_key = VerifyingKey::from_bytes(&key_bytes)
+        .map_err(|_| anyhow::anyhow!("Address {} is not a valid ed25519 public key", address))?;
+
+    let signature_bytes: [u8; 64] = hex::decode(signature)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow::anyhow!("Malformed signature"))?;
+
+    public_key.verify(message, &Signature::from_bytes(&signature_bytes))
+        .map_err(|_| anyhow::anyhow!("Invalid signature for {}", address))
+}
@@ -1,12 +1,45 @@
 use anyhow::Result;
 use tokio::sync::mpsc;
 use tracing::{info, debug, warn, error};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
+use serde::{Serialize, Deserialize};
+use ed25519_dalek::SigningKey;
 
+use super::storage::ChainStorage;
+use super::transactions::TransactionProcessor;
+use super::consensus::{self, ConsensusEngine, FinalizedBlock, Validator, Vote};
+use super::fork_choice::{self, BlockTree};
+use super::mempool::{Mempool, PendingTransaction};
+use super::fees::FeeQuote;
+use super::channels::{ChannelBook, ChannelState, ChannelUpdate, PaymentChannel};
+use super::rewards::{EpochReports, RewardStatus};
+use super::slashing::{Evidence, MeasurementBook, QualityMeasurement};
+use super::sessions::{SessionBook, StreamHistory};
+use super::state::StateProof;
+use super::block::SignedHeader;
+use super::pricing::{self, PreviewBook, PricingModel};
+use super::splits::PayoutHistory;
+use super::staking::{self, Stake, StakingStatus};
+use super::crypto;
+use super::genesis::ProtocolParams;
+use crate::network::NetworkCommand;
 use super::{BlockchainConfig, BlockchainEvent, BlockchainCommand, Block, Transaction, Account, StreamRegistration};
 
+/// Most blocks a [`ChainReader`] returns for one range
+const MAX_BLOCK_RANGE: u64 = 128;
+
+/// Most transactions a produced block includes
+const MAX_BLOCK_TRANSACTIONS: usize = 1024;
+
+/// Seconds between scans for expired stream access
+const ACCESS_SCAN_INTERVAL_SECS: u64 = 5;
+
+/// Fewest seconds between two quality reports a validator makes on one stream
+const QUALITY_REPORT_INTERVAL_SECS: u64 = 60;
+
 /// Core blockchain engine that integrates with streaming
 pub struct BlockchainEngine {
     config: BlockchainConfig,
@@ -17,14 +50,101 @@ pub struct BlockchainEngine {
     
     // Blockchain state
     state: Arc<RwLock<BlockchainState>>,
+    storage: Arc<ChainStorage>,
     
     // Block production
     is_validator: bool,
+    validator_key: Option<Arc<SigningKey>>,
+    consensus: Arc<Mutex<Box<dyn ConsensusEngine>>>,
     current_block: u64,
+    
+    // Non-finalized blocks and their states, for fork choice
+    tree: Arc<Mutex<BlockTree>>,
+    
+    // Transactions waiting for a block
+    mempool: Arc<Mutex<Mempool>>,
+    
+    // Latest off-chain payment channel updates
+    channels: Arc<RwLock<ChannelBook>>,
+    
+    // Viewers' signed quality measurements of the current epoch
+    measurements: Arc<RwLock<MeasurementBook>>,
+    
+    // This node's record of stream broadcasts, outside the chain state
+    sessions: Arc<RwLock<SessionBook>>,
+    
+    // Free previews this node gave viewers, outside the chain state
+    previews: Arc<RwLock<PreviewBook>>,
+    
+    // P2P gossip (absent when running without networking)
+    network_tx: Option<mpsc::Sender<NetworkCommand>>,
+    
+    // Sync progress: best block reported by peers, and whether we reached it
+    sync_target: Option<u64>,
+    synced: Arc<AtomicBool>,
+    
+    // Grants already revoked for expiry, so each is revoked once
+    expired_access: HashSet<(String, String)>,
+    
+    // When each stream's quality was last reported, to pace reports
+    quality_reported: HashMap<String, std::time::Instant>,
+    
+    // False quality reports already submitted as evidence, by (reporter, epoch)
+    false_reports_reported: HashSet<(String, u64)>,
+}
+
+/// Read-only view of the local chain, used to answer peers' sync requests
+///
+/// The one thing it writes is node-local and not part of the chain: the
+/// books of payment channel updates and viewer quality measurements. It also
+/// reads the node's stream broadcast history and the free previews it gave.
+#[derive(Clone)]
+pub struct ChainReader {
+    state: Arc<RwLock<BlockchainState>>,
+    storage: Arc<ChainStorage>,
+    mempool: Arc<Mutex<Mempool>>,
+    channels: Arc<RwLock<ChannelBook>>,
+    measurements: Arc<RwLock<MeasurementBook>>,
+    sessions: Arc<RwLock<SessionBook>>,
+    previews: Arc<RwLock<PreviewBook>>,
+    genesis_hash: String,
+}
+
+/// Summary of a node's chain exchanged between peers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainStatus {
+    pub chain_id: String,
+    pub genesis_hash: String,
+    pub best_block: u64,
+    pub best_hash: String,
+    pub finalized_block: u64,
+    pub finalized_hash: String,
+}
+
+/// Handles shared by the block production task and block import
+#[derive(Clone)]
+struct BlockCommitter {
+    storage: Arc<ChainStorage>,
+    consensus: Arc<Mutex<Box<dyn ConsensusEngine>>>,
+    tree: Arc<Mutex<BlockTree>>,
+    mempool: Arc<Mutex<Mempool>>,
+    validator_key: Option<Arc<SigningKey>>,
+    event_tx: mpsc::Sender<BlockchainEvent>,
+    network_tx: Option<mpsc::Sender<NetworkCommand>>,
+}
+
+/// Consensus results of committing a block
+struct CommitOutcome {
+    /// Whether the block became the canonical head
+    is_head: bool,
+    vote: Option<Vote>,
+    finalized: Option<FinalizedBlock>,
+    /// Access grants gained or lost as the canonical chain moved
+    access_events: Vec<BlockchainEvent>,
 }
 
 /// Blockchain state including accounts and streams
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlockchainState {
     /// Account balances and stream access
     pub accounts: HashMap<String, Account>,
@@ -32,13 +152,45 @@ pub struct BlockchainState {
     /// Registered streams
     pub streams: HashMap<String, StreamRegistration>,
     
+    /// Open payment channels by channel id
+    pub channels: HashMap<String, PaymentChannel>,
+    
+    /// Stake registry of validators and relays by address
+    pub stakes: BTreeMap<String, Stake>,
+    
+    /// Validator set of the current epoch, empty on a dev chain
+    pub validators: Vec<Validator>,
+    
+    /// Stream quality reports of the current epoch
+    pub quality_reports: EpochReports,
+    
+    /// Fees set aside for relay rewards, paid out at the end of the epoch
+    pub relay_pool: u64,
+    
     /// Chain metadata
     pub best_block: u64,
+    pub best_hash: String,
     pub finalized_block: u64,
     pub chain_id: String,
     
-    /// Transaction pool
-    pub pending_transactions: Vec<Transaction>,
+    /// Protocol parameters from the genesis spec
+    pub params: ProtocolParams,
+    
+    /// Fee rate the next block requires, in STREAM per unit of weight
+    pub fee_per_weight: u64,
+    
+    /// Total fees burned so far
+    pub burned_fees: u64,
+    
+    /// Total stake burned by slashing
+    pub slashed_stake: u64,
+    
+    /// Double signing already punished, as (validator, block number)
+    pub double_signs: BTreeSet<(String, u64)>,
+    
+    /// Finality reached by votes before the block itself arrived (not persisted)
+    #[serde(skip)]
+    pub pending_finality: Option<FinalizedBlock>,
 }
 
 impl BlockchainEngine {
@@ -49,67 +201,206 @@ impl BlockchainEngine {
     ) -> Result<Self> {
         info!("🔗 Initializing Blockchain Engine");
         
-        // Initialize blockchain state
-        let mut state = BlockchainState {
-            accounts: HashMap::new(),
-            streams: HashMap::new(),
-            best_block: 0,
-            finalized_block: 0,
-            chain_id: "sutantra-testnet".to_string(),
-            pending_transactions: Vec::new(),
-        };
+        let storage = ChainStorage::open(&config.data_dir)?;
         
-        // Create genesis account with initial balance
-        let genesis_account = Account {
-            address: "genesis".to_string(),
-            balance: 1_000_000_000, // 1 billion STREAM tokens
-            nonce: 0,
-            stream_access: HashMap::new(),
-            created_streams: Vec::new(),
+        // Reload persisted state, or start a fresh chain
+        let (genesis_state, genesis) = config.genesis.build();
+        let state = match storage.load_state()? {
+            Some(state) => {
+                let stored = storage.get_block(0)?
+                    .ok_or_else(|| anyhow::anyhow!("Genesis block missing from storage"))?;
+                if stored.hash != genesis.hash {
+                    return Err(anyhow::anyhow!(
+                        "Data directory {} holds chain with genesis {}, but the genesis spec gives {}",
+                        config.data_dir, stored.hash, genesis.hash));
+                }
+                
+                info!("📂 Restored chain state at block #{} ({} accounts, {} streams)",
+                      state.best_block, state.accounts.len(), state.streams.len());
+                state
+            }
+            None => {
+                let state = genesis_state;
+                storage.put_block(&genesis)?;
+                storage.save_finalized_state(&state)?;
+                storage.save_state(&state)?;
+                info!("🌱 Initialized new chain: {} (genesis {})", state.chain_id, genesis.hash);
+                state
+            }
         };
-        state.accounts.insert("genesis".to_string(), genesis_account);
         
         let is_validator = config.is_validator;
+        let validator_key = if is_validator {
+            let key = crypto::load_or_generate_validator_key(&config.data_dir)?;
+            info!("🔑 Validator address: {}", crypto::address_from_public_key(&key.verifying_key()));
+            Some(Arc::new(key))
+        } else {
+            None
+        };
+        let current_block = state.best_block;
+        
+        let tree = Self::restore_block_tree(&storage, &state)?;
+        let mempool = Mempool::new(config.mempool);
+        let sessions = storage.load_sessions()?;
+        let previews = storage.load_previews()?;
+        
+        let local_validator = validator_key.as_ref()
+            .map(|key| crypto::address_from_public_key(&key.verifying_key()));
+        let consensus_config = config.genesis.consensus_config();
+        let mut consensus = consensus::build(&consensus_config, local_validator.as_deref())?;
+        if !state.validators.is_empty() {
+            // A restored chain may have elected a different set since genesis
+            consensus.set_validators(&state.validators);
+        }
+        info!("🗳️  Consensus engine: {} ({}s slots)", consensus.name(), consensus_config.block_time_secs);
+        if let Some(address) = &local_validator {
+            if !consensus.is_validator(address) {
+                warn!("Local validator {} is not in the validator set and will not author blocks", address);
+            }
+        }
         
         Ok(Self {
             config,
             command_rx,
             event_tx,
             state: Arc::new(RwLock::new(state)),
+            storage: Arc::new(storage),
             is_validator,
-            current_block: 0,
+            validator_key,
+            consensus: Arc::new(Mutex::new(consensus)),
+            current_block,
+            tree: Arc::new(Mutex::new(tree)),
+            mempool: Arc::new(Mutex::new(mempool)),
+            channels: Arc::new(RwLock::new(ChannelBook::new())),
+            measurements: Arc::new(RwLock::new(MeasurementBook::new())),
+            sessions: Arc::new(RwLock::new(sessions)),
+            previews: Arc::new(RwLock::new(previews)),
+            network_tx: None,
+            sync_target: None,
+            synced: Arc::new(AtomicBool::new(true)),
+            expired_access: HashSet::new(),
+            quality_reported: HashMap::new(),
+            false_reports_reported: HashSet::new(),
         })
     }
     
+    /// Gossip transactions, blocks and votes through the networking service
+    ///
+    /// Block production pauses until peers have reported their chain tip and
+    /// the local chain has caught up with it.
+    pub fn set_network(&mut self, network_tx: mpsc::Sender<NetworkCommand>) {
+        self.network_tx = Some(network_tx);
+        self.synced.store(false, Ordering::SeqCst);
+    }
+    
+    /// Read-only handle for serving blocks to peers
+    pub fn chain_reader(&self) -> Result<ChainReader> {
+        let genesis = self.storage.get_block(0)?
+            .ok_or_else(|| anyhow::anyhow!("Genesis block missing from storage"))?;
+        
+        Ok(ChainReader {
+            state: Arc::clone(&self.state),
+            storage: Arc::clone(&self.storage),
+            mempool: Arc::clone(&self.mempool),
+            channels: Arc::clone(&self.channels),
+            measurements: Arc::clone(&self.measurements),
+            sessions: Arc::clone(&self.sessions),
+            previews: Arc::clone(&self.previews),
+            genesis_hash: genesis.hash,
+        })
+    }
+    
+    /// Chain identifier of the loaded chain
+    pub async fn chain_id(&self) -> String {
+        self.state.read().await.chain_id.clone()
+    }
+    
+    fn committer(&self) -> BlockCommitter {
+        BlockCommitter {
+            storage: Arc::clone(&self.storage),
+            consensus: Arc::clone(&self.consensus),
+            tree: Arc::clone(&self.tree),
+            mempool: Arc::clone(&self.mempool),
+            validator_key: self.validator_key.clone(),
+            event_tx: self.event_tx.clone(),
+            network_tx: self.network_tx.clone(),
+        }
+    }
+    
+    /// Rebuild the tree of non-finalized blocks by replaying them from the finalized state
+    fn restore_block_tree(storage: &ChainStorage, state: &BlockchainState) -> Result<BlockTree> {
+        let Some(mut parent_state) = storage.load_finalized_state()? else {
+            // Older data directories have no finalized snapshot; start at the tip
+            let tip = storage.get_block(state.best_block)?
+                .ok_or_else(|| anyhow::anyhow!("Block #{} missing from storage", state.best_block))?;
+            return Ok(BlockTree::new(tip, state.clone()));
+        };
+        
+        let root_number = parent_state.best_block;
+        let root = storage.get_block(root_number)?
+            .ok_or_else(|| anyhow::anyhow!("Finalized block #{} missing from storage", root_number))?;
+        let mut tree = BlockTree::new(root, parent_state.clone());
+        
+        for number in root_number + 1..=state.best_block {
+            let block = storage.get_block(number)?
+                .ok_or_else(|| anyhow::anyhow!("Block #{} missing from storage", number))?;
+            
+            let mut next = parent_state;
+            TransactionProcessor::apply_block(&mut next, &block)?;
+            next.best_block = block.number;
+            next.best_hash = block.hash.clone();
+            
+            tree.insert(block, next.clone())?;
+            parent_state = next;
+        }
+        
+        debug!("🌳 Restored {} non-finalized blocks", state.best_block - root_number);
+        Ok(tree)
+    }
+    
     pub async fn run(&mut self) -> Result<()> {
         info!("🚀 Starting Blockchain Engine");
         info!("📁 Data directory: {}", self.config.data_dir);
         info!("⚡ Validator mode: {}", self.is_validator);
         
         // Start block production if validator
-        let block_production_handle = if self.is_validator {
+        let block_production_handle = if let Some(key) = &self.validator_key {
             let state = Arc::clone(&self.state);
-            let event_tx = self.event_tx.clone();
+            let committer = self.committer();
+            let block_time_secs = self.config.genesis.consensus.block_time_secs;
+            let key = Arc::clone(key);
+            let synced = Arc::clone(&self.synced);
             Some(tokio::spawn(async move {
-                Self::block_production_loop(state, event_tx).await;
+                Self::block_production_loop(state, committer, key, synced, block_time_secs).await;
             }))
         } else {
             None
         };
         
+        let mut access_scan = tokio::time::interval(tokio::time::Duration::from_secs(ACCESS_SCAN_INTERVAL_SECS));
+        
         // Main command processing loop
         loop {
             tokio::select! {
                 // Process commands from integration layer
-                Some(command) = self.command_rx.recv() => {
+                command = self.command_rx.recv() => {
+                    let Some(command) = command else {
+                        warn!("Blockchain command channel closed");
+                        break;
+                    };
                     if let Err(e) = self.handle_command(command).await {
                         error!("Error handling blockchain command: {}", e);
                     }
                 }
                 
-                else => {
-                    warn!("Blockchain command channel closed");
-                    break;
+                // Revoke grants whose paid time ran out
+                _ = access_scan.tick() => {
+                    if let Err(e) = self.revoke_expired_access().await {
+                        error!("Error scanning for expired access: {}", e);
+                    }
+                    if let Err(e) = self.report_false_quality().await {
+                        error!("Error reporting false quality reports: {}", e);
+                    }
                 }
             }
         }
@@ -122,22 +413,8 @@ impl BlockchainEngine {
         Ok(())
     }
     
-    async fn handle_command(&self, command: BlockchainCommand) -> Result<()> {
+    async fn handle_command(&mut self, command: BlockchainCommand) -> Result<()> {
         match command {
-            BlockchainCommand::RegisterStream { 
-                stream_id, 
-                creator, 
-                title, 
-                description, 
-                price_per_minute 
-            } => {
-                self.register_stream(stream_id, creator, title, description, price_per_minute).await?;
-            }
-            
-            BlockchainCommand::ProcessPayment { stream_id, viewer, amount } => {
-                self.process_payment(stream_id, viewer, amount).await?;
-            }
-            
             BlockchainCommand::CheckAccess { stream_id, viewer } => {
                 self.check_access(stream_id, viewer).await?;
             }
@@ -146,268 +423,1007 @@ impl BlockchainEngine {
                 self.record_stream_start(stream_id, timestamp).await?;
             }
             
+            BlockchainCommand::RecordStreamEnd { stream_id, timestamp, duration_seconds } => {
+                self.record_stream_end(stream_id, timestamp, duration_seconds).await?;
+            }
+            
+            BlockchainCommand::RecordViewerJoin { stream_id, viewer } => {
+                self.record_viewer_join(stream_id, viewer).await?;
+            }
+            
             BlockchainCommand::ReportStreamQuality { stream_id, metrics } => {
                 self.record_stream_quality(stream_id, metrics).await?;
             }
             
-            BlockchainCommand::Transfer { from, to, amount } => {
-                self.transfer_tokens(from, to, amount).await?;
+            BlockchainCommand::SubmitTransaction { transaction } => {
+                self.submit_transaction(transaction).await?;
+            }
+            
+            BlockchainCommand::SubmitVote { vote } => {
+                self.submit_vote(vote).await?;
+            }
+            
+            BlockchainCommand::ImportBlock { block } => {
+                let result = self.import_block(block).await;
+                self.update_sync_status().await?;
+                result?;
             }
             
-            _ => {
-                debug!("Unhandled blockchain command: {:?}", command);
+            BlockchainCommand::UpdateSyncTarget { best_block } => {
+                self.sync_target = Some(best_block);
+                self.update_sync_status().await?;
             }
         }
         
         Ok(())
     }
     
-    async fn register_stream(
-        &self,
-        stream_id: String,
-        creator: String,
-        title: String,
-        description: Option<String>,
-        price_per_minute: u64,
-    ) -> Result<()> {
-        info!("📝 Registering stream: {} by {}", stream_id, creator);
+    async fn check_access(&self, stream_id: String, viewer: String) -> Result<()> {
+        debug!("🔍 Checking access: {} for stream {}", viewer, stream_id);
         
-        let mut state = self.state.write().await;
+        let state = self.state.read().await;
+        let mut previews = self.previews.write().await;
+        let now = chrono::Utc::now();
         
-        // Check if stream already exists
-        if state.streams.contains_key(&stream_id) {
-            warn!("Stream {} already exists", stream_id);
-            return Ok(());
+        // Free streams need no grant; paid ones may start with a free preview
+        let mut is_free = false;
+        if let Some(stream) = state.streams.get(&stream_id) {
+            is_free = stream.pricing == PricingModel::Free;
+            if previews.grant(stream, &viewer, state.accounts.get(&viewer), now) {
+                debug!("🎟️  {} is previewing stream {}", viewer, stream_id);
+                self.storage.save_previews(&previews)?;
+            }
         }
         
-        // Create stream registration
-        let registration = StreamRegistration {
-            stream_id: stream_id.clone(),
-            creator: creator.clone(),
-            title,
-            description,
-            price_per_minute,
-            created_at: chrono::Utc::now(),
-            is_active: false,
-            total_earnings: 0,
-            total_viewers: 0,
-            total_duration_minutes: 0,
-        };
-        
-        // Add to state
-        state.streams.insert(stream_id.clone(), registration);
-        
-        // Update creator's account
-        state.accounts.entry(creator.clone())
-            .or_insert_with(|| Account {
-                address: creator.clone(),
-                balance: 0,
-                nonce: 0,
-                stream_access: HashMap::new(),
-                created_streams: Vec::new(),
-            })
-            .created_streams.push(stream_id.clone());
+        let channels = self.channels.read().await;
+        let has_access = is_free || access_until(&state, &channels, &previews, &viewer, &stream_id)
+            .is_some_and(|paid_until| paid_until + self.access_grace() > now);
+        drop(channels);
+        drop(previews);
+        drop(state);
         
-        // Emit event
-        self.event_tx.send(BlockchainEvent::StreamRegistered { 
-            stream_id, 
-            creator 
-        }).await?;
+        if has_access {
+            self.event_tx.send(BlockchainEvent::AccessGranted { 
+                stream_id, 
+                viewer 
+            }).await?;
+        } else {
+            self.event_tx.send(BlockchainEvent::AccessRevoked { 
+                stream_id, 
+                viewer,
+                reason: "Payment required".to_string(),
+            }).await?;
+        }
         
         Ok(())
     }
     
-    async fn process_payment(&self, stream_id: String, viewer: String, amount: u64) -> Result<()> {
-        info!("💳 Processing payment: {} STREAM from {} for {}", amount, viewer, stream_id);
+    /// Emit `AccessRevoked` once for every grant whose paid time and grace period have passed
+    ///
+    /// Paid time counts both on-chain grants and payment channel updates. A
+    /// grant that is paid up again is tracked afresh, so it is revoked again
+    /// when the new paid time runs out.
+    async fn revoke_expired_access(&mut self) -> Result<()> {
+        let cutoff = chrono::Utc::now() - self.access_grace();
         
-        let mut state = self.state.write().await;
+        let state = self.state.read().await;
+        let mut channels = self.channels.write().await;
+        channels.prune(&state);
+        let previews = self.previews.read().await;
+        let expired = expired_access(&state, &channels, &previews, cutoff);
+        drop(previews);
+        drop(channels);
+        drop(state);
         
-        // Get stream info (clone to avoid borrow conflicts)
-        let stream_info = state.streams.get(&stream_id).cloned().ok_or_else(|| {
-            anyhow::anyhow!("Stream {} not found", stream_id)
-        })?;
+        let newly_expired: Vec<_> = expired.difference(&self.expired_access).cloned().collect();
+        self.expired_access = expired;
         
-        let creator = stream_info.creator.clone();
-        let price_per_minute = stream_info.price_per_minute;
+        for (stream_id, viewer) in newly_expired {
+            debug!("⌛ Access of {} to {} expired", viewer, stream_id);
+            self.event_tx.send(BlockchainEvent::AccessRevoked {
+                stream_id,
+                viewer,
+                reason: "Access expired".to_string(),
+            }).await?;
+        }
         
-        // Check viewer balance
-        let viewer_account = state.accounts.get_mut(&viewer).ok_or_else(|| {
-            anyhow::anyhow!("Viewer account {} not found", viewer)
-        })?;
+        Ok(())
+    }
+    
+    fn access_grace(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.config.access_grace_secs as i64)
+    }
+    
+    async fn record_stream_start(&self, stream_id: String, timestamp: chrono::DateTime<chrono::Utc>) -> Result<()> {
+        debug!("🎬 Recording stream start: {}", stream_id);
         
-        if viewer_account.balance < amount {
-            warn!("Insufficient balance for viewer {}: {} < {}", viewer, viewer_account.balance, amount);
+        let Some(total_earnings) = self.registered_earnings(&stream_id).await else {
             return Ok(());
+        };
+        let mut sessions = self.sessions.write().await;
+        if !sessions.start_session(&stream_id, timestamp, total_earnings) {
+            debug!("Stream {} is already live", stream_id);
         }
         
-        // Transfer tokens
-        viewer_account.balance -= amount;
-        viewer_account.nonce += 1;
+        self.storage.save_sessions(&sessions)?;
         
-        // Credit creator (drop viewer_account reference first)
-        drop(viewer_account);
-        
-        state.accounts.entry(creator.clone())
-            .or_insert_with(|| Account {
-                address: creator.clone(),
-                balance: 0,
-                nonce: 0,
-                stream_access: HashMap::new(),
-                created_streams: Vec::new(),
-            })
-            .balance += amount;
+        Ok(())
+    }
+    
+    async fn record_stream_end(
+        &self,
+        stream_id: String,
+        timestamp: chrono::DateTime<chrono::Utc>,
+        duration_seconds: u64,
+    ) -> Result<()> {
+        debug!("🏁 Recording stream end: {}", stream_id);
         
-        // Get viewer account again for access grant
-        let viewer_account = state.accounts.get_mut(&viewer).unwrap();
+        let Some(total_earnings) = self.registered_earnings(&stream_id).await else {
+            return Ok(());
+        };
+        let mut sessions = self.sessions.write().await;
+        match sessions.end_session(&stream_id, timestamp, duration_seconds, total_earnings) {
+            Some(session) => info!("🏁 Stream {} ended after {}s with {} viewers, earning {} STREAM",
+                                   stream_id, session.duration_seconds, session.viewers.len(), session.earnings),
+            None => debug!("Stream {} was not live", stream_id),
+        }
         
-        // Grant access (payment for 1 minute per 10 STREAM tokens for simplicity)
-        let access_duration_minutes = amount / price_per_minute;
-        let access_until = chrono::Utc::now() + chrono::Duration::minutes(access_duration_minutes as i64);
+        self.storage.save_sessions(&sessions)?;
         
-        viewer_account.stream_access.insert(stream_id.clone(), super::StreamAccess {
-            stream_id: stream_id.clone(),
-            paid_until: access_until,
-            total_paid: amount,
-            access_granted_at: chrono::Utc::now(),
-        });
+        Ok(())
+    }
+    
+    async fn record_viewer_join(&self, stream_id: String, viewer: String) -> Result<()> {
+        let mut sessions = self.sessions.write().await;
         
-        // Update stream earnings
-        if let Some(stream) = state.streams.get_mut(&stream_id) {
-            stream.total_earnings += amount;
+        if let Some(total_viewers) = sessions.record_viewer(&stream_id, &viewer) {
+            debug!("👤 New viewer {} for stream {} ({} total)", viewer, stream_id, total_viewers);
         }
         
-        // Emit event
-        self.event_tx.send(BlockchainEvent::PaymentProcessed { 
-            stream_id, 
-            viewer, 
-            amount 
-        }).await?;
+        self.storage.save_sessions(&sessions)?;
         
         Ok(())
     }
     
-    async fn check_access(&self, stream_id: String, viewer: String) -> Result<()> {
-        debug!("🔍 Checking access: {} for stream {}", viewer, stream_id);
+    /// On-chain earnings of a registered stream; broadcasts of other streams are not recorded
+    async fn registered_earnings(&self, stream_id: &str) -> Option<u64> {
+        self.state.read().await.streams.get(stream_id).map(|stream| stream.total_earnings)
+    }
+    
+    /// Turn locally measured stream quality into a signed `ReportQuality` transaction
+    ///
+    /// Only validators report, at most once per stream every
+    /// `QUALITY_REPORT_INTERVAL_SECS`. The metrics describe the media this
+    /// node receives for the stream, and creators publish straight to the
+    /// nodes serving their viewers, so the relay reported on is always the
+    /// stream's creator. Reports only pay out once `min_reporters` validators
+    /// agree on them.
+    async fn record_stream_quality(&mut self, stream_id: String, metrics: crate::integration::StreamQualityMetrics) -> Result<()> {
+        debug!("📊 Recording stream quality for: {}", stream_id);
         
-        let state = self.state.read().await;
+        let Some(key) = self.validator_key.clone() else {
+            return Ok(());
+        };
+        let now = std::time::Instant::now();
+        let interval = std::time::Duration::from_secs(QUALITY_REPORT_INTERVAL_SECS);
+        if self.quality_reported.get(&stream_id).is_some_and(|last| now.duration_since(*last) < interval) {
+            return Ok(());
+        }
         
-        let has_access = if let Some(account) = state.accounts.get(&viewer) {
-            if let Some(access) = account.stream_access.get(&stream_id) {
-                access.paid_until > chrono::Utc::now()
-            } else {
-                false
+        let transaction = {
+            let state = self.state.read().await;
+            let Some(stream) = state.streams.get(&stream_id) else {
+                debug!("Not reporting quality of unregistered stream {}", stream_id);
+                return Ok(());
+            };
+            let validator = crypto::address_from_public_key(&key.verifying_key());
+            if stream.creator == validator {
+                return Ok(());
             }
-        } else {
-            false
+            
+            let nonce = self.mempool.lock().await.next_nonce(&state, &validator);
+            let report = |fee| Transaction::ReportQuality {
+                validator: validator.clone(),
+                relay: stream.creator.clone(),
+                stream_id: stream_id.clone(),
+                metrics: metrics.clone(),
+                nonce,
+                fee,
+                signature: String::new(),
+            };
+            let mut transaction = report(report(0).min_fee(state.fee_per_weight));
+            transaction.sign(&key, &state.chain_id);
+            transaction
         };
         
-        if has_access {
-            self.event_tx.send(BlockchainEvent::AccessGranted { 
-                stream_id, 
-                viewer 
-            }).await?;
-        } else {
-            self.event_tx.send(BlockchainEvent::AccessRevoked { 
-                stream_id, 
-                viewer,
-                reason: "Payment required".to_string(),
-            }).await?;
+        self.quality_reported.insert(stream_id, now);
+        self.submit_transaction(transaction).await
+    }
+    
+    /// Submit evidence against quality reports that viewers' measurements contradict
+    ///
+    /// Only validators submit, once per offender and epoch; the first
+    /// evidence to land strikes the offender's other reports anyway.
+    async fn report_false_quality(&mut self) -> Result<()> {
+        if self.validator_key.is_none() {
+            return Ok(());
         }
         
+        let (epoch, contradictions) = {
+            let state = self.state.read().await;
+            let contradictions = self.measurements.read().await.contradictions(&state);
+            (state.quality_reports.epoch, contradictions)
+        };
+        self.false_reports_reported.retain(|(_, reported_epoch)| *reported_epoch == epoch);
+        
+        for evidence in contradictions {
+            if self.false_reports_reported.insert((evidence.offender().to_string(), epoch)) {
+                self.report_misbehavior(evidence).await?;
+            }
+        }
         Ok(())
     }
     
-    async fn record_stream_start(&self, stream_id: String, _timestamp: chrono::DateTime<chrono::Utc>) -> Result<()> {
-        debug!("🎬 Recording stream start: {}", stream_id);
+    /// Sign evidence of misbehavior with the validator key and submit it
+    async fn report_misbehavior(&self, evidence: Evidence) -> Result<()> {
+        let Some(key) = self.validator_key.clone() else {
+            return Ok(());
+        };
         
+        let transaction = {
+            let state = self.state.read().await;
+            let reporter = crypto::address_from_public_key(&key.verifying_key());
+            if reporter == evidence.offender() {
+                return Ok(());
+            }
+            
+            let nonce = self.mempool.lock().await.next_nonce(&state, &reporter);
+            let report = |fee| Transaction::ReportMisbehavior {
+                reporter: reporter.clone(),
+                evidence: evidence.clone(),
+                nonce,
+                fee,
+                signature: String::new(),
+            };
+            let mut transaction = report(report(0).min_fee(state.fee_per_weight));
+            transaction.sign(&key, &state.chain_id);
+            transaction
+        };
+        
+        warn!("⚔️  Reporting misbehavior by {}", evidence.offender());
+        self.submit_transaction(transaction).await
+    }
+    
+    /// Validate a signed transaction and add it to the pool
+    async fn submit_transaction(&self, transaction: Transaction) -> Result<()> {
+        let state = self.state.read().await;
+        
+        transaction.verify_signature(&state.chain_id)?;
+        let mut mempool = self.mempool.lock().await;
+        mempool.insert(&state, transaction.clone())?;
+        let pooled = mempool.len();
+        drop(mempool);
+        drop(state);
+        
+        debug!("📥 Accepted transaction from {} (nonce {}, {} pooled)", transaction.sender(), transaction.nonce(), pooled);
+        
+        if let Some(network_tx) = &self.network_tx {
+            network_tx.send(NetworkCommand::Transaction { transaction }).await?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Verify a validator's finality vote and apply any finality it reaches
+    async fn submit_vote(&self, vote: Vote) -> Result<()> {
         let mut state = self.state.write().await;
+        vote.verify(&state.chain_id)?;
+        
+        let known = self.tree.lock().await.number(&vote.block_hash) == Some(vote.block_number);
+        let mut consensus = self.consensus.lock().await;
+        if !consensus.is_validator(&vote.validator) {
+            return Err(anyhow::anyhow!("Vote from unknown validator {}", vote.validator));
+        }
+        
+        debug!("🗳️  Vote from {} for block #{}", vote.validator, vote.block_number);
+        let finalized = consensus.on_vote(&vote, known);
+        drop(consensus);
+        
+        let Some(finalized) = finalized else {
+            return Ok(());
+        };
         
-        if let Some(stream) = state.streams.get_mut(&stream_id) {
-            stream.is_active = true;
+        let committer = self.committer();
+        let mut tree = self.tree.lock().await;
+        let mut mempool = self.mempool.lock().await;
+        let mut events = Vec::new();
+        if committer.apply_finality(&mut state, &mut tree, &mut mempool, &finalized, &mut events)? {
+            self.storage.save_state(&state)?;
+            drop(mempool);
+            drop(tree);
+            drop(state);
+            
+            self.event_tx.send(BlockchainEvent::BlockFinalized {
+                block_number: finalized.number,
+                block_hash: finalized.hash,
+            }).await?;
+            for event in events {
+                self.event_tx.send(event).await?;
+            }
         }
         
         Ok(())
     }
     
-    async fn record_stream_quality(&self, stream_id: String, _metrics: crate::integration::StreamQualityMetrics) -> Result<()> {
-        debug!("📊 Recording stream quality for: {}", stream_id);
+    /// Validate a block received from a peer and add it to the block tree
+    ///
+    /// The block may extend the canonical chain, start or grow a competing
+    /// branch, or trigger a reorganization when its branch becomes the best.
+    async fn import_block(&self, block: Block) -> Result<()> {
+        let mut state = self.state.write().await;
+        
+        let (parent_number, parent_time, parent_state) = {
+            let tree = self.tree.lock().await;
+            if tree.contains(&block.hash) || block.number <= tree.root_number() {
+                debug!("Ignoring known block #{} ({})", block.number, block.hash);
+                return Ok(());
+            }
+            
+            match (tree.block(&block.parent_hash), tree.state(&block.parent_hash)) {
+                (Some(parent), Some(parent_state)) => (parent.number, parent.timestamp, parent_state.clone()),
+                _ => {
+                    // Sync fetches the missing ancestors; this block will arrive again in order
+                    debug!("Block #{} has unknown parent {}", block.number, block.parent_hash);
+                    return Ok(());
+                }
+            }
+        };
+        
+        // Fork choice follows the highest number, so it must count real blocks
+        if block.number != parent_number + 1 {
+            return Err(anyhow::anyhow!("Block #{} does not follow its parent #{}", block.number, parent_number));
+        }
+        
+        self.verify_block(&state, parent_time, &block).await?;
+        
+        // Execute on the parent's state so a failing block changes nothing
+        let mut next = parent_state;
+        TransactionProcessor::apply_block(&mut next, &block)?;
+        if next.state_root() != block.state_root {
+            return Err(anyhow::anyhow!("State root mismatch in block #{}", block.number));
+        }
+        
+        next.best_block = block.number;
+        next.best_hash = block.hash.clone();
         
-        // TODO: Use quality metrics for validator rewards
-        // For now, just acknowledge
+        let committer = self.committer();
+        let outcome = committer.commit(&mut state, &block, next).await?;
+        drop(state);
+        
+        if outcome.is_head {
+            info!("📦 Imported block #{} ({}) from {}", block.number, block.hash, block.validator);
+        } else {
+            info!("🌿 Imported side-chain block #{} ({}) from {}", block.number, block.hash, block.validator);
+        }
+        
+        // Two blocks by one author on one parent are a double sign
+        let conflicting = self.tree.lock().await.conflicting(&block).map(Block::header);
+        let header = block.header();
+        committer.announce(block, outcome, false).await?;
+        if let Some(first) = conflicting {
+            warn!("⚔️  {} signed two blocks at #{}", header.validator, header.number);
+            self.report_misbehavior(Evidence::DoubleSign { first: Box::new(first), second: Box::new(header) }).await?;
+        }
         
         Ok(())
     }
     
-    async fn transfer_tokens(&self, from: String, to: String, amount: u64) -> Result<()> {
-        info!("💸 Transfer: {} STREAM from {} to {}", amount, from, to);
+    /// Emit `SyncStatusChanged` when the chain reaches or falls behind the sync target
+    async fn update_sync_status(&self) -> Result<()> {
+        let Some(target) = self.sync_target else {
+            return Ok(());
+        };
         
-        let mut state = self.state.write().await;
+        let best_block = self.state.read().await.best_block;
+        let is_synced = best_block >= target;
+        if self.synced.swap(is_synced, Ordering::SeqCst) == is_synced {
+            return Ok(());
+        }
         
-        // Get sender account
-        let sender = state.accounts.get_mut(&from).ok_or_else(|| {
-            anyhow::anyhow!("Sender account {} not found", from)
-        })?;
+        if is_synced {
+            info!("✅ Chain synced at block #{}", best_block);
+        } else {
+            info!("🔄 Syncing from block #{} to #{}", best_block, target);
+        }
+        self.event_tx.send(BlockchainEvent::SyncStatusChanged { is_synced, best_block }).await?;
         
-        if sender.balance < amount {
-            return Err(anyhow::anyhow!("Insufficient balance"));
+        Ok(())
+    }
+    
+    /// Check a block's hash, timestamp, author and transaction signatures
+    async fn verify_block(
+        &self,
+        state: &BlockchainState,
+        parent_time: chrono::DateTime<chrono::Utc>,
+        block: &Block,
+    ) -> Result<()> {
+        if block.compute_hash() != block.hash {
+            return Err(anyhow::anyhow!("Block #{} has an invalid hash", block.number));
+        }
+        
+        // Grants, dispute windows, unbonding and jail all run on block time
+        if block.timestamp <= parent_time {
+            return Err(anyhow::anyhow!("Block #{} is not later than its parent", block.number));
         }
         
-        // Deduct from sender
-        sender.balance -= amount;
-        sender.nonce += 1;
+        let max_drift = chrono::Duration::seconds(self.config.genesis.consensus.block_time_secs as i64);
+        if block.timestamp > chrono::Utc::now() + max_drift {
+            return Err(anyhow::anyhow!("Block #{} is from the future", block.number));
+        }
         
-        // Credit receiver
-        state.accounts.entry(to.clone())
-            .or_insert_with(|| Account {
-                address: to.clone(),
-                balance: 0,
-                nonce: 0,
-                stream_access: HashMap::new(),
-                created_streams: Vec::new(),
-            })
-            .balance += amount;
+        consensus::verify_block_author(
+            self.consensus.lock().await.as_ref(),
+            block,
+            self.config.genesis.consensus.block_time_secs,
+        )?;
+        
+        for tx in &block.transactions {
+            tx.verify_signature(&state.chain_id)?;
+        }
         
         Ok(())
     }
     
     /// Block production loop for validators
+    ///
+    /// Wakes at every slot boundary and authors a block when the consensus
+    /// engine names the local validator as the slot leader.
     async fn block_production_loop(
         state: Arc<RwLock<BlockchainState>>,
-        event_tx: mpsc::Sender<BlockchainEvent>,
+        committer: BlockCommitter,
+        key: Arc<SigningKey>,
+        synced: Arc<AtomicBool>,
+        block_time_secs: u64,
     ) {
         info!("⛏️  Starting block production");
         
-        let mut block_number = 1u64;
+        let address = crypto::address_from_public_key(&key.verifying_key());
+        let slot_millis = block_time_secs.max(1) * 1000;
         
         loop {
-            tokio::time::sleep(tokio::time::Duration::from_secs(6)).await; // 6 second blocks
+            // Sleep until the start of the next slot
+            let now_millis = chrono::Utc::now().timestamp_millis().max(0) as u64;
+            tokio::time::sleep(tokio::time::Duration::from_millis(slot_millis - now_millis % slot_millis)).await;
             
-            let block_hash = format!("block_{}", block_number);
+            // Authoring on a stale tip would fork the chain
+            if !synced.load(Ordering::SeqCst) {
+                debug!("⏳ Waiting for sync before producing blocks");
+                continue;
+            }
             
-            // Update state
-            {
-                let mut state = state.write().await;
-                state.best_block = block_number;
-                state.finalized_block = block_number.saturating_sub(1);
+            let slot = consensus::slot_at(chrono::Utc::now(), block_time_secs);
+            let is_leader = committer.consensus.lock().await
+                .slot_leader(slot)
+                .is_some_and(|leader| leader.address == address);
+            if !is_leader {
+                debug!("⏭️  Not the leader for slot {}", slot);
+                continue;
             }
             
-            // Emit block produced event
-            if let Err(e) = event_tx.send(BlockchainEvent::BlockProduced { 
-                block_number, 
-                block_hash 
-            }).await {
-                error!("Failed to send block produced event: {}", e);
+            let (block, outcome) = {
+                let mut state = state.write().await;
+                let ready = committer.mempool.lock().await.ready(&state, MAX_BLOCK_TRANSACTIONS);
+                let (block, next) = match Self::assemble_block(&state, ready, &key) {
+                    Ok(assembled) => assembled,
+                    Err(e) => {
+                        error!("Failed to assemble block #{}: {}", state.best_block + 1, e);
+                        continue;
+                    }
+                };
+                
+                match committer.commit(&mut state, &block, next).await {
+                    Ok(outcome) => (block, outcome),
+                    Err(e) => {
+                        error!("Failed to persist block #{}: {}", block.number, e);
+                        break;
+                    }
+                }
+            };
+            
+            debug!("🧱 Produced block #{} ({}) with {} transactions",
+                   block.number, block.hash, block.transactions.len());
+            
+            if let Err(e) = committer.announce(block, outcome, true).await {
+                error!("Failed to announce block: {}", e);
                 break;
             }
-            
-            debug!("🧱 Produced block #{}", block_number);
-            block_number += 1;
         }
     }
     
+    /// Build a new block on top of the current tip from ready pool transactions
+    ///
+    /// Returns the block and the state after it. Included transactions leave
+    /// the pool once the block is committed, and their fees go to `key`.
+    fn assemble_block(state: &BlockchainState, ready: Vec<Transaction>, key: &SigningKey) -> Result<(Block, BlockchainState)> {
+        let number = state.best_block + 1;
+        let timestamp = chrono::Utc::now();
+        let max_weight = state.params.fees.max_block_weight();
+        
+        // Apply in pool order; a left-out transaction also holds back the sender's later nonces
+        let mut next = state.clone();
+        let mut included = Vec::with_capacity(ready.len());
+        let mut weight = 0u64;
+        let mut skipped = HashSet::new();
+        for tx in ready {
+            if skipped.contains(tx.sender()) {
+                continue;
+            }
+            if weight + tx.weight() > max_weight {
+                skipped.insert(tx.sender().to_string());
+                continue;
+            }
+            match TransactionProcessor::apply(&mut next, &tx, timestamp) {
+                Ok(()) => {
+                    weight += tx.weight();
+                    included.push(tx);
+                }
+                Err(e) => {
+                    warn!("Leaving transaction out of block #{}: {}", number, e);
+                    skipped.insert(tx.sender().to_string());
+                }
+            }
+        }
+        
+        let author = crypto::address_from_public_key(&key.verifying_key());
+        TransactionProcessor::finish_block(&mut next, &included, &author, timestamp)?;
+        
+        let block = Block::new_signed(
+            number,
+            next.best_hash.clone(),
+            timestamp,
+            included,
+            next.state_root(),
+            key,
+        );
+        
+        next.best_block = number;
+        next.best_hash = block.hash.clone();
+        
+        Ok((block, next))
+    }
+    
     /// Get current blockchain status
     pub async fn get_status(&self) -> (u64, u64, u32) {
         let state = self.state.read().await;
-        (state.best_block, state.finalized_block, state.pending_transactions.len() as u32)
+        (state.best_block, state.finalized_block, self.mempool.lock().await.len() as u32)
+    }
+}
+
+impl BlockCommitter {
+    /// Add a block and its post-state to the tree, run fork choice and consensus, and persist
+    ///
+    /// The local validator votes for the block when finality is vote-based
+    /// and the block became the canonical head.
+    async fn commit(
+        &self,
+        state: &mut BlockchainState,
+        block: &Block,
+        post_state: BlockchainState,
+    ) -> Result<CommitOutcome> {
+        let mut tree = self.tree.lock().await;
+        tree.insert(block.clone(), post_state)?;
+        let mut mempool = self.mempool.lock().await;
+        
+        let mut access_events = Vec::new();
+        let head = tree.best_head(&state.best_hash);
+        if head != state.best_hash {
+            self.switch_head(state, &tree, &mut mempool, &head, &mut access_events)?;
+        }
+        let is_head = state.best_hash == block.hash;
+        
+        let mut consensus = self.consensus.lock().await;
+        if !state.validators.is_empty() {
+            // Follow the validator set elected on the canonical chain
+            consensus.set_validators(&state.validators);
+        }
+        let mut finalized = consensus.on_block(block);
+        
+        let vote = self.validator_key.as_ref()
+            .filter(|key| {
+                let address = crypto::address_from_public_key(&key.verifying_key());
+                is_head && consensus.uses_votes() && consensus.is_validator(&address)
+            })
+            .map(|key| Vote::new_signed(block, key, &state.chain_id));
+        if let Some(vote) = &vote {
+            finalized = consensus.on_vote(vote, true).or(finalized);
+        }
+        drop(consensus);
+        
+        // Finality that arrived before its block can apply now
+        let finalized = finalized.or_else(|| {
+            state.pending_finality.take_if(|pending| tree.contains(&pending.hash))
+        });
+        let finalized = match finalized {
+            Some(finalized) if self.apply_finality(state, &mut tree, &mut mempool, &finalized, &mut access_events)? => Some(finalized),
+            _ => None,
+        };
+        
+        self.storage.save_state(state)?;
+        
+        Ok(CommitOutcome { is_head, vote, finalized, access_events })
+    }
+    
+    /// Move the canonical chain to `head`, rolling back and re-applying state
+    ///
+    /// The pool drops transactions the new head consumed or invalidated, and
+    /// takes back retracted ones the new branch does not include.
+    fn switch_head(
+        &self,
+        state: &mut BlockchainState,
+        tree: &BlockTree,
+        mempool: &mut Mempool,
+        head: &str,
+        events: &mut Vec<BlockchainEvent>,
+    ) -> Result<()> {
+        let change = tree.route(&state.best_hash, head);
+        let mut next = tree.state(head)
+            .ok_or_else(|| anyhow::anyhow!("Head {} is not in the block tree", head))?
+            .clone();
+        
+        if !change.retracted.is_empty() {
+            warn!("🔀 Reorganizing: retracting {} blocks from #{}, enacting {} blocks to #{}",
+                  change.retracted.len(), state.best_block, change.enacted.len(), next.best_block);
+        }
+        
+        // Node-local fields follow the node, not the branch
+        next.finalized_block = state.finalized_block;
+        next.pending_finality = state.pending_finality.take();
+        
+        // Blocks go to disk before the snapshot that points at them
+        for block in &change.enacted {
+            self.storage.put_block(block)?;
+        }
+        
+        events.extend(fork_choice::access_changes(state, &next));
+        *state = next;
+        
+        // Streams register and viewers pay only through signed transactions on the canonical chain
+        for tx in change.enacted.iter().flat_map(|block| &block.transactions) {
+            match tx {
+                Transaction::RegisterStream { creator, stream_data, .. } => {
+                    events.push(BlockchainEvent::StreamRegistered {
+                        stream_id: stream_data.stream_id.clone(),
+                        creator: creator.clone(),
+                    });
+                }
+                Transaction::PurchaseAccess { viewer, stream_id, amount, .. } => {
+                    events.push(BlockchainEvent::PaymentProcessed {
+                        stream_id: stream_id.clone(),
+                        viewer: viewer.clone(),
+                        amount: *amount,
+                    });
+                }
+                _ => {}
+            }
+        }
+        
+        let dropped = mempool.prune(state);
+        if dropped > 0 {
+            debug!("🧹 Dropped {} pooled transactions at block #{}", dropped, state.best_block);
+        }
+        
+        // Retracted transactions go back to the pool unless the new branch has them
+        for tx in change.retracted.iter().flat_map(|block| &block.transactions) {
+            if let Err(e) = mempool.insert(state, tx.clone()) {
+                debug!("Not returning retracted transaction to the pool: {}", e);
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Finalize a block, pruning branches that conflict with it
+    ///
+    /// Finality never moves backwards. A block that is not in the tree yet
+    /// is remembered and finalized once it is imported.
+    fn apply_finality(
+        &self,
+        state: &mut BlockchainState,
+        tree: &mut BlockTree,
+        mempool: &mut Mempool,
+        finalized: &FinalizedBlock,
+        events: &mut Vec<BlockchainEvent>,
+    ) -> Result<bool> {
+        if finalized.number <= state.finalized_block {
+            return Ok(false);
+        }
+        if !tree.finalize(&finalized.hash) {
+            debug!("Block #{} finalized before it was imported", finalized.number);
+            if state.pending_finality.as_ref().is_none_or(|pending| pending.number < finalized.number) {
+                state.pending_finality = Some(finalized.clone());
+            }
+            return Ok(false);
+        }
+        
+        state.finalized_block = finalized.number;
+        info!("🔒 Finalized block #{} ({})", finalized.number, finalized.hash);
+        
+        // The canonical head may have been on a pruned branch
+        let head = tree.best_head(&state.best_hash);
+        if head != state.best_hash {
+            self.switch_head(state, tree, mempool, &head, events)?;
+        }
+        
+        // The finalized block is canonical and on disk by now
+        if let Some(finalized_state) = tree.state(&finalized.hash) {
+            self.storage.save_finalized_state(finalized_state)?;
+        }
+        
+        Ok(true)
+    }
+    
+    /// Emit events and gossip for a committed block
+    async fn announce(&self, block: Block, outcome: CommitOutcome, produced: bool) -> Result<()> {
+        if produced {
+            self.event_tx.send(BlockchainEvent::BlockProduced { 
+                block_number: block.number, 
+                block_hash: block.hash.clone(),
+            }).await?;
+        } else {
+            self.event_tx.send(BlockchainEvent::BlockImported {
+                block_number: block.number,
+                block_hash: block.hash.clone(),
+            }).await?;
+        }
+        
+        for event in outcome.access_events {
+            self.event_tx.send(event).await?;
+        }
+        if let Some(vote) = &outcome.vote {
+            self.event_tx.send(BlockchainEvent::VoteCast { vote: vote.clone() }).await?;
+        }
+        if let Some(finalized) = outcome.finalized {
+            self.event_tx.send(BlockchainEvent::BlockFinalized {
+                block_number: finalized.number,
+                block_hash: finalized.hash,
+            }).await?;
+        }
+        
+        if let Some(network_tx) = &self.network_tx {
+            // Imported blocks are already gossiped by the mesh
+            if produced {
+                network_tx.send(NetworkCommand::Block { block }).await?;
+            }
+            if let Some(vote) = outcome.vote {
+                network_tx.send(NetworkCommand::Vote { vote }).await?;
+            }
+        }
+        
+        Ok(())
+    }
+}
+
+impl ChainReader {
+    /// Current status of the local chain
+    pub async fn status(&self) -> Result<ChainStatus> {
+        let state = self.state.read().await;
+        let finalized = self.storage.get_block(state.finalized_block)?
+            .ok_or_else(|| anyhow::anyhow!("Finalized block #{} missing from storage", state.finalized_block))?;
+        
+        Ok(ChainStatus {
+            chain_id: state.chain_id.clone(),
+            genesis_hash: self.genesis_hash.clone(),
+            best_block: state.best_block,
+            best_hash: state.best_hash.clone(),
+            finalized_block: state.finalized_block,
+            finalized_hash: finalized.hash,
+        })
+    }
+    
+    /// Current fee rate and fee market parameters
+    pub async fn fee_quote(&self) -> FeeQuote {
+        let state = self.state.read().await;
+        state.params.fees.quote(state.fee_per_weight)
+    }
+    
+    /// Lowest fee `tx` may pay to enter the next block
+    pub async fn min_fee(&self, tx: &Transaction) -> u64 {
+        tx.min_fee(self.state.read().await.fee_per_weight)
+    }
+    
+    /// Current reward epoch and the relay scores it would pay out by
+    pub async fn reward_status(&self) -> RewardStatus {
+        let state = self.state.read().await;
+        let epoch_blocks = state.params.epoch_blocks.max(1);
+        
+        RewardStatus {
+            epoch: state.quality_reports.epoch,
+            ends_at_block: (state.best_block / epoch_blocks + 1) * epoch_blocks,
+            reports: state.quality_reports.len(),
+            relay_pool: state.relay_pool,
+            epoch_reward: state.params.rewards.epoch_reward,
+            provisional: state.quality_reports.cross_check(&state.params.rewards),
+        }
+    }
+    
+    /// Bonded stakes and the validator set elected from them
+    pub async fn staking(&self) -> StakingStatus {
+        let state = self.state.read().await;
+        StakingStatus {
+            params: state.params.staking,
+            validators: state.validators.clone(),
+            stakes: state.stakes.values().cloned().collect(),
+            slashed_stake: state.slashed_stake,
+        }
+    }
+    
+    /// Relays picked for a stream this epoch, weighted by their bonded stake
+    pub async fn pick_relays(&self, stream_id: &str, count: usize) -> Vec<String> {
+        let state = self.state.read().await;
+        let mut seed = stream_id.as_bytes().to_vec();
+        seed.extend_from_slice(&state.quality_reports.epoch.to_le_bytes());
+        staking::pick_relays(&state.stakes, &state.params.staking, &seed, count, chrono::Utc::now())
+    }
+    
+    /// A stream's revenue split and the payouts made under it
+    pub async fn payouts(&self, stream_id: &str) -> Option<PayoutHistory> {
+        self.state.read().await.streams.get(stream_id).map(PayoutHistory::from)
+    }
+    
+    /// A registered stream
+    pub async fn stream(&self, stream_id: &str) -> Option<StreamRegistration> {
+        self.state.read().await.streams.get(stream_id).cloned()
+    }
+    
+    /// Prove a state entry against the canonical head
+    ///
+    /// The proof is checked against the head's header before it is returned,
+    /// so a light client holding that header can check it the same way.
+    pub async fn prove(&self, prove: impl FnOnce(&BlockchainState) -> StateProof) -> Result<(SignedHeader, StateProof)> {
+        let state = self.state.read().await;
+        let head = self.storage.get_block(state.best_block)?
+            .ok_or_else(|| anyhow::anyhow!("Head block #{} missing from storage", state.best_block))?
+            .header();
+        let proof = prove(&state);
+        drop(state);
+        
+        proof.verify(&head.state_root)?;
+        Ok((head, proof))
+    }
+    
+    /// Broadcasts of a stream this node has seen
+    pub async fn stream_history(&self, stream_id: &str) -> StreamHistory {
+        self.sessions.read().await.history(stream_id).cloned().unwrap_or_default()
+    }
+    
+    /// End of a viewer's access to a stream, from an on-chain grant, a payment channel or a free preview
+    pub async fn access_until(&self, viewer: &str, stream_id: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+        let state = self.state.read().await;
+        let channels = self.channels.read().await;
+        let previews = self.previews.read().await;
+        access_until(&state, &channels, &previews, viewer, stream_id)
+    }
+    
+    /// An open payment channel and the latest update this node accepted for it
+    pub async fn channel(&self, channel_id: &str) -> Option<(PaymentChannel, Option<ChannelState>)> {
+        let state = self.state.read().await;
+        let channel = state.channels.get(channel_id)?.clone();
+        let known = self.channels.read().await.get(channel_id).cloned();
+        Some((channel, known))
+    }
+    
+    /// Accept an off-chain payment channel update from a viewer
+    ///
+    /// Returns the end of the viewer's paid time after the update.
+    pub async fn submit_channel_update(&self, update: ChannelUpdate) -> Result<chrono::DateTime<chrono::Utc>> {
+        let state = self.state.read().await;
+        let mut channels = self.channels.write().await;
+        let channel_id = update.channel_id.clone();
+        let paid_until = channels.accept(&state, update, chrono::Utc::now())?;
+        
+        debug!("🧾 Channel {} paid until {}", channel_id, paid_until);
+        Ok(paid_until)
+    }
+    
+    /// Keep a viewer's signed quality measurement for checking validators' reports
+    pub async fn submit_quality_measurement(&self, measurement: QualityMeasurement) -> Result<()> {
+        let state = self.state.read().await;
+        let mut measurements = self.measurements.write().await;
+        let (viewer, stream_id) = (measurement.viewer.clone(), measurement.stream_id.clone());
+        measurements.accept(&state, measurement)?;
+        
+        debug!("📏 Quality measurement from {} for stream {}", viewer, stream_id);
+        Ok(())
+    }
+    
+    /// Transactions waiting in the pool, with whether each is ready for the next block
+    pub async fn pending_transactions(&self) -> Vec<PendingTransaction> {
+        let state = self.state.read().await;
+        self.mempool.lock().await.pending(&state)
+    }
+    
+    /// Stored blocks from `from`, up to `count` of them and never past the tip
+    pub async fn blocks(&self, from: u64, count: u64) -> Result<Vec<Block>> {
+        let best_block = self.state.read().await.best_block;
+        let to = from.saturating_add(count.min(MAX_BLOCK_RANGE)).min(best_block.saturating_add(1));
+        
+        let mut blocks = Vec::new();
+        for number in from..to {
+            match self.storage.get_block(number)? {
+                Some(block) => blocks.push(block),
+                None => break,
+            }
+        }
+        Ok(blocks)
+    }
+}
+
+/// (stream, viewer) pairs whose latest paid time from grants, channels and previews is at or before `cutoff`
+fn expired_access(
+    state: &BlockchainState,
+    channels: &ChannelBook,
+    previews: &PreviewBook,
+    cutoff: chrono::DateTime<chrono::Utc>,
+) -> HashSet<(String, String)> {
+    let mut paid: HashMap<(String, String), chrono::DateTime<chrono::Utc>> = HashMap::new();
+    let grants = pricing::access_grants(state);
+    let updates = channels.open(state)
+        .map(|known| ((known.stream_id.clone(), known.viewer.clone()), known.paid_until));
+    for (key, paid_until) in grants.into_iter().chain(updates).chain(previews.previews()) {
+        let entry = paid.entry(key).or_insert(paid_until);
+        *entry = (*entry).max(paid_until);
+    }
+    
+    paid.into_iter()
+        .filter(|(_, paid_until)| *paid_until <= cutoff)
+        .map(|(key, _)| key)
+        .collect()
+}
+
+/// Latest of a viewer's on-chain `paid_until`, the time bought through their payment channels and their preview
+fn access_until(
+    state: &BlockchainState,
+    channels: &ChannelBook,
+    previews: &PreviewBook,
+    viewer: &str,
+    stream_id: &str,
+) -> Option<chrono::DateTime<chrono::Utc>> {
+    let granted = state.accounts.get(viewer).and_then(|account| match state.streams.get(stream_id) {
+        Some(stream) => stream.paid_until(account),
+        None => account.stream_access.get(stream_id).map(|access| access.paid_until),
+    });
+    granted.max(channels.paid_until(state, viewer, stream_id))
+        .max(previews.preview_until(stream_id, viewer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::genesis::GenesisSpec;
+
+    #[test]
+    fn access_expires_once_paid_time_and_grace_pass() {
+        let balances = [("alice", 1_000), ("bob", 1_000)]
+            .into_iter()
+            .map(|(address, balance)| (address.to_string(), balance))
+            .collect();
+        let mut state = GenesisSpec { balances, ..Default::default() }.build().0;
+        let now = chrono::Utc::now();
+        state.accounts.get_mut("alice").unwrap().grant_access("stream", now - chrono::Duration::seconds(10), 100, now);
+        state.accounts.get_mut("bob").unwrap().grant_access("stream", now + chrono::Duration::seconds(10), 100, now);
+        let (channels, previews) = (ChannelBook::new(), PreviewBook::new());
+        let key = |viewer: &str| ("stream".to_string(), viewer.to_string());
+
+        // Within a 30 second grace period nobody has expired yet
+        let expired = expired_access(&state, &channels, &previews, now - chrono::Duration::seconds(30));
+        assert!(expired.is_empty());
+
+        let expired = expired_access(&state, &channels, &previews, now);
+        assert_eq!(expired, HashSet::from([key("alice")]));
+
+        // Paying again takes the viewer out of the expired set
+        state.accounts.get_mut("alice").unwrap().grant_access("stream", now + chrono::Duration::minutes(1), 100, now);
+        assert!(expired_access(&state, &channels, &previews, now).is_empty());
+        assert_eq!(access_until(&state, &channels, &previews, "alice", "stream"), Some(now + chrono::Duration::minutes(1)));
     }
 }
@@ -0,0 +1,165 @@
+// Transaction fees
+//
+// Every transaction pays a fee in STREAM to the author of the block that
+// includes it. A transaction's weight is a per-kind base cost plus its encoded
+// size, and its fee must cover weight times the chain's current fee rate. The
+// rate follows block fullness: a block heavier than the target raises it by up
+// to 1/8, a lighter one lowers it, and it never drops below the genesis
+// minimum. A configurable share of every fee is burned instead of paid out.
+
+use serde::{Deserialize, Serialize};
+
+/// Base weight of a transfer
+pub const TRANSFER_WEIGHT: u64 = 100;
+
+/// Base weight of a stream registration, high enough to make spam costly
+pub const REGISTER_STREAM_WEIGHT: u64 = 1_000;
+
+/// Base weight of an access purchase
+pub const PURCHASE_ACCESS_WEIGHT: u64 = 100;
+
+/// Base weight of a quality report
+pub const REPORT_QUALITY_WEIGHT: u64 = 200;
+
+/// Base weight of opening a payment channel
+pub const OPEN_CHANNEL_WEIGHT: u64 = 200;
+
+/// Base weight of closing a payment channel, which verifies an update signature
+pub const CLOSE_CHANNEL_WEIGHT: u64 = 400;
+
+/// Base weight of bonding or unbonding stake
+pub const STAKING_WEIGHT: u64 = 200;
+
+/// Base weight of withdrawing unbonded stake
+pub const WITHDRAW_WEIGHT: u64 = 100;
+
+/// Base weight of changing a stream's revenue split
+pub const SET_SPLIT_WEIGHT: u64 = 300;
+
+/// Base weight of submitting misbehavior evidence
+pub const EVIDENCE_WEIGHT: u64 = 500;
+
+/// Largest fee rate change per block is `1 / FEE_RATE_CHANGE_DENOMINATOR`
+const FEE_RATE_CHANGE_DENOMINATOR: u64 = 8;
+
+/// Fee market parameters fixed at genesis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeeParams {
+    /// Floor of the fee rate, in STREAM per unit of weight
+    pub min_fee_per_weight: u64,
+    /// Block weight the fee rate steers towards; blocks may be twice as heavy
+    pub target_block_weight: u64,
+    /// Percentage of every fee that is burned
+    pub burn_percent: u8,
+}
+
+/// The chain's current fee terms, as quoted to wallets
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeeQuote {
+    /// Fee rate the next block requires
+    pub fee_per_weight: u64,
+    pub min_fee_per_weight: u64,
+    pub target_block_weight: u64,
+    pub max_block_weight: u64,
+    pub burn_percent: u8,
+}
+
+impl Default for FeeParams {
+    fn default() -> Self {
+        Self {
+            min_fee_per_weight: 1,
+            target_block_weight: 100_000,
+            burn_percent: 0,
+        }
+    }
+}
+
+impl FeeParams {
+    /// Heaviest block validators accept
+    pub fn max_block_weight(&self) -> u64 {
+        self.target_block_weight.saturating_mul(2)
+    }
+
+    /// Fee rate for the block after one of `block_weight` built at `fee_per_weight`
+    pub fn next_fee_per_weight(&self, fee_per_weight: u64, block_weight: u64) -> u64 {
+        let target = self.target_block_weight.max(1) as u128;
+        let rate = fee_per_weight as u128;
+        let weight = block_weight as u128;
+        let denominator = target * FEE_RATE_CHANGE_DENOMINATOR as u128;
+
+        let next = if weight > target {
+            // Always move up by at least one so a zero rate can recover
+            rate + (rate * (weight - target) / denominator).max(1)
+        } else {
+            rate - rate * (target - weight) / denominator
+        };
+
+        (next.min(u64::MAX as u128) as u64).max(self.min_fee_per_weight)
+    }
+
+    /// Split collected fees into the block author's share and the burned amount
+    pub fn split(&self, fees: u64) -> (u64, u64) {
+        let burned = (fees as u128 * self.burn_percent.min(100) as u128 / 100) as u64;
+        (fees - burned, burned)
+    }
+
+    /// Fee terms at the given current rate
+    pub fn quote(&self, fee_per_weight: u64) -> FeeQuote {
+        FeeQuote {
+            fee_per_weight,
+            min_fee_per_weight: self.min_fee_per_weight,
+            target_block_weight: self.target_block_weight,
+            max_block_weight: self.max_block_weight(),
+            burn_percent: self.burn_percent,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(burn_percent: u8) -> FeeParams {
+        FeeParams {
+            min_fee_per_weight: 10,
+            target_block_weight: 1_000,
+            burn_percent,
+        }
+    }
+
+    #[test]
+    fn fee_rate_follows_block_fullness() {
+        let params = params(0);
+
+        assert_eq!(params.next_fee_per_weight(800, 1_000), 800);
+        // A full block (twice the target) raises the rate by 1/8
+        assert_eq!(params.next_fee_per_weight(800, 2_000), 900);
+        // An empty block lowers it by 1/8
+        assert_eq!(params.next_fee_per_weight(800, 0), 700);
+        assert_eq!(params.next_fee_per_weight(800, 1_500), 850);
+    }
+
+    #[test]
+    fn fee_rate_stays_at_or_above_the_minimum() {
+        let params = params(0);
+
+        assert_eq!(params.next_fee_per_weight(10, 0), 10);
+        assert_eq!(params.next_fee_per_weight(0, 0), 10);
+
+        // A rate too small for 1/8 to move still rises on a heavy block
+        let unbounded = FeeParams { min_fee_per_weight: 0, ..params };
+        assert_eq!(unbounded.next_fee_per_weight(0, 1_001), 1);
+        assert_eq!(unbounded.next_fee_per_weight(u64::MAX, u64::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn split_burns_the_configured_share() {
+        assert_eq!(params(0).split(1_000), (1_000, 0));
+        assert_eq!(params(25).split(1_000), (750, 250));
+        assert_eq!(params(100).split(1_000), (0, 1_000));
+        // Rounding favours the block author
+        assert_eq!(params(50).split(3), (2, 1));
+        assert_eq!(params(50).split(u64::MAX), (u64::MAX - u64::MAX / 2, u64::MAX / 2));
+    }
+}
@@ -0,0 +1,183 @@
+// Block tree and fork choice
+//
+// Blocks that are not yet final are kept in a tree rooted at the last
+// finalized block, each with the state it produced. The canonical head is the
+// highest block in the tree (ties keep the current head, then the lowest
+// hash). Because the tree is pruned to descendants of the finalized root, the
+// chain never reorganizes past finality. Switching heads swaps in the stored
+// post-state, which rolls back the retracted branch's account, stream and
+// access changes and re-applies the enacted branch's.
+
+use anyhow::{anyhow, Result};
+use std::collections::{HashMap, HashSet};
+
+use super::engine::BlockchainState;
+use super::pricing;
+use super::{Block, BlockchainEvent};
+
+struct TreeNode {
+    block: Block,
+    /// State after applying the block
+    state: BlockchainState,
+}
+
+/// Blocks leaving and joining the canonical chain when the head moves
+pub struct HeadChange {
+    /// Old branch, from the old head down to the common ancestor
+    pub retracted: Vec<Block>,
+    /// New branch, from the common ancestor up to the new head
+    pub enacted: Vec<Block>,
+}
+
+/// Tree of non-finalized blocks keyed by hash and linked by `parent_hash`
+pub struct BlockTree {
+    root: String,
+    nodes: HashMap<String, TreeNode>,
+    children: HashMap<String, Vec<String>>,
+}
+
+impl BlockTree {
+    /// Start a tree at `root`, whose state is already committed
+    pub fn new(root: Block, state: BlockchainState) -> Self {
+        let hash = root.hash.clone();
+        let mut nodes = HashMap::new();
+        nodes.insert(hash.clone(), TreeNode { block: root, state });
+
+        Self {
+            root: hash,
+            nodes,
+            children: HashMap::new(),
+        }
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.nodes.contains_key(hash)
+    }
+
+    /// Number of the tree root; nothing below it can change
+    pub fn root_number(&self) -> u64 {
+        self.nodes[&self.root].block.number
+    }
+
+    /// Number of the block with `hash`
+    pub fn number(&self, hash: &str) -> Option<u64> {
+        self.nodes.get(hash).map(|node| node.block.number)
+    }
+
+    /// Block with `hash`
+    pub fn block(&self, hash: &str) -> Option<&Block> {
+        self.nodes.get(hash).map(|node| &node.block)
+    }
+
+    /// State after the block with `hash`
+    pub fn state(&self, hash: &str) -> Option<&BlockchainState> {
+        self.nodes.get(hash).map(|node| &node.state)
+    }
+
+    /// Another block by the same author on the same parent, if the tree holds one
+    pub fn conflicting(&self, block: &Block) -> Option<&Block> {
+        self.children.get(&block.parent_hash)?
+            .iter()
+            .map(|hash| &self.nodes[hash].block)
+            .find(|sibling| sibling.validator == block.validator && sibling.hash != block.hash)
+    }
+
+    /// Add a validated block whose parent is already in the tree
+    pub fn insert(&mut self, block: Block, state: BlockchainState) -> Result<()> {
+        if !self.nodes.contains_key(&block.parent_hash) {
+            return Err(anyhow!("Parent {} of block #{} is not in the block tree", block.parent_hash, block.number));
+        }
+        if self.nodes.contains_key(&block.hash) {
+            return Ok(());
+        }
+
+        self.children.entry(block.parent_hash.clone()).or_default().push(block.hash.clone());
+        self.nodes.insert(block.hash.clone(), TreeNode { block, state });
+        Ok(())
+    }
+
+    /// Fork choice: the highest block, keeping `current` on ties
+    pub fn best_head(&self, current: &str) -> String {
+        self.nodes.values()
+            .max_by(|a, b| {
+                a.block.number.cmp(&b.block.number)
+                    .then_with(|| (a.block.hash == current).cmp(&(b.block.hash == current)))
+                    .then_with(|| b.block.hash.cmp(&a.block.hash))
+            })
+            .map(|node| node.block.hash.clone())
+            .expect("block tree always holds its root")
+    }
+
+    /// Blocks to retract and enact to move the head from `from` to `to`
+    pub fn route(&self, from: &str, to: &str) -> HeadChange {
+        let mut retracted = Vec::new();
+        let mut enacted = Vec::new();
+
+        let mut old = &self.nodes[from];
+        let mut new = &self.nodes[to];
+        while old.block.hash != new.block.hash {
+            if old.block.number >= new.block.number {
+                retracted.push(old.block.clone());
+                old = &self.nodes[&old.block.parent_hash];
+            } else {
+                enacted.push(new.block.clone());
+                new = &self.nodes[&new.block.parent_hash];
+            }
+        }
+
+        enacted.reverse();
+        HeadChange { retracted, enacted }
+    }
+
+    /// Make `hash` the new root, dropping every block that does not descend from it
+    pub fn finalize(&mut self, hash: &str) -> bool {
+        if !self.nodes.contains_key(hash) {
+            return false;
+        }
+
+        let mut keep = HashSet::new();
+        let mut queue = vec![hash.to_string()];
+        while let Some(next) = queue.pop() {
+            if let Some(children) = self.children.get(&next) {
+                queue.extend(children.iter().cloned());
+            }
+            keep.insert(next);
+        }
+
+        self.nodes.retain(|hash, _| keep.contains(hash));
+        self.children.retain(|hash, _| keep.contains(hash));
+        self.root = hash.to_string();
+        true
+    }
+}
+
+/// Access grants gained or lost when the canonical state changes
+///
+/// A grant counts while its `paid_until` is in the future, so a reorg that
+/// drops or adds a `PurchaseAccess` produces the matching revoke or grant.
+pub fn access_changes(before: &BlockchainState, after: &BlockchainState) -> Vec<BlockchainEvent> {
+    let now = chrono::Utc::now();
+    let active = |state: &BlockchainState| -> HashSet<(String, String)> {
+        pricing::access_grants(state).into_iter()
+            .filter(|(_, paid_until)| *paid_until > now)
+            .map(|(key, _)| key)
+            .collect()
+    };
+
+    let was_active = active(before);
+    let is_active = active(after);
+
+    let granted = is_active.difference(&was_active)
+        .map(|(stream_id, viewer)| BlockchainEvent::AccessGranted {
+            stream_id: stream_id.clone(),
+            viewer: viewer.clone(),
+        });
+    let revoked = was_active.difference(&is_active)
+        .map(|(stream_id, viewer)| BlockchainEvent::AccessRevoked {
+            stream_id: stream_id.clone(),
+            viewer: viewer.clone(),
+            reason: "Chain reorganization".to_string(),
+        });
+
+    revoked.chain(granted).collect()
+}
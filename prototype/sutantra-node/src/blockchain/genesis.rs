@@ -0,0 +1,277 @@
+// Genesis specification
+//
+// A chain is defined by a JSON or TOML genesis file: its id, launch
+// timestamp, initial balances, validator set, consensus parameters and
+// protocol parameters. The genesis block commits to the spec and the state it
+// produces, so its hash identifies the chain. Nodes refuse to open a data
+// directory created from a different spec, and peers on another genesis are
+// ignored during sync.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+use super::consensus::{ConsensusConfig, ConsensusMode, LeaderSelection, Validator};
+use super::engine::BlockchainState;
+use super::fees::FeeParams;
+use super::rewards::{EpochReports, RewardParams};
+use super::slashing::SlashingParams;
+use super::staking::{Stake, StakeRole, StakingParams};
+use super::{Account, Block};
+
+/// Chain parameters fixed at genesis
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenesisSpec {
+    pub chain_id: String,
+
+    /// Genesis block timestamp
+    #[serde(default = "unix_epoch")]
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+
+    /// Initial account balances by address
+    #[serde(default)]
+    pub balances: BTreeMap<String, u64>,
+
+    #[serde(default)]
+    pub consensus: GenesisConsensus,
+
+    #[serde(default)]
+    pub params: ProtocolParams,
+}
+
+/// Consensus parameters; an empty validator set runs single-node dev consensus
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenesisConsensus {
+    pub block_time_secs: u64,
+    #[serde(default)]
+    pub validators: Vec<Validator>,
+    #[serde(default = "default_leader_selection")]
+    pub leader_selection: LeaderSelection,
+}
+
+/// Protocol rules enforced by every node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProtocolParams {
+    /// Lowest `price_per_minute` a stream can be registered with
+    pub min_price_per_minute: u64,
+
+    /// Transaction fee market
+    pub fees: FeeParams,
+
+    /// Seconds a creator has to answer a viewer's payment channel close
+    pub channel_dispute_secs: u64,
+
+    /// Blocks per epoch, the period relay rewards are paid over
+    pub epoch_blocks: u64,
+
+    /// Relay rewards from stream quality reports
+    pub rewards: RewardParams,
+
+    /// Bonding, unbonding and validator elections
+    pub staking: StakingParams,
+
+    /// Penalties for double signing and false quality reports
+    pub slashing: SlashingParams,
+}
+
+impl Default for GenesisConsensus {
+    fn default() -> Self {
+        Self {
+            block_time_secs: 6,
+            validators: Vec::new(),
+            leader_selection: default_leader_selection(),
+        }
+    }
+}
+
+impl Default for ProtocolParams {
+    fn default() -> Self {
+        Self {
+            min_price_per_minute: 1,
+            fees: FeeParams::default(),
+            channel_dispute_secs: 600,
+            epoch_blocks: 600,
+            rewards: RewardParams::default(),
+            staking: StakingParams::default(),
+            slashing: SlashingParams::default(),
+        }
+    }
+}
+
+impl Default for GenesisSpec {
+    /// Local development chain with a funded "genesis" account
+    fn default() -> Self {
+        Self {
+            chain_id: "sutantra-testnet".to_string(),
+            timestamp: unix_epoch(),
+            balances: BTreeMap::from([("genesis".to_string(), 1_000_000_000)]),
+            consensus: GenesisConsensus::default(),
+            params: ProtocolParams::default(),
+        }
+    }
+}
+
+impl GenesisSpec {
+    /// Read a genesis file, TOML if its extension is `.toml` and JSON otherwise
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read genesis file {}", path.display()))?;
+        let spec: Self = if is_toml(path) {
+            toml::from_str(&text).with_context(|| format!("Invalid genesis file {}", path.display()))?
+        } else {
+            serde_json::from_str(&text).with_context(|| format!("Invalid genesis file {}", path.display()))?
+        };
+
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    /// Write a genesis file in the format its extension picks, as `load` reads it
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let text = if is_toml(path) { self.to_toml()? } else { self.to_json()? };
+        std::fs::write(path, text)
+            .with_context(|| format!("Failed to write genesis file {}", path.display()))
+    }
+
+    /// Pretty-printed JSON for a genesis file
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// TOML for a genesis file
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+
+    /// Check the spec describes a chain that can run
+    pub fn validate(&self) -> Result<()> {
+        if self.chain_id.is_empty() {
+            return Err(anyhow!("Genesis chain_id must not be empty"));
+        }
+        if self.consensus.block_time_secs == 0 {
+            return Err(anyhow!("Genesis block_time_secs must be at least 1"));
+        }
+        if self.params.fees.burn_percent > 100 {
+            return Err(anyhow!("Genesis fee burn_percent must be at most 100"));
+        }
+        if self.params.fees.target_block_weight == 0 {
+            return Err(anyhow!("Genesis target_block_weight must be at least 1"));
+        }
+        if self.params.epoch_blocks == 0 {
+            return Err(anyhow!("Genesis epoch_blocks must be at least 1"));
+        }
+        if self.params.rewards.relay_fee_share_percent > 100 {
+            return Err(anyhow!("Genesis relay_fee_share_percent must be at most 100"));
+        }
+        let slashing = self.params.slashing;
+        if slashing.double_sign_percent > 100 || slashing.false_report_percent > 100
+            || slashing.reporter_reward_percent > 100
+        {
+            return Err(anyhow!("Genesis slashing percentages must be at most 100"));
+        }
+        if !self.consensus.validators.is_empty()
+            && self.consensus.validators.iter().all(|validator| validator.stake == 0)
+        {
+            return Err(anyhow!("Genesis validator set has no stake"));
+        }
+        Ok(())
+    }
+
+    /// Consensus configuration for this chain
+    pub fn consensus_config(&self) -> ConsensusConfig {
+        let mode = if self.consensus.validators.is_empty() {
+            ConsensusMode::Dev
+        } else {
+            ConsensusMode::Bft {
+                validators: self.consensus.validators.clone(),
+                leader_selection: self.consensus.leader_selection,
+            }
+        };
+
+        ConsensusConfig {
+            block_time_secs: self.consensus.block_time_secs,
+            mode,
+        }
+    }
+
+    /// Initial state and the genesis block committing to it
+    pub fn build(&self) -> (BlockchainState, Block) {
+        let accounts = self.balances.iter()
+            .map(|(address, balance)| {
+                (address.clone(), Account {
+                    address: address.clone(),
+                    balance: *balance,
+                    nonce: 0,
+                    stream_access: HashMap::new(),
+                    created_streams: Vec::new(),
+                })
+            })
+            .collect();
+
+        let mut state = BlockchainState {
+            accounts,
+            streams: HashMap::new(),
+            channels: HashMap::new(),
+            stakes: self.genesis_stakes(),
+            validators: self.consensus.validators.clone(),
+            quality_reports: EpochReports::new(0),
+            relay_pool: 0,
+            best_block: 0,
+            best_hash: String::new(),
+            finalized_block: 0,
+            chain_id: self.chain_id.clone(),
+            params: self.params,
+            fee_per_weight: self.params.fees.min_fee_per_weight,
+            burned_fees: 0,
+            slashed_stake: 0,
+            double_signs: BTreeSet::new(),
+            pending_finality: None,
+        };
+
+        let genesis = Block::genesis(self.timestamp, state.state_root(), self.spec_hash());
+        state.best_hash = genesis.hash.clone();
+        (state, genesis)
+    }
+
+    /// Genesis validators' stake, bonded from the start without leaving any balance
+    fn genesis_stakes(&self) -> BTreeMap<String, Stake> {
+        self.consensus.validators.iter()
+            .map(|validator| {
+                (validator.address.clone(), Stake {
+                    address: validator.address.clone(),
+                    role: StakeRole::Validator,
+                    bonded: validator.stake,
+                    unbonding: Vec::new(),
+                    jailed_until: None,
+                })
+            })
+            .collect()
+    }
+
+    /// Hash of the genesis block, which identifies the chain
+    pub fn hash(&self) -> String {
+        self.build().1.hash
+    }
+
+    /// blake3 hash over the canonical JSON encoding of the spec, hex-encoded
+    fn spec_hash(&self) -> String {
+        let bytes = serde_json::to_vec(self).expect("genesis spec serialization cannot fail");
+        blake3::hash(&bytes).to_hex().to_string()
+    }
+}
+
+fn unix_epoch() -> chrono::DateTime<chrono::Utc> {
+    chrono::DateTime::UNIX_EPOCH
+}
+
+fn default_leader_selection() -> LeaderSelection {
+    LeaderSelection::RoundRobin
+}
+
+fn is_toml(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("toml"))
+}
@@ -0,0 +1,430 @@
+// Transaction pool
+//
+// Pending transactions are queued per sender and keyed by nonce. A sender's
+// transactions are ready once they form an unbroken nonce sequence starting at
+// the account nonce; later ones wait as future transactions. Blocks take ready
+// transactions highest fee per unit of weight first (earliest arrival on ties)
+// while keeping each sender's nonce order. When the pool is full, the
+// lowest-rate transactions are evicted to make room for one that pays more.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use tracing::debug;
+
+use super::engine::BlockchainState;
+use super::transactions::TransactionProcessor;
+use super::Transaction;
+
+/// Limits on the transaction pool
+#[derive(Debug, Clone, Copy)]
+pub struct MempoolConfig {
+    /// Most transactions held at once
+    pub max_transactions: usize,
+    /// Most encoded transaction bytes held at once
+    pub max_bytes: usize,
+    /// How far past the account nonce a queued transaction may be
+    pub max_nonce_gap: u64,
+}
+
+impl Default for MempoolConfig {
+    fn default() -> Self {
+        Self {
+            max_transactions: 8192,
+            max_bytes: 4 * 1024 * 1024,
+            max_nonce_gap: 64,
+        }
+    }
+}
+
+struct PoolEntry {
+    tx: Transaction,
+    fee: u64,
+    /// Fee per unit of weight, the pool's priority
+    fee_rate: u64,
+    size: usize,
+    /// Insertion order, the tie-breaker between equal fee rates
+    arrival: u64,
+    received_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A pooled transaction as reported by the node API
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingTransaction {
+    pub hash: String,
+    pub sender: String,
+    pub nonce: u64,
+    pub fee: u64,
+    pub weight: u64,
+    pub size: usize,
+    /// Whether the transaction can go into the next block
+    pub ready: bool,
+    pub received_at: chrono::DateTime<chrono::Utc>,
+    pub transaction: Transaction,
+}
+
+/// Validated transactions waiting for a block
+pub struct Mempool {
+    config: MempoolConfig,
+    by_sender: HashMap<String, BTreeMap<u64, PoolEntry>>,
+    len: usize,
+    bytes: usize,
+    next_arrival: u64,
+}
+
+impl Mempool {
+    pub fn new(config: MempoolConfig) -> Self {
+        Self {
+            config,
+            by_sender: HashMap::new(),
+            len: 0,
+            bytes: 0,
+            next_arrival: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Nonce for a sender's next transaction, after the ones already queued in order
+    pub fn next_nonce(&self, state: &BlockchainState, sender: &str) -> u64 {
+        let mut next = account_nonce(state, sender);
+        if let Some(queue) = self.by_sender.get(sender) {
+            while queue.contains_key(&next) {
+                next += 1;
+            }
+        }
+        next
+    }
+
+    /// Validate a transaction against `state` and queue it
+    ///
+    /// The signature must already be verified. A transaction reusing a queued
+    /// nonce replaces the queued one only when it pays a higher fee.
+    pub fn insert(&mut self, state: &BlockchainState, tx: Transaction) -> Result<()> {
+        let sender = tx.sender().to_string();
+        let nonce = tx.nonce();
+        let fee = tx.fee();
+        let fee_rate = fee / tx.weight().max(1);
+        let size = tx.encoded_len();
+
+        let account_nonce = account_nonce(state, &sender);
+        if nonce < account_nonce {
+            return Err(anyhow!("Replayed transaction from {}: nonce {} already used", sender, nonce));
+        }
+        if nonce > account_nonce + self.config.max_nonce_gap {
+            return Err(anyhow!(
+                "Nonce {} from {} is too far ahead of account nonce {}", nonce, sender, account_nonce
+            ));
+        }
+        if size > self.config.max_bytes {
+            return Err(anyhow!("Transaction from {} is too large ({} bytes)", sender, size));
+        }
+
+        let queue = self.by_sender.get(&sender);
+        let replaced = queue.and_then(|queue| queue.get(&nonce));
+        if replaced.is_some_and(|queued| fee <= queued.fee) {
+            return Err(anyhow!("Duplicate transaction from {} with nonce {}", sender, nonce));
+        }
+
+        // Earlier queued transactions from the sender already claim part of the balance
+        let reserved = queue.map_or(0, |queue| {
+            queue.range(..nonce).map(|(_, entry)| entry.tx.spend()).sum()
+        });
+        TransactionProcessor::check(state, &tx, reserved)?;
+
+        let victims = self.eviction_victims(&sender, nonce, fee_rate, size, replaced)?;
+
+        self.remove(&sender, nonce);
+        for (victim, victim_nonce) in victims {
+            debug!("🗑️  Evicting transaction from {} (nonce {}) from full mempool", victim, victim_nonce);
+            self.remove(&victim, victim_nonce);
+        }
+
+        let entry = PoolEntry {
+            tx,
+            fee,
+            fee_rate,
+            size,
+            arrival: self.next_arrival,
+            received_at: chrono::Utc::now(),
+        };
+        self.next_arrival += 1;
+        self.len += 1;
+        self.bytes += size;
+        self.by_sender.entry(sender).or_default().insert(nonce, entry);
+
+        Ok(())
+    }
+
+    /// Ready transactions in block order, at most `limit` of them
+    ///
+    /// Each sender contributes its unbroken nonce run from the account nonce,
+    /// interleaved with other senders by fee rate.
+    pub fn ready(&self, state: &BlockchainState, limit: usize) -> Vec<Transaction> {
+        let mut heads = BinaryHeap::new();
+        for (sender, queue) in &self.by_sender {
+            if let Some(entry) = queue.get(&account_nonce(state, sender)) {
+                heads.push((entry.fee_rate, Reverse(entry.arrival), sender.as_str(), entry.tx.nonce()));
+            }
+        }
+
+        let mut ready = Vec::new();
+        while ready.len() < limit {
+            let Some((_, _, sender, nonce)) = heads.pop() else {
+                break;
+            };
+            let queue = &self.by_sender[sender];
+            ready.push(queue[&nonce].tx.clone());
+
+            if let Some(next) = queue.get(&(nonce + 1)) {
+                heads.push((next.fee_rate, Reverse(next.arrival), sender, nonce + 1));
+            }
+        }
+        ready
+    }
+
+    /// Drop transactions that `state` has consumed or made invalid
+    ///
+    /// Called whenever the canonical head moves. Returns how many were dropped.
+    pub fn prune(&mut self, state: &BlockchainState) -> usize {
+        let before = self.len;
+
+        let mut stale = Vec::new();
+        for (sender, queue) in &self.by_sender {
+            let account_nonce = account_nonce(state, sender);
+            stale.extend(queue.range(..account_nonce).map(|(nonce, _)| (sender.clone(), *nonce)));
+
+            // Re-check the ready run against the new balances and streams
+            let mut reserved = 0u64;
+            for (next_ready, (nonce, entry)) in (account_nonce..).zip(queue.range(account_nonce..)) {
+                if *nonce != next_ready {
+                    break;
+                }
+
+                match TransactionProcessor::check(state, &entry.tx, reserved) {
+                    Ok(()) => reserved = reserved.saturating_add(entry.tx.spend()),
+                    Err(e) => {
+                        debug!("Dropping pooled transaction from {} (nonce {}): {}", sender, nonce, e);
+                        stale.push((sender.clone(), *nonce));
+                    }
+                }
+            }
+        }
+
+        for (sender, nonce) in stale {
+            self.remove(&sender, nonce);
+        }
+        before - self.len
+    }
+
+    /// All pooled transactions, by sender and nonce
+    pub fn pending(&self, state: &BlockchainState) -> Vec<PendingTransaction> {
+        let mut senders: Vec<_> = self.by_sender.keys().collect();
+        senders.sort();
+
+        let mut pending = Vec::with_capacity(self.len);
+        for sender in senders {
+            let mut next_ready = account_nonce(state, sender);
+            for (nonce, entry) in &self.by_sender[sender] {
+                let ready = *nonce == next_ready;
+                if ready {
+                    next_ready += 1;
+                }
+
+                pending.push(PendingTransaction {
+                    hash: entry.tx.hash(),
+                    sender: sender.clone(),
+                    nonce: *nonce,
+                    fee: entry.fee,
+                    weight: entry.tx.weight(),
+                    size: entry.size,
+                    ready,
+                    received_at: entry.received_at,
+                    transaction: entry.tx.clone(),
+                });
+            }
+        }
+        pending
+    }
+
+    /// Queued transactions to evict so that a new one fits
+    ///
+    /// Victims are the lowest-rate transactions, highest nonce first so that
+    /// senders' queues shrink from the tail. The sender's own earlier nonces
+    /// are never evicted, and nothing paying at least `fee_rate` is.
+    fn eviction_victims(
+        &self,
+        sender: &str,
+        nonce: u64,
+        fee_rate: u64,
+        size: usize,
+        replaced: Option<&PoolEntry>,
+    ) -> Result<Vec<(String, u64)>> {
+        let mut len = self.len - usize::from(replaced.is_some());
+        let mut bytes = self.bytes - replaced.map_or(0, |entry| entry.size);
+        let fits = |len: usize, bytes: usize| {
+            len < self.config.max_transactions && bytes + size <= self.config.max_bytes
+        };
+        if fits(len, bytes) {
+            return Ok(Vec::new());
+        }
+
+        let mut candidates: Vec<_> = self.by_sender.iter()
+            .flat_map(|(address, queue)| queue.values().map(move |entry| (address, entry)))
+            .filter(|(address, entry)| *address != sender || entry.tx.nonce() > nonce)
+            .collect();
+        candidates.sort_by_key(|(_, entry)| (entry.fee_rate, Reverse(entry.tx.nonce()), Reverse(entry.arrival)));
+
+        let mut victims = Vec::new();
+        for (address, entry) in candidates {
+            if fits(len, bytes) {
+                break;
+            }
+            if entry.fee_rate >= fee_rate {
+                break;
+            }
+            len -= 1;
+            bytes -= entry.size;
+            victims.push((address.clone(), entry.tx.nonce()));
+        }
+
+        if !fits(len, bytes) {
+            return Err(anyhow!("Mempool is full; transaction from {} pays too little to enter", sender));
+        }
+        Ok(victims)
+    }
+
+    fn remove(&mut self, sender: &str, nonce: u64) -> Option<PoolEntry> {
+        let queue = self.by_sender.get_mut(sender)?;
+        let entry = queue.remove(&nonce)?;
+        if queue.is_empty() {
+            self.by_sender.remove(sender);
+        }
+
+        self.len -= 1;
+        self.bytes -= entry.size;
+        Some(entry)
+    }
+}
+
+fn account_nonce(state: &BlockchainState, address: &str) -> u64 {
+    state.accounts.get(address).map_or(0, |account| account.nonce)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::genesis::GenesisSpec;
+
+    fn state() -> BlockchainState {
+        let spec = GenesisSpec {
+            balances: ["alice", "bob", "carol"].into_iter()
+                .map(|address| (address.to_string(), 1_000_000))
+                .collect(),
+            ..GenesisSpec::default()
+        };
+        spec.build().0
+    }
+
+    fn transfer(from: &str, nonce: u64, fee: u64) -> Transaction {
+        Transaction::Transfer {
+            from: from.to_string(),
+            to: "dave".to_string(),
+            amount: 10,
+            nonce,
+            fee,
+            signature: String::new(),
+        }
+    }
+
+    fn nonces(txs: &[Transaction]) -> Vec<(String, u64)> {
+        txs.iter().map(|tx| (tx.sender().to_string(), tx.nonce())).collect()
+    }
+
+    #[test]
+    fn queues_future_nonces_until_the_gap_is_filled() {
+        let state = state();
+        let mut pool = Mempool::new(MempoolConfig { max_nonce_gap: 2, ..MempoolConfig::default() });
+
+        pool.insert(&state, transfer("alice", 2, 1_000)).unwrap();
+        assert!(pool.insert(&state, transfer("alice", 3, 1_000)).is_err());
+        assert!(pool.ready(&state, 10).is_empty());
+        assert_eq!(pool.next_nonce(&state, "alice"), 0);
+
+        pool.insert(&state, transfer("alice", 0, 1_000)).unwrap();
+        assert_eq!(nonces(&pool.ready(&state, 10)), [("alice".to_string(), 0)]);
+
+        pool.insert(&state, transfer("alice", 1, 1_000)).unwrap();
+        assert_eq!(pool.next_nonce(&state, "alice"), 3);
+        let ready = nonces(&pool.ready(&state, 10));
+        assert_eq!(ready, [0, 1, 2].map(|nonce| ("alice".to_string(), nonce)));
+    }
+
+    #[test]
+    fn replaces_a_queued_nonce_only_for_a_higher_fee() {
+        let state = state();
+        let mut pool = Mempool::new(MempoolConfig::default());
+
+        pool.insert(&state, transfer("alice", 0, 1_000)).unwrap();
+        assert!(pool.insert(&state, transfer("alice", 0, 1_000)).is_err());
+        assert!(pool.insert(&state, transfer("alice", 0, 900)).is_err());
+
+        pool.insert(&state, transfer("alice", 0, 2_000)).unwrap();
+        assert_eq!(pool.len(), 1);
+        assert_eq!(pool.ready(&state, 10)[0].fee(), 2_000);
+    }
+
+    #[test]
+    fn orders_ready_transactions_by_fee_rate() {
+        let state = state();
+        let mut pool = Mempool::new(MempoolConfig::default());
+
+        pool.insert(&state, transfer("alice", 0, 1_000)).unwrap();
+        pool.insert(&state, transfer("alice", 1, 9_000)).unwrap();
+        pool.insert(&state, transfer("bob", 0, 5_000)).unwrap();
+
+        // Alice's high fee waits behind her own earlier nonce
+        let ready = nonces(&pool.ready(&state, 10));
+        assert_eq!(ready, [
+            ("bob".to_string(), 0),
+            ("alice".to_string(), 0),
+            ("alice".to_string(), 1),
+        ]);
+        assert_eq!(pool.ready(&state, 1).len(), 1);
+    }
+
+    #[test]
+    fn evicts_the_lowest_rate_when_full() {
+        let state = state();
+        let mut pool = Mempool::new(MempoolConfig { max_transactions: 2, ..MempoolConfig::default() });
+
+        pool.insert(&state, transfer("alice", 0, 1_000)).unwrap();
+        pool.insert(&state, transfer("bob", 0, 3_000)).unwrap();
+        assert!(pool.insert(&state, transfer("carol", 0, 900)).is_err());
+
+        pool.insert(&state, transfer("carol", 0, 2_000)).unwrap();
+        assert_eq!(pool.len(), 2);
+        let mut senders: Vec<_> = pool.pending(&state).into_iter().map(|tx| tx.sender).collect();
+        senders.sort();
+        assert_eq!(senders, ["bob", "carol"]);
+    }
+
+    #[test]
+    fn prunes_included_and_unaffordable_transactions() {
+        let mut state = state();
+        let mut pool = Mempool::new(MempoolConfig::default());
+
+        pool.insert(&state, transfer("alice", 0, 1_000)).unwrap();
+        pool.insert(&state, transfer("alice", 1, 1_000)).unwrap();
+        pool.insert(&state, transfer("bob", 0, 1_000)).unwrap();
+
+        state.accounts.get_mut("alice").unwrap().nonce = 1;
+        state.accounts.get_mut("bob").unwrap().balance = 0;
+        assert_eq!(pool.prune(&state), 2);
+
+        assert_eq!(nonces(&pool.ready(&state, 10)), [("alice".to_string(), 1)]);
+    }
+}
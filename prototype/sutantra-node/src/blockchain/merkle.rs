@@ -0,0 +1,302 @@
+// Sorted binary Merkle tree with inclusion and exclusion proofs
+//
+// Leaves are sorted by key. A level with an odd number of nodes promotes its
+// last node unchanged, and the published root also commits to the leaf count,
+// so a proof's leaf index and the side of each sibling are bound to the root.
+// Exclusion is proven by showing the two adjacent leaves that would surround
+// the key.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+const ROOT_PREFIX: u8 = 0x02;
+
+pub type Hash = [u8; 32];
+
+/// Merkle tree over sorted key/value leaves
+pub struct MerkleTree {
+    keys: Vec<Vec<u8>>,
+    value_hashes: Vec<Hash>,
+    /// levels[0] holds the leaf hashes, the last level holds the root node
+    levels: Vec<Vec<Hash>>,
+}
+
+/// Path from a leaf to the root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MerkleProof {
+    pub leaf_index: u64,
+    pub leaf_count: u64,
+    pub siblings: Vec<Hash>,
+}
+
+/// A leaf that borders an absent key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Neighbor {
+    pub key: Vec<u8>,
+    pub value_hash: Hash,
+    pub proof: MerkleProof,
+}
+
+/// Proof that a key is or is not in the tree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum MembershipProof {
+    /// The key is present with the accompanying value
+    Inclusion(MerkleProof),
+    /// The key is absent: `left` and `right` are adjacent leaves around it
+    Exclusion {
+        leaf_count: u64,
+        left: Option<Neighbor>,
+        right: Option<Neighbor>,
+    },
+}
+
+impl MerkleTree {
+    /// Build a tree from unsorted key/value pairs; keys must be unique
+    pub fn new(mut entries: Vec<(Vec<u8>, Vec<u8>)>) -> Self {
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut keys = Vec::with_capacity(entries.len());
+        let mut value_hashes = Vec::with_capacity(entries.len());
+        let mut leaves = Vec::with_capacity(entries.len());
+        for (key, value) in entries {
+            let value_hash = *blake3::hash(&value).as_bytes();
+            leaves.push(leaf_hash(&key, &value_hash));
+            keys.push(key);
+            value_hashes.push(value_hash);
+        }
+
+        let mut levels = vec![leaves];
+        while levels.last().unwrap().len() > 1 {
+            let level = levels.last().unwrap();
+            let next = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+
+        Self { keys, value_hashes, levels }
+    }
+
+    /// Root committing to the leaf count and tree contents
+    pub fn root(&self) -> Hash {
+        let tree_root = self.levels.last().unwrap().first().copied().unwrap_or([0u8; 32]);
+        committed_root(self.keys.len() as u64, &tree_root)
+    }
+
+    /// Prove membership or non-membership of `key`
+    pub fn prove(&self, key: &[u8]) -> MembershipProof {
+        match self.keys.binary_search_by(|probe| probe.as_slice().cmp(key)) {
+            Ok(index) => MembershipProof::Inclusion(self.path(index)),
+            Err(insert_at) => MembershipProof::Exclusion {
+                leaf_count: self.keys.len() as u64,
+                left: insert_at.checked_sub(1).map(|index| self.neighbor(index)),
+                right: (insert_at < self.keys.len()).then(|| self.neighbor(insert_at)),
+            },
+        }
+    }
+
+    fn neighbor(&self, index: usize) -> Neighbor {
+        Neighbor {
+            key: self.keys[index].clone(),
+            value_hash: self.value_hashes[index],
+            proof: self.path(index),
+        }
+    }
+
+    fn path(&self, leaf_index: usize) -> MerkleProof {
+        let mut siblings = Vec::new();
+        let mut index = leaf_index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = index ^ 1;
+            if sibling < level.len() {
+                siblings.push(level[sibling]);
+            }
+            index /= 2;
+        }
+
+        MerkleProof {
+            leaf_index: leaf_index as u64,
+            leaf_count: self.keys.len() as u64,
+            siblings,
+        }
+    }
+}
+
+impl MerkleProof {
+    /// Root implied by this path for the given leaf
+    fn root_for(&self, key: &[u8], value_hash: &Hash) -> Result<Hash> {
+        if self.leaf_index >= self.leaf_count {
+            return Err(anyhow!("Leaf index {} out of range", self.leaf_index));
+        }
+
+        let mut hash = leaf_hash(key, value_hash);
+        let mut index = self.leaf_index;
+        let mut level_len = self.leaf_count;
+        let mut siblings = self.siblings.iter();
+
+        while level_len > 1 {
+            if index % 2 == 1 {
+                let left = siblings.next().ok_or_else(|| anyhow!("Proof too short"))?;
+                hash = node_hash(left, &hash);
+            } else if index + 1 < level_len {
+                let right = siblings.next().ok_or_else(|| anyhow!("Proof too short"))?;
+                hash = node_hash(&hash, right);
+            }
+            index /= 2;
+            level_len = level_len.div_ceil(2);
+        }
+
+        if siblings.next().is_some() {
+            return Err(anyhow!("Proof too long"));
+        }
+
+        Ok(committed_root(self.leaf_count, &hash))
+    }
+}
+
+impl MembershipProof {
+    /// Check the proof against `root`
+    ///
+    /// `value` must be `Some` for an inclusion proof and `None` for an
+    /// exclusion proof.
+    pub fn verify(&self, root: &Hash, key: &[u8], value: Option<&[u8]>) -> Result<()> {
+        match (self, value) {
+            (MembershipProof::Inclusion(proof), Some(value)) => {
+                let value_hash = *blake3::hash(value).as_bytes();
+                if proof.root_for(key, &value_hash)? != *root {
+                    return Err(anyhow!("Inclusion proof does not match root"));
+                }
+            }
+
+            (MembershipProof::Exclusion { leaf_count, left, right }, None) => {
+                if let Some(left) = left {
+                    if left.key.as_slice() >= key || left.proof.leaf_count != *leaf_count {
+                        return Err(anyhow!("Left neighbor does not precede key"));
+                    }
+                    if left.proof.root_for(&left.key, &left.value_hash)? != *root {
+                        return Err(anyhow!("Left neighbor proof does not match root"));
+                    }
+                }
+                if let Some(right) = right {
+                    if right.key.as_slice() <= key || right.proof.leaf_count != *leaf_count {
+                        return Err(anyhow!("Right neighbor does not follow key"));
+                    }
+                    if right.proof.root_for(&right.key, &right.value_hash)? != *root {
+                        return Err(anyhow!("Right neighbor proof does not match root"));
+                    }
+                }
+
+                // The neighbors must be adjacent leaves (or tree edges)
+                let adjacent = match (left, right) {
+                    (Some(l), Some(r)) => r.proof.leaf_index == l.proof.leaf_index + 1,
+                    (Some(l), None) => l.proof.leaf_index + 1 == *leaf_count,
+                    (None, Some(r)) => r.proof.leaf_index == 0,
+                    (None, None) => *leaf_count == 0 && committed_root(0, &[0u8; 32]) == *root,
+                };
+                if !adjacent {
+                    return Err(anyhow!("Exclusion neighbors are not adjacent"));
+                }
+            }
+
+            _ => return Err(anyhow!("Proof kind does not match claimed value")),
+        }
+
+        Ok(())
+    }
+}
+
+fn leaf_hash(key: &[u8], value_hash: &Hash) -> Hash {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[LEAF_PREFIX]);
+    hasher.update(&(key.len() as u64).to_le_bytes());
+    hasher.update(key);
+    hasher.update(value_hash);
+    *hasher.finalize().as_bytes()
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    *hasher.finalize().as_bytes()
+}
+
+fn committed_root(leaf_count: u64, tree_root: &Hash) -> Hash {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[ROOT_PREFIX]);
+    hasher.update(&leaf_count.to_le_bytes());
+    hasher.update(tree_root);
+    *hasher.finalize().as_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(keys: &[&str]) -> MerkleTree {
+        MerkleTree::new(keys.iter().map(|key| (key.as_bytes().to_vec(), value(key))).collect())
+    }
+
+    fn value(key: &str) -> Vec<u8> {
+        format!("value of {}", key).into_bytes()
+    }
+
+    #[test]
+    fn proves_every_key_in_trees_of_any_size() {
+        let keys = ["a", "c", "e", "g", "i", "k", "m"];
+        for len in 1..=keys.len() {
+            let tree = tree(&keys[..len]);
+            let root = tree.root();
+            for key in &keys[..len] {
+                tree.prove(key.as_bytes()).verify(&root, key.as_bytes(), Some(&value(key))).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn inclusion_rejects_a_wrong_value_or_root() {
+        let root = tree(&["a", "b", "c"]).root();
+        let proof = tree(&["a", "b", "c"]).prove(b"b");
+
+        assert!(proof.verify(&root, b"b", Some(b"forged")).is_err());
+        assert!(proof.verify(&root, b"b", None).is_err());
+        assert!(proof.verify(&tree(&["a", "b"]).root(), b"b", Some(&value("b"))).is_err());
+    }
+
+    #[test]
+    fn proves_absent_keys_between_and_past_the_leaves() {
+        let tree = tree(&["b", "d", "f"]);
+        let root = tree.root();
+
+        for key in ["a", "c", "e", "g"] {
+            tree.prove(key.as_bytes()).verify(&root, key.as_bytes(), None).unwrap();
+        }
+        assert!(tree.prove(b"d").verify(&root, b"d", None).is_err());
+
+        let empty = MerkleTree::new(Vec::new());
+        empty.prove(b"a").verify(&empty.root(), b"a", None).unwrap();
+    }
+
+    #[test]
+    fn exclusion_rejects_neighbors_that_skip_a_leaf() {
+        let tree = tree(&["b", "d", "f"]);
+        let MembershipProof::Exclusion { leaf_count, left, .. } = tree.prove(b"c") else {
+            panic!("c is not in the tree");
+        };
+        let MembershipProof::Exclusion { right, .. } = tree.prove(b"e") else {
+            panic!("e is not in the tree");
+        };
+
+        // "b" and "f" surround "d", hiding it
+        let forged = MembershipProof::Exclusion { leaf_count, left, right };
+        assert!(forged.verify(&tree.root(), b"d", None).is_err());
+    }
+}
@@ -4,13 +4,36 @@ use tracing::{info, warn};
 use serde::{Serialize, Deserialize};
 
 use crate::integration::StreamQualityMetrics;
+use channels::ChannelUpdate;
+use pricing::PricingModel;
+use slashing::Evidence;
+use splits::{Payout, RevenueSplit};
+use staking::StakeRole;
 
 pub mod engine;
 pub mod state;
 pub mod consensus;
 pub mod transactions;
+pub mod storage;
+pub mod block;
+pub mod crypto;
+pub mod merkle;
+pub mod fork_choice;
+pub mod genesis;
+pub mod mempool;
+pub mod fees;
+pub mod channels;
+pub mod sessions;
+pub mod rewards;
+pub mod staking;
+pub mod slashing;
+pub mod splits;
+pub mod pricing;
 
-pub use engine::BlockchainEngine;
+pub use engine::{BlockchainEngine, ChainReader, ChainStatus};
+pub use consensus::Vote;
+pub use genesis::GenesisSpec;
+pub use mempool::MempoolConfig;
 
 /// Configuration for the blockchain engine
 #[derive(Debug, Clone)]
@@ -18,6 +41,10 @@ pub struct BlockchainConfig {
     pub port: u16,
     pub is_validator: bool,
     pub data_dir: String,
+    pub genesis: GenesisSpec,
+    pub mempool: MempoolConfig,
+    /// Seconds a viewer keeps access after `paid_until` passes
+    pub access_grace_secs: u64,
 }
 
 /// Events emitted by the blockchain layer
@@ -55,6 +82,23 @@ pub enum BlockchainEvent {
         block_hash: String 
     },
     
+    /// A block from a peer was validated and appended to the chain
+    BlockImported {
+        block_number: u64,
+        block_hash: String,
+    },
+    
+    /// The local validator voted to finalize a block
+    VoteCast {
+        vote: Vote,
+    },
+    
+    /// A block reached finality
+    BlockFinalized {
+        block_number: u64,
+        block_hash: String,
+    },
+    
     /// Blockchain sync status changed
     SyncStatusChanged { 
         is_synced: bool, 
@@ -65,22 +109,6 @@ pub enum BlockchainEvent {
 /// Commands that can be sent to the blockchain layer
 #[derive(Debug, Clone)]
 pub enum BlockchainCommand {
-    /// Register a new stream
-    RegisterStream {
-        stream_id: String,
-        creator: String,
-        title: String,
-        description: Option<String>,
-        price_per_minute: u64,
-    },
-    
-    /// Process a payment for stream access
-    ProcessPayment {
-        stream_id: String,
-        viewer: String,
-        amount: u64,
-    },
-    
     /// Check if viewer has access to stream
     CheckAccess {
         stream_id: String,
@@ -100,17 +128,36 @@ pub enum BlockchainCommand {
         duration_seconds: u64,
     },
     
+    /// Record that a viewer joined a live stream
+    RecordViewerJoin {
+        stream_id: String,
+        viewer: String,
+    },
+    
     /// Report stream quality metrics for validator rewards
     ReportStreamQuality {
         stream_id: String,
         metrics: StreamQualityMetrics,
     },
     
-    /// Transfer tokens between accounts
-    Transfer {
-        from: String,
-        to: String,
-        amount: u64,
+    /// Submit a signed transaction to the transaction pool
+    SubmitTransaction {
+        transaction: Transaction,
+    },
+    
+    /// Submit a finality vote from a validator
+    SubmitVote {
+        vote: Vote,
+    },
+    
+    /// Validate and import a block received from a peer
+    ImportBlock {
+        block: Block,
+    },
+    
+    /// Best block reported by peers, the height sync is catching up to
+    UpdateSyncTarget {
+        best_block: u64,
     },
 }
 
@@ -122,11 +169,23 @@ pub struct StreamRegistration {
     pub title: String,
     pub description: Option<String>,
     pub price_per_minute: u64,
+    /// How viewers pay; `price_per_minute` applies to per-minute pricing
+    #[serde(default)]
+    pub pricing: PricingModel,
+    /// Free minutes each viewer of a paid stream gets once
+    #[serde(default)]
+    pub preview_minutes: u32,
     pub created_at: chrono::DateTime<chrono::Utc>,
-    pub is_active: bool,
     pub total_earnings: u64,
-    pub total_viewers: u32,
-    pub total_duration_minutes: u64,
+    /// How payments to the stream are shared
+    #[serde(default)]
+    pub split: RevenueSplit,
+    /// Total paid to each recipient of the stream's revenue
+    #[serde(default)]
+    pub paid_out: std::collections::BTreeMap<String, u64>,
+    /// Most recent payouts, oldest first
+    #[serde(default)]
+    pub recent_payouts: Vec<Payout>,
 }
 
 /// Account balance and stream access information
@@ -149,6 +208,11 @@ pub struct StreamAccess {
 }
 
 /// Transaction types supported by Sutantra blockchain
+///
+/// Senders are identified by their address (hex-encoded ed25519 public key)
+/// and every variant carries the sender's next account nonce, the fee paid to
+/// the block author, and a hex ed25519 signature over
+/// `Transaction::signing_payload`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Transaction {
     /// Transfer STREAM tokens
@@ -157,6 +221,7 @@ pub enum Transaction {
         to: String,
         amount: u64,
         nonce: u64,
+        fee: u64,
         signature: String,
     },
     
@@ -164,6 +229,8 @@ pub enum Transaction {
     RegisterStream {
         creator: String,
         stream_data: StreamRegistration,
+        nonce: u64,
+        fee: u64,
         signature: String,
     },
     
@@ -172,15 +239,90 @@ pub enum Transaction {
         viewer: String,
         stream_id: String,
         amount: u64,
+        /// Informational; the time bought follows from `amount` and the stream's pricing
         duration_minutes: u64,
+        nonce: u64,
+        fee: u64,
         signature: String,
     },
     
-    /// Report stream quality (validator only)
+    /// Report the quality a relay delivered for a stream (validator only)
     ReportQuality {
         validator: String,
+        relay: String,
         stream_id: String,
         metrics: StreamQualityMetrics,
+        nonce: u64,
+        fee: u64,
+        signature: String,
+    },
+    
+    /// Lock a deposit in a payment channel to a stream's creator
+    OpenChannel {
+        viewer: String,
+        stream_id: String,
+        deposit: u64,
+        nonce: u64,
+        fee: u64,
+        signature: String,
+    },
+    
+    /// Close a payment channel, settling it at the latest signed update
+    ///
+    /// A close by the creator settles at once; a close by the viewer waits
+    /// out the dispute window.
+    CloseChannel {
+        closer: String,
+        channel_id: String,
+        update: Option<ChannelUpdate>,
+        nonce: u64,
+        fee: u64,
+        signature: String,
+    },
+    
+    /// Bond stake as a validator or relay; bonding again adds to it and sets the role
+    Bond {
+        staker: String,
+        amount: u64,
+        role: StakeRole,
+        nonce: u64,
+        fee: u64,
+        signature: String,
+    },
+    
+    /// Start unbonding stake, which can be withdrawn after the unbonding period
+    Unbond {
+        staker: String,
+        amount: u64,
+        nonce: u64,
+        fee: u64,
+        signature: String,
+    },
+    
+    /// Return unbonded stake whose unbonding period has passed to the balance
+    Withdraw {
+        staker: String,
+        nonce: u64,
+        fee: u64,
+        signature: String,
+    },
+    
+    /// Replace the revenue split of a stream the sender created
+    SetRevenueSplit {
+        creator: String,
+        stream_id: String,
+        split: RevenueSplit,
+        nonce: u64,
+        fee: u64,
+        signature: String,
+    },
+    
+    /// Submit evidence of misbehavior to slash and jail the offender
+    ReportMisbehavior {
+        reporter: String,
+        evidence: Evidence,
+        nonce: u64,
+        fee: u64,
         signature: String,
     },
 }
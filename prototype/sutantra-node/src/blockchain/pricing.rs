@@ -0,0 +1,381 @@
+// Stream pricing models
+//
+// A stream is priced one of four ways. Per-minute streams sell watch time at
+// `price_per_minute`. A subscription is bought per period from a creator and
+// unlocks every subscription stream that creator runs. An event ticket is
+// bought once at a fixed price and gives access until the event ends. Free
+// streams need no payment, and anything paid to them is a tip. Paid streams
+// can also give each viewer some free preview minutes, once.
+//
+// All paid access is kept as `StreamAccess` grants, so it expires and is
+// revoked the same way under every model. Grants are keyed by stream id,
+// except subscriptions, which are keyed by the creator's subscription key.
+// Previews are not paid for and are handed out by whichever node the viewer
+// connects to, so they live in a node-local `PreviewBook` rather than the
+// chain state.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::engine::BlockchainState;
+use super::{Account, StreamAccess, StreamRegistration};
+
+/// Prefix of the access keys subscriptions are granted under
+const SUBSCRIPTION_KEY_PREFIX: &str = "subscription:";
+
+/// Longest subscription period, in days
+const MAX_PERIOD_DAYS: u32 = 366;
+
+/// How viewers pay for a stream
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PricingModel {
+    /// Watch time bought at the stream's `price_per_minute`
+    #[default]
+    PerMinute,
+    /// `price` per period of `period_days`, for all of the creator's subscription streams
+    Subscription { price: u64, period_days: u32 },
+    /// One `price` for access until the event ends at `ends_at`
+    Ticket { price: u64, ends_at: chrono::DateTime<chrono::Utc> },
+    /// No payment needed; payments are tips
+    Free,
+}
+
+/// What a payment buys
+#[derive(Debug, Clone, PartialEq)]
+pub struct Purchase {
+    /// Part of the payment taken; the rest stays with the viewer
+    pub charged: u64,
+    /// Access key and the new end of the grant under it, `None` for a tip
+    pub grant: Option<(String, chrono::DateTime<chrono::Utc>)>,
+}
+
+/// Access key a creator's subscription is granted under
+pub fn subscription_key(creator: &str) -> String {
+    format!("{}{}", SUBSCRIPTION_KEY_PREFIX, creator)
+}
+
+/// Creator an access key subscribes to, if it is a subscription key
+pub fn subscribed_creator(key: &str) -> Option<&str> {
+    key.strip_prefix(SUBSCRIPTION_KEY_PREFIX)
+}
+
+impl PricingModel {
+    /// Check the model's prices and periods are usable
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            PricingModel::Subscription { price, period_days } => {
+                if *price == 0 || *period_days == 0 {
+                    return Err(anyhow!("Subscriptions need a price and a period of at least one day"));
+                }
+                if *period_days > MAX_PERIOD_DAYS {
+                    return Err(anyhow!("Subscription periods are at most {} days, {} given", MAX_PERIOD_DAYS, period_days));
+                }
+            }
+            PricingModel::Ticket { price, .. } => {
+                if *price == 0 {
+                    return Err(anyhow!("Tickets need a price"));
+                }
+            }
+            PricingModel::PerMinute | PricingModel::Free => {}
+        }
+        Ok(())
+    }
+}
+
+impl StreamRegistration {
+    /// Key of the grant that paid access to the stream is kept under, `None` for a free stream
+    pub fn access_key(&self) -> Option<String> {
+        match &self.pricing {
+            PricingModel::PerMinute | PricingModel::Ticket { .. } => Some(self.stream_id.clone()),
+            PricingModel::Subscription { .. } => Some(subscription_key(&self.creator)),
+            PricingModel::Free => None,
+        }
+    }
+
+    /// End of a viewer's access to the stream, counting previews and subscriptions
+    pub fn paid_until(&self, account: &Account) -> Option<chrono::DateTime<chrono::Utc>> {
+        let own = account.stream_access.get(&self.stream_id).map(|access| access.paid_until);
+        let keyed = self.access_key()
+            .and_then(|key| account.stream_access.get(&key))
+            .map(|access| access.paid_until);
+        own.max(keyed)
+    }
+
+    /// What paying `amount` at `now` buys a viewer under the stream's pricing
+    ///
+    /// Time bought extends any paid time left. Subscriptions are bought in
+    /// whole periods and tickets at their price; the rest of `amount` is not
+    /// charged.
+    pub fn purchase(&self, account: Option<&Account>, amount: u64, now: chrono::DateTime<chrono::Utc>) -> Result<Purchase> {
+        let current = self.access_key()
+            .and_then(|key| account?.stream_access.get(&key))
+            .map_or(now, |access| access.paid_until.max(now));
+
+        let (charged, paid_until) = match &self.pricing {
+            PricingModel::PerMinute => {
+                let seconds = i64::try_from(amount as u128 * 60 / self.price_per_minute.max(1) as u128).ok();
+                let paid_until = seconds
+                    .and_then(chrono::TimeDelta::try_seconds)
+                    .and_then(|bought| current.checked_add_signed(bought))
+                    .ok_or_else(|| anyhow!("{} buys more watch time on stream {} than can be recorded", amount, self.stream_id))?;
+                (amount, paid_until)
+            }
+            PricingModel::Subscription { price, period_days } => {
+                let periods = amount / (*price).max(1);
+                if periods == 0 {
+                    return Err(anyhow!("A subscription to {} costs {}, {} given", self.creator, price, amount));
+                }
+                let paid_until = periods.checked_mul(*period_days as u64)
+                    .and_then(|days| i64::try_from(days).ok())
+                    .and_then(chrono::TimeDelta::try_days)
+                    .and_then(|bought| current.checked_add_signed(bought))
+                    .ok_or_else(|| anyhow!("{} buys more of {}'s subscription than can be recorded", amount, self.creator))?;
+                (periods * price, paid_until)
+            }
+            PricingModel::Ticket { price, ends_at } => {
+                if now >= *ends_at {
+                    return Err(anyhow!("The event on stream {} is over", self.stream_id));
+                }
+                if current >= *ends_at {
+                    return Err(anyhow!("Viewer already holds a ticket for stream {}", self.stream_id));
+                }
+                if amount < *price {
+                    return Err(anyhow!("A ticket for stream {} costs {}, {} given", self.stream_id, price, amount));
+                }
+                (*price, *ends_at)
+            }
+            PricingModel::Free => return Ok(Purchase { charged: amount, grant: None }),
+        };
+
+        let key = self.access_key().expect("paid streams have an access key");
+        Ok(Purchase { charged, grant: Some((key, paid_until)) })
+    }
+
+    /// Whether the stream offers a preview to a viewer who never had access to it
+    fn offers_preview(&self, account: Option<&Account>) -> bool {
+        if self.preview_minutes == 0 || self.pricing == PricingModel::Free {
+            return false;
+        }
+        account.is_none_or(|account| self.paid_until(account).is_none())
+    }
+}
+
+/// Free previews this node has given, by (stream id, viewer)
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PreviewBook {
+    previews: BTreeMap<(String, String), chrono::DateTime<chrono::Utc>>,
+}
+
+impl PreviewBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Give a viewer the stream's free preview, unless they had it or paid access before
+    pub fn grant(
+        &mut self,
+        stream: &StreamRegistration,
+        viewer: &str,
+        account: Option<&Account>,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> bool {
+        let key = (stream.stream_id.clone(), viewer.to_string());
+        if self.previews.contains_key(&key) || !stream.offers_preview(account) {
+            return false;
+        }
+
+        self.previews.insert(key, now + chrono::Duration::minutes(stream.preview_minutes as i64));
+        true
+    }
+
+    /// End of a viewer's preview of a stream
+    pub fn preview_until(&self, stream_id: &str, viewer: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.previews.get(&(stream_id.to_string(), viewer.to_string())).copied()
+    }
+
+    /// Every preview given, keyed by (stream id, viewer) like `access_grants`
+    pub fn previews(&self) -> impl Iterator<Item = ((String, String), chrono::DateTime<chrono::Utc>)> + '_ {
+        self.previews.iter().map(|(key, until)| (key.clone(), *until))
+    }
+}
+
+impl Account {
+    /// Set the grant under `key` to end at `paid_until`, adding `amount` to what it cost
+    pub fn grant_access(
+        &mut self,
+        key: &str,
+        paid_until: chrono::DateTime<chrono::Utc>,
+        amount: u64,
+        now: chrono::DateTime<chrono::Utc>,
+    ) {
+        let access = self.stream_access.entry(key.to_string()).or_insert_with(|| StreamAccess {
+            stream_id: key.to_string(),
+            paid_until: now,
+            total_paid: 0,
+            access_granted_at: now,
+        });
+        access.paid_until = paid_until;
+        access.total_paid += amount;
+    }
+}
+
+/// Every viewer's paid access per stream, with subscriptions spread over the creator's subscription streams
+pub fn access_grants(state: &BlockchainState) -> Vec<((String, String), chrono::DateTime<chrono::Utc>)> {
+    let mut grants = Vec::new();
+    for account in state.accounts.values() {
+        for (key, access) in &account.stream_access {
+            let Some(creator) = subscribed_creator(key) else {
+                grants.push(((key.clone(), account.address.clone()), access.paid_until));
+                continue;
+            };
+
+            let streams = state.accounts.get(creator).map_or(&[][..], |creator| &creator.created_streams);
+            for stream_id in streams {
+                if state.streams.get(stream_id)
+                    .is_some_and(|stream| matches!(stream.pricing, PricingModel::Subscription { .. }))
+                {
+                    grants.push(((stream_id.clone(), account.address.clone()), access.paid_until));
+                }
+            }
+        }
+    }
+    grants
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::genesis::GenesisSpec;
+
+    fn stream(stream_id: &str, pricing: PricingModel) -> StreamRegistration {
+        StreamRegistration {
+            stream_id: stream_id.to_string(),
+            creator: "creator".to_string(),
+            title: "Stream".to_string(),
+            description: None,
+            price_per_minute: 10,
+            pricing,
+            preview_minutes: 5,
+            created_at: chrono::Utc::now(),
+            total_earnings: 0,
+            split: Default::default(),
+            paid_out: Default::default(),
+            recent_payouts: Vec::new(),
+        }
+    }
+
+    fn viewer() -> Account {
+        Account {
+            address: "viewer".to_string(),
+            balance: 10_000,
+            nonce: 0,
+            stream_access: Default::default(),
+            created_streams: Vec::new(),
+        }
+    }
+
+    /// Buy access and record the grant on the account
+    fn buy(
+        stream: &StreamRegistration,
+        account: &mut Account,
+        amount: u64,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Purchase> {
+        let purchase = stream.purchase(Some(account), amount, now)?;
+        if let Some((key, paid_until)) = &purchase.grant {
+            account.grant_access(key, *paid_until, purchase.charged, now);
+        }
+        Ok(purchase)
+    }
+
+    #[test]
+    fn per_minute_time_extends_what_is_left() {
+        let stream = stream("stream", PricingModel::PerMinute);
+        let mut account = viewer();
+        let now = chrono::Utc::now();
+
+        let purchase = buy(&stream, &mut account, 50, now).unwrap();
+        let five_minutes = now + chrono::Duration::minutes(5);
+        assert_eq!(purchase, Purchase { charged: 50, grant: Some(("stream".to_string(), five_minutes)) });
+        let purchase = buy(&stream, &mut account, 25, now + chrono::Duration::minutes(1)).unwrap();
+        assert_eq!(purchase.grant.unwrap().1, now + chrono::Duration::seconds(450));
+        assert_eq!(account.stream_access["stream"].total_paid, 75);
+    }
+
+    #[test]
+    fn subscriptions_sell_whole_periods_for_all_of_a_creators_streams() {
+        let pricing = PricingModel::Subscription { price: 300, period_days: 30 };
+        let mut account = viewer();
+        let now = chrono::Utc::now();
+
+        assert!(buy(&stream("first", pricing.clone()), &mut account, 299, now).is_err());
+        let purchase = buy(&stream("first", pricing.clone()), &mut account, 700, now).unwrap();
+        assert_eq!(purchase.charged, 600);
+        assert_eq!(purchase.grant, Some((subscription_key("creator"), now + chrono::Duration::days(60))));
+
+        let mut state = GenesisSpec::default().build().0;
+        let streams = [("first", pricing.clone()), ("second", pricing), ("per-minute", PricingModel::PerMinute)];
+        for (stream_id, pricing) in streams {
+            state.streams.insert(stream_id.to_string(), stream(stream_id, pricing));
+        }
+        let creator = Account {
+            address: "creator".to_string(),
+            created_streams: vec!["first".to_string(), "second".to_string(), "per-minute".to_string()],
+            ..viewer()
+        };
+        state.accounts.insert("creator".to_string(), creator);
+        state.accounts.insert("viewer".to_string(), account);
+
+        let mut grants: Vec<String> = access_grants(&state).into_iter().map(|((stream_id, _), _)| stream_id).collect();
+        grants.sort();
+        assert_eq!(grants, ["first", "second"]);
+        assert_eq!(state.streams["second"].paid_until(&state.accounts["viewer"]), Some(now + chrono::Duration::days(60)));
+    }
+
+    #[test]
+    fn tickets_are_bought_once_before_the_event_ends() {
+        let now = chrono::Utc::now();
+        let ends_at = now + chrono::Duration::hours(2);
+        let stream = stream("event", PricingModel::Ticket { price: 500, ends_at });
+        let mut account = viewer();
+
+        assert!(buy(&stream, &mut account, 499, now).is_err());
+        let purchase = buy(&stream, &mut account, 800, now).unwrap();
+        assert_eq!(purchase, Purchase { charged: 500, grant: Some(("event".to_string(), ends_at)) });
+        assert!(buy(&stream, &mut account, 500, now).is_err());
+        assert!(stream.purchase(None, 500, ends_at).is_err());
+    }
+
+    #[test]
+    fn free_streams_take_tips_and_offer_no_preview() {
+        let free = stream("free", PricingModel::Free);
+        let now = chrono::Utc::now();
+        assert_eq!(free.purchase(None, 40, now).unwrap(), Purchase { charged: 40, grant: None });
+        assert!(!PreviewBook::new().grant(&free, "viewer", None, now));
+    }
+
+    #[test]
+    fn previews_are_given_once_to_viewers_who_never_paid() {
+        let stream = stream("stream", PricingModel::PerMinute);
+        let now = chrono::Utc::now();
+        let mut previews = PreviewBook::new();
+
+        assert!(previews.grant(&stream, "viewer", None, now));
+        assert!(!previews.grant(&stream, "viewer", None, now + chrono::Duration::hours(1)));
+        assert_eq!(previews.preview_until("stream", "viewer"), Some(now + chrono::Duration::minutes(5)));
+
+        let mut paid = viewer();
+        buy(&stream, &mut paid, 10, now).unwrap();
+        assert!(!previews.grant(&stream, "payer", Some(&paid), now));
+    }
+
+    #[test]
+    fn validates_prices_and_periods() {
+        assert!(PricingModel::Subscription { price: 0, period_days: 30 }.validate().is_err());
+        assert!(PricingModel::Subscription { price: 1, period_days: 0 }.validate().is_err());
+        assert!(PricingModel::Subscription { price: 1, period_days: MAX_PERIOD_DAYS + 1 }.validate().is_err());
+        assert!(PricingModel::Ticket { price: 0, ends_at: chrono::Utc::now() }.validate().is_err());
+        assert!(PricingModel::Subscription { price: 1, period_days: MAX_PERIOD_DAYS }.validate().is_ok());
+    }
+}
@@ -0,0 +1,257 @@
+// Relay rewards from stream quality reports
+//
+// Validators report the delivery quality they observe for a stream and the
+// relay that served it with `ReportQuality` transactions. Each report is
+// scored from 0 to 100 over latency, packet loss, frame rate and bandwidth,
+// and a reporter's latest report per relay and stream counts for the epoch.
+// At the end of every epoch the reports for each relay and stream are
+// cross-checked: scores further than the tolerance from the median are
+// discarded as outliers, and a pair needs enough agreeing reporters to count.
+// The reward pool, a share of block fees plus a fixed amount minted each
+// epoch, is paid to relays in proportion to their summed scores.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::integration::StreamQualityMetrics;
+
+/// Relay reward parameters fixed at genesis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RewardParams {
+    /// STREAM minted for relays at the end of each epoch that has scored relays
+    pub epoch_reward: u64,
+    /// Percentage of the block author's fee share diverted to the relay pool
+    pub relay_fee_share_percent: u8,
+    /// Largest distance in score points from the median a report may have
+    pub outlier_tolerance: u32,
+    /// Fewest agreeing reporters a relay and stream need to be scored, so no
+    /// validator scores a relay on its own word
+    pub min_reporters: u32,
+}
+
+impl Default for RewardParams {
+    fn default() -> Self {
+        Self {
+            epoch_reward: 10_000,
+            relay_fee_share_percent: 20,
+            outlier_tolerance: 20,
+            min_reporters: 2,
+        }
+    }
+}
+
+/// Quality score from 0 to 100 for one report
+///
+/// Latency counts for 30 points (full at 50 ms, none from 1 s), packet loss
+/// for 30 (none from 10%), frame rate for 20 (full at 30 fps) and bandwidth
+/// for 20 (full at 5 Mbps).
+pub fn score(metrics: &StreamQualityMetrics) -> u32 {
+    let latency = 1.0 - ((metrics.latency_ms as f64 - 50.0) / 950.0).clamp(0.0, 1.0);
+    let loss = 1.0 - (metrics.packet_loss_percent / 10.0).clamp(0.0, 1.0);
+    let fps = (metrics.fps as f64 / 30.0).clamp(0.0, 1.0);
+    let bandwidth = (metrics.bandwidth_mbps / 5.0).clamp(0.0, 1.0);
+
+    // Reported floats may be NaN; those earn nothing
+    let points = |fraction: f64, weight: f64| if fraction.is_nan() { 0.0 } else { fraction * weight };
+    (points(latency, 30.0) + points(loss, 30.0) + points(fps, 20.0) + points(bandwidth, 20.0)).round() as u32
+}
+
+/// Scores reported in the current epoch: relay → stream → reporter → score
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EpochReports {
+    pub epoch: u64,
+    reports: BTreeMap<String, BTreeMap<String, BTreeMap<String, u32>>>,
+}
+
+/// Progress of the current reward epoch, as reported by the node API
+#[derive(Debug, Clone, Serialize)]
+pub struct RewardStatus {
+    pub epoch: u64,
+    /// Last block of the epoch, where the pool is paid out
+    pub ends_at_block: u64,
+    pub reports: usize,
+    pub relay_pool: u64,
+    pub epoch_reward: u64,
+    /// Scores relays would be paid by if the epoch ended now
+    pub provisional: CrossCheck,
+}
+
+/// Outcome of cross-checking an epoch's reports
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CrossCheck {
+    /// Summed score of each relay over the streams it was scored for
+    pub relay_scores: BTreeMap<String, u64>,
+    /// Reports discarded as outliers, as (reporter, relay, stream, score)
+    pub outliers: Vec<(String, String, String, u32)>,
+}
+
+impl EpochReports {
+    pub fn new(epoch: u64) -> Self {
+        Self { epoch, reports: BTreeMap::new() }
+    }
+
+    /// Record a reporter's score, replacing their earlier report on the same relay and stream
+    pub fn record(&mut self, reporter: &str, relay: &str, stream_id: &str, score: u32) {
+        self.reports.entry(relay.to_string()).or_default()
+            .entry(stream_id.to_string()).or_default()
+            .insert(reporter.to_string(), score);
+    }
+
+    /// A reporter's recorded score for a relay and stream
+    pub fn score(&self, reporter: &str, relay: &str, stream_id: &str) -> Option<u32> {
+        self.reports.get(relay)?.get(stream_id)?.get(reporter).copied()
+    }
+
+    /// Reporters who scored a relay on a stream
+    pub fn reporters<'a>(&'a self, relay: &str, stream_id: &str) -> impl Iterator<Item = &'a String> + 'a {
+        self.reports.get(relay)
+            .and_then(|streams| streams.get(stream_id))
+            .into_iter()
+            .flat_map(|by_reporter| by_reporter.keys())
+    }
+
+    /// Strike every report a reporter made this epoch
+    pub fn remove_reporter(&mut self, reporter: &str) {
+        for streams in self.reports.values_mut() {
+            for by_reporter in streams.values_mut() {
+                by_reporter.remove(reporter);
+            }
+            streams.retain(|_, by_reporter| !by_reporter.is_empty());
+        }
+        self.reports.retain(|_, streams| !streams.is_empty());
+    }
+
+    /// Number of reports recorded this epoch
+    pub fn len(&self) -> usize {
+        self.reports.values().flat_map(|streams| streams.values()).map(BTreeMap::len).sum()
+    }
+
+    /// Score relays from the reports that agree with the median
+    pub fn cross_check(&self, params: &RewardParams) -> CrossCheck {
+        let mut result = CrossCheck::default();
+
+        for (relay, streams) in &self.reports {
+            for (stream_id, by_reporter) in streams {
+                let mut scores: Vec<u32> = by_reporter.values().copied().collect();
+                scores.sort_unstable();
+                let median = scores[scores.len() / 2];
+
+                let mut agreeing = Vec::new();
+                for (reporter, score) in by_reporter {
+                    if score.abs_diff(median) <= params.outlier_tolerance {
+                        agreeing.push(*score as u64);
+                    } else {
+                        result.outliers.push((reporter.clone(), relay.clone(), stream_id.clone(), *score));
+                    }
+                }
+
+                if agreeing.len() < params.min_reporters.max(1) as usize {
+                    continue;
+                }
+                let mean = agreeing.iter().sum::<u64>() / agreeing.len() as u64;
+                *result.relay_scores.entry(relay.clone()).or_default() += mean;
+            }
+        }
+
+        result
+    }
+}
+
+/// Split `pool` between relays in proportion to their scores
+///
+/// Rounding leftovers are not paid out and stay in the pool.
+pub fn payouts(pool: u64, relay_scores: &BTreeMap<String, u64>) -> Vec<(String, u64)> {
+    let total: u128 = relay_scores.values().map(|score| *score as u128).sum();
+    if total == 0 {
+        return Vec::new();
+    }
+
+    relay_scores.iter()
+        .map(|(relay, score)| (relay.clone(), (pool as u128 * *score as u128 / total) as u64))
+        .filter(|(_, amount)| *amount > 0)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(min_reporters: u32) -> RewardParams {
+        RewardParams { outlier_tolerance: 10, min_reporters, ..RewardParams::default() }
+    }
+
+    #[test]
+    fn scores_perfect_and_failed_delivery() {
+        let perfect = StreamQualityMetrics {
+            bandwidth_mbps: 8.0,
+            latency_ms: 20,
+            packet_loss_percent: 0.0,
+            resolution: "1080p".to_string(),
+            fps: 60,
+        };
+        assert_eq!(score(&perfect), 100);
+
+        let failed = StreamQualityMetrics {
+            bandwidth_mbps: f64::NAN,
+            latency_ms: 5_000,
+            packet_loss_percent: 50.0,
+            fps: 0,
+            ..perfect
+        };
+        assert_eq!(score(&failed), 0);
+    }
+
+    #[test]
+    fn cross_check_discards_outliers_from_the_median() {
+        let mut reports = EpochReports::new(0);
+        reports.record("v1", "relay", "stream", 80);
+        reports.record("v2", "relay", "stream", 90);
+        reports.record("v3", "relay", "stream", 10);
+
+        let checked = reports.cross_check(&params(2));
+        assert_eq!(checked.relay_scores, BTreeMap::from([("relay".to_string(), 85)]));
+        assert_eq!(checked.outliers, [("v3".to_string(), "relay".to_string(), "stream".to_string(), 10)]);
+    }
+
+    #[test]
+    fn cross_check_needs_enough_agreeing_reporters() {
+        let mut reports = EpochReports::new(0);
+        reports.record("v1", "relay", "stream", 80);
+        assert!(reports.cross_check(&params(2)).relay_scores.is_empty());
+
+        // A later report from the same reporter replaces the earlier one
+        reports.record("v1", "relay", "stream", 70);
+        assert_eq!(reports.len(), 1);
+        reports.record("v2", "relay", "stream", 70);
+        assert_eq!(reports.cross_check(&params(2)).relay_scores["relay"], 70);
+
+        reports.remove_reporter("v2");
+        assert!(reports.cross_check(&params(2)).relay_scores.is_empty());
+    }
+
+    #[test]
+    fn cross_check_sums_a_relays_streams() {
+        let mut reports = EpochReports::new(0);
+        for reporter in ["v1", "v2"] {
+            reports.record(reporter, "relay", "a", 60);
+            reports.record(reporter, "relay", "b", 40);
+        }
+
+        assert_eq!(reports.cross_check(&params(2)).relay_scores["relay"], 100);
+    }
+
+    #[test]
+    fn payouts_follow_scores_and_keep_rounding_leftovers() {
+        let scores = BTreeMap::from([
+            ("a".to_string(), 1),
+            ("b".to_string(), 2),
+            ("c".to_string(), 0),
+        ]);
+
+        let paid = payouts(100, &scores);
+        assert_eq!(paid, [("a".to_string(), 33), ("b".to_string(), 66)]);
+        assert!(payouts(100, &BTreeMap::new()).is_empty());
+        assert_eq!(payouts(u64::MAX, &scores)[1].1, u64::MAX / 3 * 2);
+    }
+}
@@ -0,0 +1,214 @@
+// Stream broadcast sessions
+//
+// A registered stream can go live many times. Each broadcast is a session
+// that records when it ran, the viewers who joined and what it earned, so a
+// creator can compare broadcasts. A stream's history adds up every session:
+// `total_duration_minutes` over the sessions' durations, and `total_viewers`
+// over distinct viewers across all of them. Only the most recent sessions are
+// kept, but the totals still count the dropped ones.
+//
+// Sessions are what this node saw of a broadcast, so they are node-local and
+// never part of the chain state: two nodes may disagree on when a stream went
+// live. Earnings are the exception, read from the stream's on-chain
+// `total_earnings` as the session starts and ends.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Most sessions kept per stream; older ones only count towards the totals
+const MAX_SESSIONS_PER_STREAM: usize = 100;
+
+/// One broadcast of a stream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamSession {
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    /// `None` while the stream is live
+    pub ended_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub duration_seconds: u64,
+    /// Stream's `total_earnings` when the session started
+    pub earnings_at_start: u64,
+    /// Payments to the stream while the session was live, set when it ends
+    pub earnings: u64,
+    /// Distinct viewers who joined the session
+    pub viewers: BTreeSet<String>,
+}
+
+impl StreamSession {
+    /// Payments to the stream during the session, given the stream's current `total_earnings`
+    pub fn earnings_so_far(&self, total_earnings: u64) -> u64 {
+        if self.ended_at.is_some() {
+            return self.earnings;
+        }
+        total_earnings.saturating_sub(self.earnings_at_start)
+    }
+}
+
+/// Broadcast history of one stream
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StreamHistory {
+    pub is_active: bool,
+    /// Distinct viewers across all sessions
+    pub total_viewers: u32,
+    pub total_duration_minutes: u64,
+    /// Most recent broadcasts of the stream, oldest first
+    pub sessions: Vec<StreamSession>,
+    /// Broadcast time of every ended session, dropped ones included
+    total_duration_seconds: u64,
+    /// Distinct viewers of every session, dropped ones included
+    viewers: BTreeSet<String>,
+}
+
+/// Broadcast histories of the streams this node has served, by stream id
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SessionBook {
+    streams: BTreeMap<String, StreamHistory>,
+}
+
+impl SessionBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// History of a stream, if this node has seen it broadcast
+    pub fn history(&self, stream_id: &str) -> Option<&StreamHistory> {
+        self.streams.get(stream_id)
+    }
+
+    /// Go live, opening a new session; returns false if already live
+    pub fn start_session(
+        &mut self,
+        stream_id: &str,
+        timestamp: chrono::DateTime<chrono::Utc>,
+        total_earnings: u64,
+    ) -> bool {
+        let history = self.streams.entry(stream_id.to_string()).or_default();
+        if history.live_session().is_some() {
+            return false;
+        }
+
+        history.sessions.push(StreamSession {
+            started_at: timestamp,
+            ended_at: None,
+            duration_seconds: 0,
+            earnings_at_start: total_earnings,
+            earnings: 0,
+            viewers: BTreeSet::new(),
+        });
+        if history.sessions.len() > MAX_SESSIONS_PER_STREAM {
+            let dropped = history.sessions.len() - MAX_SESSIONS_PER_STREAM;
+            history.sessions.drain(..dropped);
+        }
+        history.is_active = true;
+        true
+    }
+
+    /// Close the live session and add its duration to the stream's total
+    ///
+    /// `duration_seconds` is the broadcast time reported by the streaming
+    /// layer; when it is zero the wall-clock time since the start is used.
+    pub fn end_session(
+        &mut self,
+        stream_id: &str,
+        timestamp: chrono::DateTime<chrono::Utc>,
+        duration_seconds: u64,
+        total_earnings: u64,
+    ) -> Option<&StreamSession> {
+        let history = self.streams.get_mut(stream_id)?;
+        history.is_active = false;
+
+        let session = history.sessions.last_mut().filter(|session| session.ended_at.is_none())?;
+        session.earnings = session.earnings_so_far(total_earnings);
+        session.ended_at = Some(timestamp);
+        session.duration_seconds = if duration_seconds > 0 {
+            duration_seconds
+        } else {
+            (timestamp - session.started_at).num_seconds().max(0) as u64
+        };
+
+        history.total_duration_seconds = history.total_duration_seconds.saturating_add(session.duration_seconds);
+        history.total_duration_minutes = history.total_duration_seconds / 60;
+        history.sessions.last()
+    }
+
+    /// Count a viewer joining the live session; returns the stream's distinct viewers if they are new to it
+    pub fn record_viewer(&mut self, stream_id: &str, viewer: &str) -> Option<u32> {
+        let history = self.streams.get_mut(stream_id)?;
+        let session = history.sessions.last_mut().filter(|session| session.ended_at.is_none())?;
+        session.viewers.insert(viewer.to_string());
+
+        if !history.viewers.insert(viewer.to_string()) {
+            return None;
+        }
+        history.total_viewers = history.total_viewers.saturating_add(1);
+        Some(history.total_viewers)
+    }
+}
+
+impl StreamHistory {
+    /// The session currently live, if any
+    pub fn live_session(&self) -> Option<&StreamSession> {
+        self.sessions.last().filter(|session| session.ended_at.is_none())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: i64) -> chrono::DateTime<chrono::Utc> {
+        chrono::DateTime::from_timestamp(secs, 0).unwrap()
+    }
+
+    #[test]
+    fn end_session_falls_back_to_wall_clock_time() {
+        let mut book = SessionBook::new();
+        assert!(book.start_session("s", at(0), 0));
+        assert!(!book.start_session("s", at(10), 0));
+
+        let session = book.end_session("s", at(150), 0, 30).unwrap();
+        assert_eq!(session.duration_seconds, 150);
+        assert_eq!(session.earnings, 30);
+
+        book.start_session("s", at(200), 30);
+        assert_eq!(book.end_session("s", at(1_000), 90, 30).unwrap().duration_seconds, 90);
+        assert!(book.end_session("s", at(1_100), 0, 30).is_none());
+
+        let history = book.history("s").unwrap();
+        assert!(!history.is_active);
+        assert_eq!(history.total_duration_minutes, 4);
+    }
+
+    #[test]
+    fn counts_each_viewer_once_across_sessions() {
+        let mut book = SessionBook::new();
+        assert_eq!(book.record_viewer("s", "alice"), None);
+
+        book.start_session("s", at(0), 0);
+        assert_eq!(book.record_viewer("s", "alice"), Some(1));
+        assert_eq!(book.record_viewer("s", "alice"), None);
+        assert_eq!(book.record_viewer("s", "bob"), Some(2));
+        book.end_session("s", at(60), 0, 0);
+        assert_eq!(book.record_viewer("s", "carol"), None);
+
+        book.start_session("s", at(120), 0);
+        assert_eq!(book.record_viewer("s", "alice"), None);
+        assert_eq!(book.history("s").unwrap().sessions[1].viewers.len(), 1);
+        assert_eq!(book.history("s").unwrap().total_viewers, 2);
+    }
+
+    #[test]
+    fn keeps_the_latest_sessions_and_all_time_totals() {
+        let mut book = SessionBook::new();
+        for session in 0..MAX_SESSIONS_PER_STREAM as i64 + 5 {
+            book.start_session("s", at(session * 100), 0);
+            book.record_viewer("s", &format!("viewer-{}", session));
+            book.end_session("s", at(session * 100 + 60), 0, 0);
+        }
+
+        let history = book.history("s").unwrap();
+        assert_eq!(history.sessions.len(), MAX_SESSIONS_PER_STREAM);
+        assert_eq!(history.sessions[0].started_at, at(500));
+        assert_eq!(history.total_duration_minutes, MAX_SESSIONS_PER_STREAM as u64 + 5);
+        assert_eq!(history.total_viewers, MAX_SESSIONS_PER_STREAM as u32 + 5);
+    }
+}
@@ -0,0 +1,418 @@
+// Slashing
+//
+// Stakers who misbehave lose part of their stake and are jailed. Any account
+// can submit evidence on-chain with a `ReportMisbehavior` transaction. Two
+// offenses are punished: double signing, where a validator signs two
+// different blocks at the same height on the same parent, and false quality
+// reports, where a validator's `ReportQuality` score for a relay and stream in
+// the current epoch is further than the reward outlier tolerance from the
+// median score measured by viewers who paid for the stream. Each measuring
+// viewer must have paid at least a minimum, and a false report never costs
+// more than the measuring viewers paid together, so cheap sybil viewers
+// cannot wipe out an honest validator. A slash takes a percentage of both
+// bonded and unbonding stake; the submitter receives a share and the rest is
+// burned. A jailed staker leaves the validator set at once and
+// cannot be elected or picked as a relay until its jail time has passed. A
+// false report is also struck from the epoch, so it earns the relay nothing.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::block::SignedHeader;
+use super::crypto;
+use super::engine::BlockchainState;
+use super::rewards;
+use super::staking::Stake;
+use crate::integration::StreamQualityMetrics;
+
+/// Domain separator for viewer quality measurement signatures
+const MEASUREMENT_SIGNING_DOMAIN: &str = "sutantra-quality-measurement-v1";
+
+/// Slashing parameters fixed at genesis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SlashingParams {
+    /// Percentage of stake taken for signing two blocks at one height
+    pub double_sign_percent: u8,
+    /// Percentage of stake taken for a quality report viewers contradict
+    pub false_report_percent: u8,
+    /// Percentage of the slashed stake paid to the evidence submitter
+    pub reporter_reward_percent: u8,
+    /// Seconds an offender stays jailed
+    pub jail_secs: u64,
+    /// Fewest paying viewers whose measurements can contradict a report
+    pub min_viewer_measurements: u32,
+    /// Least a viewer must have paid for a stream for their measurements to count
+    pub min_measurer_payment: u64,
+}
+
+impl Default for SlashingParams {
+    fn default() -> Self {
+        Self {
+            double_sign_percent: 20,
+            false_report_percent: 10,
+            reporter_reward_percent: 10,
+            jail_secs: 24 * 3600,
+            min_viewer_measurements: 3,
+            min_measurer_payment: 100,
+        }
+    }
+}
+
+/// Stream quality as measured by a viewer, signed by the viewer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QualityMeasurement {
+    pub viewer: String,
+    pub relay: String,
+    pub stream_id: String,
+    /// Reward epoch the measurement was taken in
+    pub epoch: u64,
+    pub metrics: StreamQualityMetrics,
+    /// Hex ed25519 signature by the viewer over [`QualityMeasurement::signing_payload`]
+    pub signature: String,
+}
+
+impl QualityMeasurement {
+    /// Canonical bytes covered by the signature
+    pub fn signing_payload(&self, chain_id: &str) -> Vec<u8> {
+        bincode::serialize(&(
+            MEASUREMENT_SIGNING_DOMAIN,
+            chain_id,
+            &self.viewer,
+            &self.relay,
+            &self.stream_id,
+            self.epoch,
+            &self.metrics,
+        ))
+        .expect("quality measurement serialization cannot fail")
+    }
+
+    /// Check the measurement is signed by its viewer
+    pub fn verify(&self, chain_id: &str) -> Result<()> {
+        crypto::verify(&self.viewer, &self.signing_payload(chain_id), &self.signature)
+    }
+}
+
+/// Proof that a staker misbehaved
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Evidence {
+    /// Two different blocks signed by one validator on the same parent
+    DoubleSign {
+        first: Box<SignedHeader>,
+        second: Box<SignedHeader>,
+    },
+
+    /// A recorded quality report that paying viewers' measurements contradict
+    FalseQualityReport {
+        reporter: String,
+        relay: String,
+        stream_id: String,
+        epoch: u64,
+        measurements: Vec<QualityMeasurement>,
+    },
+}
+
+impl Evidence {
+    /// Staker the evidence incriminates
+    pub fn offender(&self) -> &str {
+        match self {
+            Evidence::DoubleSign { first, .. } => &first.validator,
+            Evidence::FalseQualityReport { reporter, .. } => reporter,
+        }
+    }
+
+    /// Percentage of the offender's stake the offense costs
+    pub fn slash_percent(&self, params: &SlashingParams) -> u8 {
+        match self {
+            Evidence::DoubleSign { .. } => params.double_sign_percent,
+            Evidence::FalseQualityReport { .. } => params.false_report_percent,
+        }
+    }
+
+    /// Most stake the offense can cost: a false report costs at most what its measurers paid
+    pub fn slash_limit(&self, state: &BlockchainState) -> u64 {
+        match self {
+            Evidence::DoubleSign { .. } => u64::MAX,
+            Evidence::FalseQualityReport { stream_id, measurements, .. } => measurements.iter()
+                .map(|measurement| amount_paid(state, &measurement.viewer, stream_id))
+                .fold(0, u64::saturating_add),
+        }
+    }
+
+    /// Check the evidence proves an offense that is not yet punished
+    pub fn verify(&self, state: &BlockchainState) -> Result<()> {
+        match self {
+            Evidence::DoubleSign { first, second } => {
+                if first.validator != second.validator
+                    || first.number != second.number
+                    || first.parent_hash != second.parent_hash
+                {
+                    return Err(anyhow!("Blocks are not by one validator on the same parent"));
+                }
+                if first.hash == second.hash {
+                    return Err(anyhow!("Both headers are the same block"));
+                }
+                for header in [first, second] {
+                    if header.compute_hash() != header.hash {
+                        return Err(anyhow!("Header of block #{} has an invalid hash", header.number));
+                    }
+                    crypto::verify(&header.validator, header.hash.as_bytes(), &header.signature)?;
+                }
+                if state.double_signs.contains(&(first.validator.clone(), first.number)) {
+                    return Err(anyhow!(
+                        "{} is already slashed for double signing block #{}", first.validator, first.number
+                    ));
+                }
+                Ok(())
+            }
+
+            Evidence::FalseQualityReport { reporter, relay, stream_id, epoch, measurements } => {
+                if *epoch != state.quality_reports.epoch {
+                    return Err(anyhow!("Evidence is for epoch {}, not the current epoch", epoch));
+                }
+                let reported = state.quality_reports.score(reporter, relay, stream_id)
+                    .ok_or_else(|| anyhow!("{} has no report on {} for stream {}", reporter, relay, stream_id))?;
+
+                let mut viewers = BTreeSet::new();
+                let mut scores = Vec::new();
+                for measurement in measurements {
+                    if measurement.relay != *relay || measurement.stream_id != *stream_id || measurement.epoch != *epoch {
+                        return Err(anyhow!("Measurement by {} is for another relay, stream or epoch", measurement.viewer));
+                    }
+                    if measurement.viewer == *reporter || measurement.viewer == *relay {
+                        return Err(anyhow!("{} cannot measure a report it is party to", measurement.viewer));
+                    }
+                    if !viewers.insert(&measurement.viewer) {
+                        return Err(anyhow!("{} measured more than once", measurement.viewer));
+                    }
+                    check_measurer(state, &measurement.viewer, stream_id)?;
+                    measurement.verify(&state.chain_id)?;
+                    scores.push(rewards::score(&measurement.metrics));
+                }
+
+                let min_measurements = state.params.slashing.min_viewer_measurements.max(1) as usize;
+                if scores.len() < min_measurements {
+                    return Err(anyhow!("{} viewer measurements given, {} needed", scores.len(), min_measurements));
+                }
+
+                scores.sort_unstable();
+                let median = scores[scores.len() / 2];
+                if reported.abs_diff(median) <= state.params.rewards.outlier_tolerance {
+                    return Err(anyhow!("Reported score {} agrees with the viewers' median {}", reported, median));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Stake {
+    /// Take `percent` of the bonded and unbonding stake, but no more than `limit`, returning the amount taken
+    ///
+    /// Bonded stake is taken first, then unbonding stake from the oldest chunk.
+    pub fn slash(&mut self, percent: u8, limit: u64) -> u64 {
+        let mut left = limit;
+        let mut take = |amount: u64| {
+            let taken = ((amount as u128 * percent.min(100) as u128 / 100) as u64).min(left);
+            left -= taken;
+            taken
+        };
+
+        let mut slashed = take(self.bonded);
+        self.bonded -= slashed;
+        for chunk in &mut self.unbonding {
+            let taken = take(chunk.amount);
+            chunk.amount -= taken;
+            slashed += taken;
+        }
+        self.unbonding.retain(|chunk| chunk.amount > 0);
+        slashed
+    }
+}
+
+/// What a viewer paid for a stream, on-chain and through payment channels
+///
+/// Free previews pay nothing and an open channel's deposit is not yet paid,
+/// so only grants and the amounts of closing channels count.
+fn amount_paid(state: &BlockchainState, viewer: &str, stream_id: &str) -> u64 {
+    let mut keys = BTreeSet::from([stream_id.to_string()]);
+    keys.extend(state.streams.get(stream_id).and_then(|stream| stream.access_key()));
+    let granted = state.accounts.get(viewer).map_or(0, |account| {
+        keys.iter()
+            .filter_map(|key| account.stream_access.get(key))
+            .map(|access| access.total_paid)
+            .fold(0, u64::saturating_add)
+    });
+    let channeled = state.channels.values()
+        .filter(|channel| channel.viewer == viewer && channel.stream_id == stream_id)
+        .filter_map(|channel| channel.closing.as_ref().map(|closing| closing.amount))
+        .fold(0, u64::saturating_add);
+    granted.saturating_add(channeled)
+}
+
+/// Check a viewer paid enough for a stream for their measurements to count
+fn check_measurer(state: &BlockchainState, viewer: &str, stream_id: &str) -> Result<()> {
+    let paid = amount_paid(state, viewer, stream_id);
+    let needed = state.params.slashing.min_measurer_payment.max(1);
+    if paid < needed {
+        return Err(anyhow!("{} paid {} for stream {}, {} needed to measure it", viewer, paid, stream_id, needed));
+    }
+    Ok(())
+}
+
+/// Viewer measurements this node has received in the current epoch
+///
+/// Node-local and not part of consensus: a validator collects measurements
+/// from viewers and turns those that contradict a recorded quality report
+/// into evidence.
+#[derive(Default)]
+pub struct MeasurementBook {
+    epoch: u64,
+    /// (relay, stream) → viewer → latest measurement
+    measurements: BTreeMap<(String, String), BTreeMap<String, QualityMeasurement>>,
+}
+
+impl MeasurementBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep a viewer's signed measurement, replacing their earlier one
+    pub fn accept(&mut self, state: &BlockchainState, measurement: QualityMeasurement) -> Result<()> {
+        if measurement.epoch != state.quality_reports.epoch {
+            return Err(anyhow!("Measurement is for epoch {}, not the current epoch", measurement.epoch));
+        }
+        check_measurer(state, &measurement.viewer, &measurement.stream_id)?;
+        measurement.verify(&state.chain_id)?;
+
+        if self.epoch != measurement.epoch {
+            self.epoch = measurement.epoch;
+            self.measurements.clear();
+        }
+        self.measurements
+            .entry((measurement.relay.clone(), measurement.stream_id.clone()))
+            .or_default()
+            .insert(measurement.viewer.clone(), measurement);
+        Ok(())
+    }
+
+    /// Evidence against every recorded report in the current epoch that the measurements contradict
+    pub fn contradictions(&self, state: &BlockchainState) -> Vec<Evidence> {
+        if self.epoch != state.quality_reports.epoch {
+            return Vec::new();
+        }
+
+        let mut evidence = Vec::new();
+        for ((relay, stream_id), by_viewer) in &self.measurements {
+            for reporter in state.quality_reports.reporters(relay, stream_id) {
+                let candidate = Evidence::FalseQualityReport {
+                    reporter: reporter.clone(),
+                    relay: relay.clone(),
+                    stream_id: stream_id.clone(),
+                    epoch: self.epoch,
+                    measurements: by_viewer.values()
+                        .filter(|measurement| measurement.viewer != *reporter && measurement.viewer != *relay)
+                        .cloned()
+                        .collect(),
+                };
+                if candidate.verify(state).is_ok() {
+                    evidence.push(candidate);
+                }
+            }
+        }
+        evidence
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::genesis::GenesisSpec;
+    use crate::blockchain::staking::{StakeRole, Unbonding};
+    use crate::blockchain::StreamAccess;
+
+    fn paid(state: &mut BlockchainState, viewer: &str, stream_id: &str, total_paid: u64) {
+        let now = chrono::Utc::now();
+        state.accounts.get_mut(viewer).unwrap().stream_access.insert(stream_id.to_string(), StreamAccess {
+            stream_id: stream_id.to_string(),
+            paid_until: now,
+            total_paid,
+            access_granted_at: now,
+        });
+    }
+
+    fn measurement(viewer: &str, stream_id: &str) -> QualityMeasurement {
+        QualityMeasurement {
+            viewer: viewer.to_string(),
+            relay: "relay".to_string(),
+            stream_id: stream_id.to_string(),
+            epoch: 0,
+            metrics: StreamQualityMetrics {
+                bandwidth_mbps: 5.0,
+                latency_ms: 50,
+                packet_loss_percent: 0.0,
+                resolution: "1280x720".to_string(),
+                fps: 30,
+            },
+            signature: String::new(),
+        }
+    }
+
+    #[test]
+    fn slashing_stops_at_the_limit() {
+        let release_at = chrono::Utc::now();
+        let mut stake = Stake {
+            address: "validator".to_string(),
+            role: StakeRole::Validator,
+            bonded: 1_000,
+            unbonding: vec![Unbonding { amount: 500, release_at }, Unbonding { amount: 200, release_at }],
+            jailed_until: None,
+        };
+
+        assert_eq!(stake.slash(50, 600), 600);
+        assert_eq!(stake.bonded, 500);
+        assert_eq!(stake.unbonding.iter().map(|chunk| chunk.amount).collect::<Vec<_>>(), vec![400, 200]);
+
+        assert_eq!(stake.slash(50, u64::MAX), 550);
+        assert_eq!(stake.bonded, 250);
+    }
+
+    #[test]
+    fn measurers_must_pay_the_minimum() {
+        let balances = [("cheap", 1_000), ("generous", 1_000)]
+            .into_iter()
+            .map(|(address, balance)| (address.to_string(), balance))
+            .collect();
+        let mut state = GenesisSpec { balances, ..Default::default() }.build().0;
+        let minimum = state.params.slashing.min_measurer_payment;
+        paid(&mut state, "cheap", "stream", minimum - 1);
+        paid(&mut state, "generous", "stream", minimum);
+
+        let err = check_measurer(&state, "cheap", "stream").unwrap_err();
+        assert!(err.to_string().contains("needed to measure it"));
+        assert!(check_measurer(&state, "generous", "stream").is_ok());
+        assert!(check_measurer(&state, "generous", "other").is_err());
+        assert!(MeasurementBook::new().accept(&state, measurement("cheap", "stream")).is_err());
+    }
+
+    #[test]
+    fn false_reports_cost_at_most_what_measurers_paid() {
+        let balances = [("alice", 1_000), ("bob", 1_000)]
+            .into_iter()
+            .map(|(address, balance)| (address.to_string(), balance))
+            .collect();
+        let mut state = GenesisSpec { balances, ..Default::default() }.build().0;
+        paid(&mut state, "alice", "stream", 150);
+        paid(&mut state, "bob", "stream", 250);
+
+        let evidence = Evidence::FalseQualityReport {
+            reporter: "reporter".to_string(),
+            relay: "relay".to_string(),
+            stream_id: "stream".to_string(),
+            epoch: 0,
+            measurements: vec![measurement("alice", "stream"), measurement("bob", "stream")],
+        };
+        assert_eq!(evidence.slash_limit(&state), 400);
+    }
+}
@@ -0,0 +1,227 @@
+// Creator revenue splits
+//
+// A stream's revenue can be shared between co-hosts and team members. The
+// split on a registration optionally takes a platform or relay cut off the
+// top, then gives each recipient their share of the rest in basis points.
+// Whatever the shares leave unallocated, rounding included, goes to the
+// creator, so an empty split pays the creator everything. Every payment for
+// a stream, whether bought on-chain, billed by the node or settled through a
+// payment channel, is split in the same state change that takes it from the
+// viewer. The creator changes the split with a signed `SetRevenueSplit`
+// transaction. Each registration keeps what every recipient has been paid in
+// total and its most recent payouts.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::StreamRegistration;
+
+/// Basis points in a whole payment
+pub const BASIS_POINTS: u32 = 10_000;
+
+/// Largest number of shares a split may have
+pub const MAX_SHARES: usize = 16;
+
+/// Payouts kept per stream in its recent history
+pub const PAYOUT_HISTORY_LEN: usize = 100;
+
+/// A recipient's part of a payment, in basis points
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SplitShare {
+    pub recipient: String,
+    pub basis_points: u32,
+}
+
+/// How a stream's payments are divided
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RevenueSplit {
+    /// Platform or relay cut, taken before the shares
+    pub cut: Option<SplitShare>,
+    /// Shares of what is left after the cut; the creator keeps the remainder
+    pub shares: Vec<SplitShare>,
+}
+
+/// One payment as it was divided between recipients
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Payout {
+    pub paid_at: chrono::DateTime<chrono::Utc>,
+    pub amount: u64,
+    pub recipients: Vec<(String, u64)>,
+}
+
+impl RevenueSplit {
+    /// Check the split is one a stream can use
+    pub fn validate(&self) -> Result<()> {
+        if self.shares.len() > MAX_SHARES {
+            return Err(anyhow!("Revenue split has {} shares, at most {} allowed", self.shares.len(), MAX_SHARES));
+        }
+
+        let mut recipients = BTreeSet::new();
+        for share in self.cut.iter().chain(&self.shares) {
+            if share.recipient.is_empty() || share.basis_points == 0 {
+                return Err(anyhow!("Revenue split shares need a recipient and a non-zero share"));
+            }
+            if share.basis_points > BASIS_POINTS {
+                return Err(anyhow!("Share of {} exceeds {} basis points", share.recipient, BASIS_POINTS));
+            }
+        }
+        for share in &self.shares {
+            if !recipients.insert(&share.recipient) {
+                return Err(anyhow!("{} appears more than once in the revenue split", share.recipient));
+            }
+        }
+
+        let total: u32 = self.shares.iter().map(|share| share.basis_points).sum();
+        if total > BASIS_POINTS {
+            return Err(anyhow!("Revenue split shares add up to {} basis points, more than {}", total, BASIS_POINTS));
+        }
+        Ok(())
+    }
+
+    /// Divide `amount` between the cut, the shares and `creator`
+    ///
+    /// The parts always add up to `amount`; zero parts are left out.
+    pub fn distribute(&self, creator: &str, amount: u64) -> Vec<(String, u64)> {
+        let part = |amount: u64, basis_points: u32| {
+            (amount as u128 * basis_points.min(BASIS_POINTS) as u128 / BASIS_POINTS as u128) as u64
+        };
+
+        let mut parts = Vec::new();
+        let cut = self.cut.as_ref().map_or(0, |cut| part(amount, cut.basis_points));
+        if let Some(cut_share) = &self.cut {
+            parts.push((cut_share.recipient.clone(), cut));
+        }
+
+        let rest = amount - cut;
+        let mut allocated = 0;
+        for share in &self.shares {
+            let paid = part(rest, share.basis_points).min(rest - allocated);
+            allocated += paid;
+            parts.push((share.recipient.clone(), paid));
+        }
+        parts.push((creator.to_string(), rest - allocated));
+
+        parts.retain(|(_, paid)| *paid > 0);
+        parts
+    }
+}
+
+impl StreamRegistration {
+    /// Split a payment to the stream, record it and return what each recipient gets
+    ///
+    /// The gross amount counts toward the stream's earnings.
+    pub fn split_payment(&mut self, amount: u64, timestamp: chrono::DateTime<chrono::Utc>) -> Vec<(String, u64)> {
+        let recipients = self.split.distribute(&self.creator, amount);
+
+        self.total_earnings += amount;
+        for (recipient, paid) in &recipients {
+            *self.paid_out.entry(recipient.clone()).or_default() += paid;
+        }
+
+        self.recent_payouts.push(Payout { paid_at: timestamp, amount, recipients: recipients.clone() });
+        if self.recent_payouts.len() > PAYOUT_HISTORY_LEN {
+            let excess = self.recent_payouts.len() - PAYOUT_HISTORY_LEN;
+            self.recent_payouts.drain(..excess);
+        }
+
+        recipients
+    }
+}
+
+/// Payout history of a stream, as reported by the node API
+#[derive(Debug, Clone, Serialize)]
+pub struct PayoutHistory {
+    pub stream_id: String,
+    pub creator: String,
+    pub split: RevenueSplit,
+    pub total_earnings: u64,
+    /// Total paid to each recipient
+    pub paid_out: BTreeMap<String, u64>,
+    /// Most recent payouts, oldest first
+    pub recent: Vec<Payout>,
+}
+
+impl From<&StreamRegistration> for PayoutHistory {
+    fn from(stream: &StreamRegistration) -> Self {
+        Self {
+            stream_id: stream.stream_id.clone(),
+            creator: stream.creator.clone(),
+            split: stream.split.clone(),
+            total_earnings: stream.total_earnings,
+            paid_out: stream.paid_out.clone(),
+            recent: stream.recent_payouts.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn share(recipient: &str, basis_points: u32) -> SplitShare {
+        SplitShare { recipient: recipient.to_string(), basis_points }
+    }
+
+    fn parts(pairs: &[(&str, u64)]) -> Vec<(String, u64)> {
+        pairs.iter().map(|(recipient, paid)| (recipient.to_string(), *paid)).collect()
+    }
+
+    #[test]
+    fn empty_split_pays_the_creator_everything() {
+        assert_eq!(RevenueSplit::default().distribute("creator", 1_000), parts(&[("creator", 1_000)]));
+        assert!(RevenueSplit::default().distribute("creator", 0).is_empty());
+    }
+
+    #[test]
+    fn takes_the_cut_before_the_shares() {
+        let split = RevenueSplit {
+            cut: Some(share("platform", 1_000)),
+            shares: vec![share("cohost", 5_000)],
+        };
+
+        assert_eq!(split.distribute("creator", 1_000), parts(&[
+            ("platform", 100),
+            ("cohost", 450),
+            ("creator", 450),
+        ]));
+    }
+
+    #[test]
+    fn rounding_remainder_goes_to_the_creator() {
+        let split = RevenueSplit {
+            cut: None,
+            shares: vec![share("a", 3_333), share("b", 3_333), share("c", 3_334)],
+        };
+
+        let paid = split.distribute("creator", 10);
+        assert_eq!(paid, parts(&[("a", 3), ("b", 3), ("c", 3), ("creator", 1)]));
+        assert_eq!(paid.iter().map(|(_, amount)| amount).sum::<u64>(), 10);
+    }
+
+    #[test]
+    fn parts_add_up_for_large_amounts() {
+        let split = RevenueSplit {
+            cut: Some(share("platform", 250)),
+            shares: vec![share("a", 7_000), share("b", 3_000)],
+        };
+
+        let paid = split.distribute("creator", u64::MAX);
+        assert_eq!(paid.iter().map(|(_, amount)| *amount as u128).sum::<u128>(), u64::MAX as u128);
+    }
+
+    #[test]
+    fn rejects_oversubscribed_and_duplicate_shares() {
+        let oversubscribed = RevenueSplit { cut: None, shares: vec![share("a", 6_000), share("b", 5_000)] };
+        assert!(oversubscribed.validate().is_err());
+
+        let duplicate = RevenueSplit { cut: None, shares: vec![share("a", 1_000), share("a", 1_000)] };
+        assert!(duplicate.validate().is_err());
+
+        let empty_share = RevenueSplit { cut: Some(share("platform", 0)), shares: Vec::new() };
+        assert!(empty_share.validate().is_err());
+
+        let full = RevenueSplit { cut: Some(share("platform", 500)), shares: vec![share("a", 10_000)] };
+        assert!(full.validate().is_ok());
+    }
+}
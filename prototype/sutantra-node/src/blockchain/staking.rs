@@ -0,0 +1,237 @@
+// Staking
+//
+// Accounts bond STREAM to become validators or streaming relays. Bonded stake
+// leaves the account balance; unbonding moves it into a queue that can be
+// withdrawn once the unbonding period has passed, and it stays slashable
+// until then. At the end of every epoch the validator set is re-elected: the
+// largest validator stakes above the minimum, up to the maximum set size. A
+// dev chain has no validator set and keeps none. Relays register the number of
+// streams they can carry and are picked for streams with a chance
+// proportional to their stake.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::consensus::Validator;
+
+/// Staking parameters fixed at genesis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StakingParams {
+    /// Seconds unbonded stake stays locked before it can be withdrawn
+    pub unbonding_secs: u64,
+    /// Smallest bond that can be elected as a validator
+    pub min_validator_stake: u64,
+    /// Largest validator set an election produces
+    pub max_validators: u32,
+    /// Smallest bond a relay needs to be picked
+    pub min_relay_stake: u64,
+}
+
+impl Default for StakingParams {
+    fn default() -> Self {
+        Self {
+            unbonding_secs: 7 * 24 * 3600,
+            min_validator_stake: 10_000,
+            max_validators: 21,
+            min_relay_stake: 1_000,
+        }
+    }
+}
+
+/// What an account bonds stake for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StakeRole {
+    Validator,
+    /// A streaming relay carrying up to `capacity` concurrent streams,
+    /// the same unit as `StreamingConfig::relay_capacity`
+    Relay { capacity: u32 },
+}
+
+/// Stake on its way out, withdrawable from `release_at`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Unbonding {
+    pub amount: u64,
+    pub release_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// An account's entry in the stake registry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Stake {
+    pub address: String,
+    pub role: StakeRole,
+    pub bonded: u64,
+    /// Oldest first
+    pub unbonding: Vec<Unbonding>,
+    /// Set when slashed; a jailed staker is neither elected nor picked
+    #[serde(default)]
+    pub jailed_until: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl Stake {
+    /// Unbonded stake whose period has passed at `now`
+    pub fn withdrawable(&self, now: chrono::DateTime<chrono::Utc>) -> u64 {
+        self.unbonding.iter()
+            .filter(|chunk| chunk.release_at <= now)
+            .map(|chunk| chunk.amount)
+            .sum()
+    }
+
+    /// Whether the staker is serving a jail term at `now`
+    pub fn is_jailed(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        self.jailed_until.is_some_and(|until| until > now)
+    }
+
+    /// Whether nothing is bonded or unbonding, so the entry can go
+    pub fn is_empty(&self) -> bool {
+        self.bonded == 0 && self.unbonding.is_empty()
+    }
+}
+
+/// Stake registry and validator set, as reported by the node API
+#[derive(Debug, Clone, Serialize)]
+pub struct StakingStatus {
+    pub params: StakingParams,
+    pub validators: Vec<Validator>,
+    pub stakes: Vec<Stake>,
+    /// Total stake burned by slashing
+    pub slashed_stake: u64,
+}
+
+/// Validator set for the next epoch, ordered by address
+///
+/// The largest validator bonds of at least `min_validator_stake` that are not
+/// jailed at `now` win, ties going to the lower address.
+pub fn elect_validators(
+    stakes: &BTreeMap<String, Stake>,
+    params: &StakingParams,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Vec<Validator> {
+    let mut candidates: Vec<&Stake> = stakes.values()
+        .filter(|stake| stake.role == StakeRole::Validator && stake.bonded >= params.min_validator_stake.max(1))
+        .filter(|stake| !stake.is_jailed(now))
+        .collect();
+    candidates.sort_by(|a, b| b.bonded.cmp(&a.bonded).then_with(|| a.address.cmp(&b.address)));
+    candidates.truncate(params.max_validators as usize);
+
+    let mut validators: Vec<Validator> = candidates.into_iter()
+        .map(|stake| Validator { address: stake.address.clone(), stake: stake.bonded })
+        .collect();
+    validators.sort_by(|a, b| a.address.cmp(&b.address));
+    validators
+}
+
+/// Up to `count` relays for a stream, each draw weighted by bonded stake
+///
+/// Draws are without replacement and seeded by `seed`, so every node picks
+/// the same relays for the same seed. Relays jailed at `now` are skipped.
+pub fn pick_relays(
+    stakes: &BTreeMap<String, Stake>,
+    params: &StakingParams,
+    seed: &[u8],
+    count: usize,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Vec<String> {
+    let mut candidates: Vec<&Stake> = stakes.values()
+        .filter(|stake| matches!(stake.role, StakeRole::Relay { capacity } if capacity > 0))
+        .filter(|stake| stake.bonded >= params.min_relay_stake.max(1) && !stake.is_jailed(now))
+        .collect();
+
+    let mut picked = Vec::new();
+    for draw in 0..count.min(candidates.len()) {
+        let total: u128 = candidates.iter().map(|stake| stake.bonded as u128).sum();
+
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(seed);
+        hasher.update(&(draw as u64).to_le_bytes());
+        let mut point_bytes = [0u8; 16];
+        point_bytes.copy_from_slice(&hasher.finalize().as_bytes()[..16]);
+        let mut point = u128::from_le_bytes(point_bytes) % total;
+
+        let index = candidates.iter()
+            .position(|stake| {
+                if point < stake.bonded as u128 {
+                    true
+                } else {
+                    point -= stake.bonded as u128;
+                    false
+                }
+            })
+            .expect("point is below the total stake");
+
+        picked.push(candidates.remove(index).address.clone());
+    }
+    picked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stake(address: &str, role: StakeRole, bonded: u64) -> (String, Stake) {
+        (address.to_string(), Stake {
+            address: address.to_string(),
+            role,
+            bonded,
+            unbonding: Vec::new(),
+            jailed_until: None,
+        })
+    }
+
+    #[test]
+    fn elects_the_largest_unjailed_validator_stakes() {
+        let now = chrono::Utc::now();
+        let mut stakes: BTreeMap<String, Stake> = [
+            stake("a", StakeRole::Validator, 50_000),
+            stake("b", StakeRole::Validator, 20_000),
+            stake("c", StakeRole::Validator, 20_000),
+            stake("d", StakeRole::Validator, 9_999),
+            stake("e", StakeRole::Relay { capacity: 10 }, 90_000),
+            stake("f", StakeRole::Validator, 80_000),
+        ].into_iter().collect();
+        stakes.get_mut("f").unwrap().jailed_until = Some(now + chrono::Duration::hours(1));
+        let params = StakingParams { max_validators: 2, ..StakingParams::default() };
+
+        let elected = elect_validators(&stakes, &params, now);
+        let addresses: Vec<&str> = elected.iter().map(|validator| validator.address.as_str()).collect();
+        assert_eq!(addresses, ["a", "b"]);
+        assert_eq!(elected[1].stake, 20_000);
+
+        // A served jail term no longer keeps a validator out
+        let elected = elect_validators(&stakes, &params, now + chrono::Duration::hours(2));
+        let addresses: Vec<&str> = elected.iter().map(|validator| validator.address.as_str()).collect();
+        assert_eq!(addresses, ["a", "f"]);
+    }
+
+    #[test]
+    fn picks_distinct_bonded_relays_deterministically() {
+        let now = chrono::Utc::now();
+        let stakes: BTreeMap<String, Stake> = [
+            stake("r1", StakeRole::Relay { capacity: 10 }, 5_000),
+            stake("r2", StakeRole::Relay { capacity: 10 }, 1_000),
+            stake("r3", StakeRole::Relay { capacity: 10 }, 999),
+            stake("r4", StakeRole::Relay { capacity: 0 }, 5_000),
+            stake("v", StakeRole::Validator, 50_000),
+        ].into_iter().collect();
+        let params = StakingParams::default();
+
+        let picked = pick_relays(&stakes, &params, b"seed", 5, now);
+        assert_eq!(picked.len(), 2);
+        assert!(picked.contains(&"r1".to_string()) && picked.contains(&"r2".to_string()));
+        assert_eq!(pick_relays(&stakes, &params, b"seed", 1, now), pick_relays(&stakes, &params, b"seed", 1, now));
+    }
+
+    #[test]
+    fn only_released_unbonding_is_withdrawable() {
+        let now = chrono::Utc::now();
+        let (_, mut stake) = stake("a", StakeRole::Validator, 0);
+        stake.unbonding = vec![
+            Unbonding { amount: 100, release_at: now },
+            Unbonding { amount: 200, release_at: now + chrono::Duration::days(1) },
+        ];
+        assert_eq!(stake.withdrawable(now - chrono::Duration::seconds(1)), 0);
+        assert_eq!(stake.withdrawable(now), 100);
+        assert_eq!(stake.withdrawable(now + chrono::Duration::days(1)), 300);
+        assert!(!stake.is_empty());
+    }
+}
@@ -1,11 +1,339 @@
-// Blockchain state management
-// Placeholder for future implementation
+// Blockchain state commitments
+//
+// The state root commits to five Merkle trees: accounts, stream
+// registrations, stream access grants, payment channels and stakes. Each tree
+// supports inclusion and exclusion proofs, so a light client holding only a
+// block header can check a balance, a viewer's `paid_until`, a channel deposit
+// or a bond. A sixth leaf hashes the rest of the chain-wide state: protocol
+// params, the validator set, the fee rate, the relay pool and its quality
+// reports, burn and slashing totals, and punished double signs.
 
-pub struct StateManager;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 
-impl StateManager {
-    pub fn new() -> Self {
-        Self
+use super::channels::PaymentChannel;
+use super::consensus::Validator;
+use super::engine::BlockchainState;
+use super::genesis::ProtocolParams;
+use super::merkle::{Hash, MembershipProof, MerkleTree};
+use super::rewards::EpochReports;
+use super::staking::Stake;
+use super::{StreamAccess, StreamRegistration};
+
+const STATE_ROOT_DOMAIN: &[u8] = b"sutantra-state-v1";
+const CHAIN_META_DOMAIN: &[u8] = b"sutantra-chain-meta-v1";
+
+/// Which state tree a proof refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StateTree {
+    Accounts,
+    Streams,
+    StreamAccess,
+    Channels,
+    Stakes,
+}
+
+/// Committed fields of an account (access grants live in their own tree)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountLeaf {
+    pub address: String,
+    pub balance: u64,
+    pub nonce: u64,
+    pub created_streams: Vec<String>,
+}
+
+/// Chain-wide state outside the five trees, committed as one leaf
+#[derive(Serialize)]
+struct ChainMeta<'a> {
+    chain_id: &'a str,
+    params: &'a ProtocolParams,
+    validators: &'a [Validator],
+    fee_per_weight: u64,
+    relay_pool: u64,
+    quality_reports: &'a EpochReports,
+    burned_fees: u64,
+    slashed_stake: u64,
+    double_signs: &'a BTreeSet<(String, u64)>,
+}
+
+/// Proof of a single entry (or its absence) against a block's `state_root`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateProof {
+    pub tree: StateTree,
+    pub key: Vec<u8>,
+    /// Encoded leaf value, `None` when proving absence
+    pub value: Option<Vec<u8>>,
+    pub accounts_root: Hash,
+    pub streams_root: Hash,
+    pub access_root: Hash,
+    pub channels_root: Hash,
+    pub stakes_root: Hash,
+    /// Hash of the chain-wide state outside the trees
+    pub meta_hash: Hash,
+    pub proof: MembershipProof,
+}
+
+/// The five state trees built from a snapshot of the state, and the chain meta leaf
+struct StateTrees {
+    accounts: MerkleTree,
+    streams: MerkleTree,
+    access: MerkleTree,
+    channels: MerkleTree,
+    stakes: MerkleTree,
+    meta: Hash,
+}
+
+impl StateTrees {
+    fn root(&self) -> Hash {
+        combine_roots(
+            &self.accounts.root(),
+            &self.streams.root(),
+            &self.access.root(),
+            &self.channels.root(),
+            &self.stakes.root(),
+            &self.meta,
+        )
+    }
+
+    fn prove(&self, tree: StateTree, key: Vec<u8>, value: Option<Vec<u8>>) -> StateProof {
+        let proof = match tree {
+            StateTree::Accounts => self.accounts.prove(&key),
+            StateTree::Streams => self.streams.prove(&key),
+            StateTree::StreamAccess => self.access.prove(&key),
+            StateTree::Channels => self.channels.prove(&key),
+            StateTree::Stakes => self.stakes.prove(&key),
+        };
+
+        StateProof {
+            tree,
+            key,
+            value,
+            accounts_root: self.accounts.root(),
+            streams_root: self.streams.root(),
+            access_root: self.access.root(),
+            channels_root: self.channels.root(),
+            stakes_root: self.stakes.root(),
+            meta_hash: self.meta,
+            proof,
+        }
     }
 }
 
+impl BlockchainState {
+    /// Merkle commitment over accounts, streams, access grants, channels, stakes and chain meta, hex-encoded
+    pub fn state_root(&self) -> String {
+        hex::encode(self.state_trees().root())
+    }
+
+    /// Prove an account's balance and nonce, or that the account does not exist
+    pub fn prove_account(&self, address: &str) -> StateProof {
+        let value = self.accounts.get(address).map(|account| encode(&AccountLeaf::from(account)));
+        self.state_trees().prove(StateTree::Accounts, address.as_bytes().to_vec(), value)
+    }
+
+    /// Prove a stream registration, or that the stream is not registered
+    pub fn prove_stream(&self, stream_id: &str) -> StateProof {
+        let value = self.streams.get(stream_id).map(encode);
+        self.state_trees().prove(StateTree::Streams, stream_id.as_bytes().to_vec(), value)
+    }
+
+    /// Prove a viewer's access grant for a stream, or that none exists
+    pub fn prove_stream_access(&self, viewer: &str, stream_id: &str) -> StateProof {
+        let value = self.accounts.get(viewer)
+            .and_then(|account| account.stream_access.get(stream_id))
+            .map(encode);
+        self.state_trees().prove(StateTree::StreamAccess, access_key(viewer, stream_id), value)
+    }
+
+    /// Prove a payment channel is open, or that it is not
+    pub fn prove_channel(&self, channel_id: &str) -> StateProof {
+        let value = self.channels.get(channel_id).map(encode);
+        self.state_trees().prove(StateTree::Channels, channel_id.as_bytes().to_vec(), value)
+    }
+
+    /// Prove an account's bonded and unbonding stake, or that it has none
+    pub fn prove_stake(&self, address: &str) -> StateProof {
+        let value = self.stakes.get(address).map(encode);
+        self.state_trees().prove(StateTree::Stakes, address.as_bytes().to_vec(), value)
+    }
+
+    fn state_trees(&self) -> StateTrees {
+        let accounts = self.accounts.iter()
+            .map(|(address, account)| (address.as_bytes().to_vec(), encode(&AccountLeaf::from(account))))
+            .collect();
+
+        let streams = self.streams.iter()
+            .map(|(stream_id, stream)| (stream_id.as_bytes().to_vec(), encode(stream)))
+            .collect();
+
+        let access = self.accounts.iter()
+            .flat_map(|(address, account)| {
+                account.stream_access.iter()
+                    .map(move |(stream_id, grant)| (access_key(address, stream_id), encode(grant)))
+            })
+            .collect();
+
+        let channels = self.channels.iter()
+            .map(|(channel_id, channel)| (channel_id.as_bytes().to_vec(), encode(channel)))
+            .collect();
+
+        let stakes = self.stakes.iter()
+            .map(|(address, stake)| (address.as_bytes().to_vec(), encode(stake)))
+            .collect();
+
+        StateTrees {
+            accounts: MerkleTree::new(accounts),
+            streams: MerkleTree::new(streams),
+            access: MerkleTree::new(access),
+            channels: MerkleTree::new(channels),
+            stakes: MerkleTree::new(stakes),
+            meta: self.meta_hash(),
+        }
+    }
+
+    fn meta_hash(&self) -> Hash {
+        let meta = ChainMeta {
+            chain_id: &self.chain_id,
+            params: &self.params,
+            validators: &self.validators,
+            fee_per_weight: self.fee_per_weight,
+            relay_pool: self.relay_pool,
+            quality_reports: &self.quality_reports,
+            burned_fees: self.burned_fees,
+            slashed_stake: self.slashed_stake,
+            double_signs: &self.double_signs,
+        };
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(CHAIN_META_DOMAIN);
+        hasher.update(&encode(&meta));
+        *hasher.finalize().as_bytes()
+    }
+}
+
+impl StateProof {
+    /// Check the proof against a hex `state_root` from a block header
+    pub fn verify(&self, state_root: &str) -> Result<()> {
+        let expected = combine_roots(
+            &self.accounts_root,
+            &self.streams_root,
+            &self.access_root,
+            &self.channels_root,
+            &self.stakes_root,
+            &self.meta_hash,
+        );
+        if hex::encode(expected) != state_root {
+            return Err(anyhow!("Tree roots do not match state root"));
+        }
+
+        let tree_root = match self.tree {
+            StateTree::Accounts => &self.accounts_root,
+            StateTree::Streams => &self.streams_root,
+            StateTree::StreamAccess => &self.access_root,
+            StateTree::Channels => &self.channels_root,
+            StateTree::Stakes => &self.stakes_root,
+        };
+        self.proof.verify(tree_root, &self.key, self.value.as_deref())
+    }
+
+    /// Decode the proven account, if this is an inclusion proof for one
+    pub fn account(&self) -> Option<AccountLeaf> {
+        self.decode_value(StateTree::Accounts)
+    }
+
+    /// Decode the proven stream registration, if this is an inclusion proof for one
+    pub fn stream(&self) -> Option<StreamRegistration> {
+        self.decode_value(StateTree::Streams)
+    }
+
+    /// Decode the proven access grant, if this is an inclusion proof for one
+    pub fn stream_access(&self) -> Option<StreamAccess> {
+        self.decode_value(StateTree::StreamAccess)
+    }
+
+    /// Decode the proven payment channel, if this is an inclusion proof for one
+    pub fn channel(&self) -> Option<PaymentChannel> {
+        self.decode_value(StateTree::Channels)
+    }
+
+    /// Decode the proven stake, if this is an inclusion proof for one
+    pub fn stake(&self) -> Option<Stake> {
+        self.decode_value(StateTree::Stakes)
+    }
+
+    fn decode_value<T: serde::de::DeserializeOwned>(&self, tree: StateTree) -> Option<T> {
+        if self.tree != tree {
+            return None;
+        }
+        self.value.as_ref().and_then(|bytes| bincode::deserialize(bytes).ok())
+    }
+}
+
+impl From<&super::Account> for AccountLeaf {
+    fn from(account: &super::Account) -> Self {
+        Self {
+            address: account.address.clone(),
+            balance: account.balance,
+            nonce: account.nonce,
+            created_streams: account.created_streams.clone(),
+        }
+    }
+}
+
+/// Key of a grant in the access tree (length-prefixed viewer, then stream id)
+fn access_key(viewer: &str, stream_id: &str) -> Vec<u8> {
+    let mut key = Vec::with_capacity(4 + viewer.len() + stream_id.len());
+    key.extend_from_slice(&(viewer.len() as u32).to_be_bytes());
+    key.extend_from_slice(viewer.as_bytes());
+    key.extend_from_slice(stream_id.as_bytes());
+    key
+}
+
+fn combine_roots(accounts: &Hash, streams: &Hash, access: &Hash, channels: &Hash, stakes: &Hash, meta: &Hash) -> Hash {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(STATE_ROOT_DOMAIN);
+    hasher.update(accounts);
+    hasher.update(streams);
+    hasher.update(access);
+    hasher.update(channels);
+    hasher.update(stakes);
+    hasher.update(meta);
+    *hasher.finalize().as_bytes()
+}
+
+fn encode<T: Serialize>(value: &T) -> Vec<u8> {
+    bincode::serialize(value).expect("state serialization cannot fail")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::blockchain::genesis::GenesisSpec;
+
+    #[test]
+    fn account_proofs_verify_against_the_state_root() {
+        let (state, _) = GenesisSpec::default().build();
+        let root = state.state_root();
+
+        let proof = state.prove_account("genesis");
+        proof.verify(&root).unwrap();
+        assert_eq!(proof.account().map(|account| account.balance), Some(1_000_000_000));
+
+        let absent = state.prove_account("nobody");
+        absent.verify(&root).unwrap();
+        assert!(absent.account().is_none());
+    }
+
+    #[test]
+    fn proofs_fail_after_the_state_changes() {
+        let (mut state, _) = GenesisSpec::default().build();
+        let proof = state.prove_account("genesis");
+
+        state.accounts.get_mut("genesis").unwrap().balance -= 1;
+        assert!(proof.verify(&state.state_root()).is_err());
+
+        // Chain-wide state outside the trees is committed too
+        let proof = state.prove_account("genesis");
+        state.relay_pool += 1;
+        assert!(proof.verify(&state.state_root()).is_err());
+    }
+}
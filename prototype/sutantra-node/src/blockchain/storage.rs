@@ -0,0 +1,183 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::{info, debug, warn};
+
+use super::Block;
+use super::engine::BlockchainState;
+use super::pricing::PreviewBook;
+use super::sessions::SessionBook;
+
+const STATE_FILE: &str = "state.bin";
+const FINALIZED_STATE_FILE: &str = "finalized_state.bin";
+const SESSIONS_FILE: &str = "sessions.bin";
+const PREVIEWS_FILE: &str = "previews.bin";
+const BLOCKS_DIR: &str = "blocks";
+
+/// Embedded on-disk storage for chain data
+///
+/// Layout under `data_dir`:
+/// - `state.bin`: snapshot of accounts, stream registrations and chain metadata
+/// - `finalized_state.bin`: state after the last finalized block, the point
+///   fork choice restarts from
+/// - `blocks/<number>.bin`: one file per block
+/// - `sessions.bin`: the node's own record of stream broadcasts, not part of
+///   the chain
+/// - `previews.bin`: free previews this node gave viewers, also node-local
+///
+/// Every file is written to a temporary path, synced and then renamed into
+/// place, so a crash mid-write leaves the previous version intact. Blocks are
+/// written before the state snapshot that references them, so the snapshot is
+/// always the source of truth for the chain tip.
+pub struct ChainStorage {
+    root: PathBuf,
+}
+
+impl ChainStorage {
+    /// Open (or create) storage rooted at `data_dir`
+    pub fn open(data_dir: impl AsRef<Path>) -> Result<Self> {
+        let root = data_dir.as_ref().to_path_buf();
+        fs::create_dir_all(root.join(BLOCKS_DIR))
+            .with_context(|| format!("Failed to create data directory {}", root.display()))?;
+
+        // Remove temp files left behind by a crash mid-write
+        for dir in [root.clone(), root.join(BLOCKS_DIR)] {
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path.extension().is_some_and(|ext| ext == "tmp") {
+                    warn!("🧹 Removing incomplete write: {}", path.display());
+                    fs::remove_file(&path)?;
+                }
+            }
+        }
+
+        info!("💾 Chain storage opened at {}", root.display());
+        Ok(Self { root })
+    }
+
+    /// Load the persisted state snapshot, if any
+    pub fn load_state(&self) -> Result<Option<BlockchainState>> {
+        let state = self.read_state(STATE_FILE)?;
+        if let Some(state) = &state {
+            debug!("💾 Loaded state at block #{}", state.best_block);
+        }
+        Ok(state)
+    }
+
+    /// Persist a full state snapshot atomically
+    pub fn save_state(&self, state: &BlockchainState) -> Result<()> {
+        let bytes = bincode::serialize(state)?;
+        write_atomic(&self.root.join(STATE_FILE), &bytes)
+    }
+
+    /// Load the state after the last finalized block, if any
+    pub fn load_finalized_state(&self) -> Result<Option<BlockchainState>> {
+        self.read_state(FINALIZED_STATE_FILE)
+    }
+
+    /// Persist the state after a newly finalized block
+    pub fn save_finalized_state(&self, state: &BlockchainState) -> Result<()> {
+        let bytes = bincode::serialize(state)?;
+        write_atomic(&self.root.join(FINALIZED_STATE_FILE), &bytes)
+    }
+
+    fn read_state(&self, file: &str) -> Result<Option<BlockchainState>> {
+        let path = self.root.join(file);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let bytes = fs::read(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let state = bincode::deserialize(&bytes)
+            .with_context(|| format!("Corrupted state snapshot {}", path.display()))?;
+        Ok(Some(state))
+    }
+
+    /// Load the node's stream broadcast history, empty on first start
+    pub fn load_sessions(&self) -> Result<SessionBook> {
+        let path = self.root.join(SESSIONS_FILE);
+        if !path.exists() {
+            return Ok(SessionBook::new());
+        }
+
+        let bytes = fs::read(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        bincode::deserialize(&bytes)
+            .with_context(|| format!("Corrupted session history {}", path.display()))
+    }
+
+    /// Persist the node's stream broadcast history atomically
+    pub fn save_sessions(&self, sessions: &SessionBook) -> Result<()> {
+        let bytes = bincode::serialize(sessions)?;
+        write_atomic(&self.root.join(SESSIONS_FILE), &bytes)
+    }
+
+    /// Load the free previews this node has given, empty on first start
+    pub fn load_previews(&self) -> Result<PreviewBook> {
+        let path = self.root.join(PREVIEWS_FILE);
+        if !path.exists() {
+            return Ok(PreviewBook::new());
+        }
+
+        let bytes = fs::read(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        bincode::deserialize(&bytes)
+            .with_context(|| format!("Corrupted preview book {}", path.display()))
+    }
+
+    /// Persist the free previews this node has given atomically
+    pub fn save_previews(&self, previews: &PreviewBook) -> Result<()> {
+        let bytes = bincode::serialize(previews)?;
+        write_atomic(&self.root.join(PREVIEWS_FILE), &bytes)
+    }
+
+    /// Persist a block atomically
+    pub fn put_block(&self, block: &Block) -> Result<()> {
+        let bytes = bincode::serialize(block)?;
+        write_atomic(&self.block_path(block.number), &bytes)
+    }
+
+    /// Load a block by number
+    pub fn get_block(&self, number: u64) -> Result<Option<Block>> {
+        let path = self.block_path(number);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let bytes = fs::read(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let block = bincode::deserialize(&bytes)
+            .with_context(|| format!("Corrupted block file {}", path.display()))?;
+        Ok(Some(block))
+    }
+
+    fn block_path(&self, number: u64) -> PathBuf {
+        self.root.join(BLOCKS_DIR).join(format!("{:020}.bin", number))
+    }
+}
+
+/// Write `bytes` to `path` via a synced temp file and rename
+fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    let tmp_path = path.with_extension("tmp");
+
+    {
+        let mut file = fs::File::create(&tmp_path)
+            .with_context(|| format!("Failed to create {}", tmp_path.display()))?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+    }
+
+    fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed to move {} into place", path.display()))?;
+
+    // Sync the directory so the rename itself survives a crash
+    if let Some(parent) = path.parent() {
+        if let Ok(dir) = fs::File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+
+    Ok(())
+}
@@ -1,11 +1,841 @@
 // Transaction processing
-// Placeholder for future implementation
 
+use anyhow::{anyhow, Result};
+use ed25519_dalek::SigningKey;
+use std::collections::HashMap;
+
+use super::channels::{self, ChannelClosing, PaymentChannel};
+use super::crypto;
+use super::engine::BlockchainState;
+use super::fees;
+use super::pricing::{self, PricingModel};
+use super::rewards::{self, EpochReports};
+use super::slashing::Evidence;
+use super::staking::{self, Stake, StakeRole, Unbonding};
+use super::{Account, Block, Transaction};
+
+/// Domain separator for transaction signatures
+const SIGNING_DOMAIN: &str = "sutantra-tx-v1";
+
+impl Transaction {
+    /// Address of the account that signs this transaction
+    pub fn sender(&self) -> &str {
+        match self {
+            Transaction::Transfer { from, .. } => from,
+            Transaction::RegisterStream { creator, .. } => creator,
+            Transaction::PurchaseAccess { viewer, .. } => viewer,
+            Transaction::ReportQuality { validator, .. } => validator,
+            Transaction::OpenChannel { viewer, .. } => viewer,
+            Transaction::CloseChannel { closer, .. } => closer,
+            Transaction::Bond { staker, .. }
+            | Transaction::Unbond { staker, .. }
+            | Transaction::Withdraw { staker, .. } => staker,
+            Transaction::SetRevenueSplit { creator, .. } => creator,
+            Transaction::ReportMisbehavior { reporter, .. } => reporter,
+        }
+    }
+
+    /// Sender account nonce this transaction consumes
+    pub fn nonce(&self) -> u64 {
+        match self {
+            Transaction::Transfer { nonce, .. }
+            | Transaction::RegisterStream { nonce, .. }
+            | Transaction::PurchaseAccess { nonce, .. }
+            | Transaction::ReportQuality { nonce, .. }
+            | Transaction::OpenChannel { nonce, .. }
+            | Transaction::CloseChannel { nonce, .. }
+            | Transaction::Bond { nonce, .. }
+            | Transaction::Unbond { nonce, .. }
+            | Transaction::Withdraw { nonce, .. }
+            | Transaction::SetRevenueSplit { nonce, .. }
+            | Transaction::ReportMisbehavior { nonce, .. } => *nonce,
+        }
+    }
+
+    /// Fee paid to the block author
+    pub fn fee(&self) -> u64 {
+        match self {
+            Transaction::Transfer { fee, .. }
+            | Transaction::RegisterStream { fee, .. }
+            | Transaction::PurchaseAccess { fee, .. }
+            | Transaction::ReportQuality { fee, .. }
+            | Transaction::OpenChannel { fee, .. }
+            | Transaction::CloseChannel { fee, .. }
+            | Transaction::Bond { fee, .. }
+            | Transaction::Unbond { fee, .. }
+            | Transaction::Withdraw { fee, .. }
+            | Transaction::SetRevenueSplit { fee, .. }
+            | Transaction::ReportMisbehavior { fee, .. } => *fee,
+        }
+    }
+
+    /// Balance the transaction debits from the sender, fee included
+    pub fn spend(&self) -> u64 {
+        let amount = match self {
+            Transaction::Transfer { amount, .. }
+            | Transaction::PurchaseAccess { amount, .. } => *amount,
+            Transaction::OpenChannel { deposit, .. } => *deposit,
+            Transaction::Bond { amount, .. } => *amount,
+            Transaction::RegisterStream { .. }
+            | Transaction::ReportQuality { .. }
+            | Transaction::CloseChannel { .. }
+            | Transaction::Unbond { .. }
+            | Transaction::Withdraw { .. }
+            | Transaction::SetRevenueSplit { .. }
+            | Transaction::ReportMisbehavior { .. } => 0,
+        };
+        amount.saturating_add(self.fee())
+    }
+
+    /// Block space the transaction uses: a per-kind base plus its encoded size
+    ///
+    /// The signature is left out of the size so the weight, and with it the
+    /// minimum fee, is known before signing.
+    pub fn weight(&self) -> u64 {
+        let base = match self {
+            Transaction::Transfer { .. } => fees::TRANSFER_WEIGHT,
+            Transaction::RegisterStream { .. } => fees::REGISTER_STREAM_WEIGHT,
+            Transaction::PurchaseAccess { .. } => fees::PURCHASE_ACCESS_WEIGHT,
+            Transaction::ReportQuality { .. } => fees::REPORT_QUALITY_WEIGHT,
+            Transaction::OpenChannel { .. } => fees::OPEN_CHANNEL_WEIGHT,
+            Transaction::CloseChannel { .. } => fees::CLOSE_CHANNEL_WEIGHT,
+            Transaction::Bond { .. } | Transaction::Unbond { .. } => fees::STAKING_WEIGHT,
+            Transaction::Withdraw { .. } => fees::WITHDRAW_WEIGHT,
+            Transaction::SetRevenueSplit { .. } => fees::SET_SPLIT_WEIGHT,
+            Transaction::ReportMisbehavior { .. } => fees::EVIDENCE_WEIGHT,
+        };
+
+        let mut unsigned = self.clone();
+        unsigned.signature_mut().clear();
+        base + unsigned.encoded_len() as u64
+    }
+
+    /// Lowest fee this transaction may pay at the given fee rate
+    pub fn min_fee(&self, fee_per_weight: u64) -> u64 {
+        self.weight().saturating_mul(fee_per_weight)
+    }
+
+    /// blake3 hash of the encoded transaction, hex-encoded
+    pub fn hash(&self) -> String {
+        let bytes = bincode::serialize(self).expect("transaction serialization cannot fail");
+        blake3::hash(&bytes).to_hex().to_string()
+    }
+
+    /// Size of the encoded transaction in bytes
+    pub fn encoded_len(&self) -> usize {
+        bincode::serialized_size(self).expect("transaction serialization cannot fail") as usize
+    }
+
+    fn signature_mut(&mut self) -> &mut String {
+        match self {
+            Transaction::Transfer { signature, .. }
+            | Transaction::RegisterStream { signature, .. }
+            | Transaction::PurchaseAccess { signature, .. }
+            | Transaction::ReportQuality { signature, .. }
+            | Transaction::OpenChannel { signature, .. }
+            | Transaction::CloseChannel { signature, .. }
+            | Transaction::Bond { signature, .. }
+            | Transaction::Unbond { signature, .. }
+            | Transaction::Withdraw { signature, .. }
+            | Transaction::SetRevenueSplit { signature, .. }
+            | Transaction::ReportMisbehavior { signature, .. } => signature,
+        }
+    }
+
+    /// Canonical bytes covered by the signature
+    ///
+    /// This is the bincode encoding of the domain separator, the chain id and
+    /// the transaction with an empty signature, so every field of every
+    /// variant is signed and a transaction cannot be replayed on another chain.
+    pub fn signing_payload(&self, chain_id: &str) -> Vec<u8> {
+        let mut unsigned = self.clone();
+        unsigned.signature_mut().clear();
+        bincode::serialize(&(SIGNING_DOMAIN, chain_id, &unsigned))
+            .expect("transaction serialization cannot fail")
+    }
+
+    /// Sign the transaction in place with the sender's key
+    pub fn sign(&mut self, key: &SigningKey, chain_id: &str) {
+        let payload = self.signing_payload(chain_id);
+        *self.signature_mut() = crypto::sign(key, &payload);
+    }
+
+    /// Check that the signature was made by the sender's key
+    pub fn verify_signature(&self, chain_id: &str) -> Result<()> {
+        let mut unsigned = self.clone();
+        let signature = std::mem::take(unsigned.signature_mut());
+        crypto::verify(self.sender(), &unsigned.signing_payload(chain_id), &signature)
+    }
+}
+
+/// Applies transactions to blockchain state
+///
+/// Every check runs before any mutation, so a rejected transaction leaves
+/// the state untouched. Signatures are verified before transactions reach
+/// the pool; here only the nonce, fee and state-dependent rules are enforced.
 pub struct TransactionProcessor;
 
 impl TransactionProcessor {
-    pub fn new() -> Self {
-        Self
+    /// Apply a block's transactions and pay its fees
+    pub fn apply_block(state: &mut BlockchainState, block: &Block) -> Result<()> {
+        for tx in &block.transactions {
+            Self::apply(state, tx, block.timestamp)?;
+        }
+        Self::finish_block(state, &block.transactions, &block.validator, block.timestamp)
+    }
+
+    /// Apply a single transaction at the given block timestamp
+    ///
+    /// The fee leaves the sender here; [`Self::finish_block`] pays it out.
+    pub fn apply(
+        state: &mut BlockchainState,
+        tx: &Transaction,
+        timestamp: chrono::DateTime<chrono::Utc>,
+    ) -> Result<()> {
+        let expected_nonce = state.accounts.get(tx.sender()).map_or(0, |account| account.nonce);
+        if tx.nonce() != expected_nonce {
+            return Err(anyhow!(
+                "Invalid nonce for {}: expected {}, got {}",
+                tx.sender(), expected_nonce, tx.nonce()
+            ));
+        }
+
+        Self::check(state, tx, 0)?;
+
+        match tx {
+            Transaction::Transfer { from, to, amount, .. } => {
+                Self::account_mut(state, from).balance -= amount;
+                Self::account_mut(state, to).balance += amount;
+            }
+
+            Transaction::RegisterStream { creator, stream_data, .. } => {
+                state.streams.insert(stream_data.stream_id.clone(), stream_data.clone());
+                Self::account_mut(state, creator).created_streams.push(stream_data.stream_id.clone());
+            }
+
+            Transaction::PurchaseAccess { viewer, stream_id, amount, .. } => {
+                // Time bought follows from the amount and the stream's price, never from what the
+                // viewer asks for; depends on the block time, so checked here rather than in `check`
+                let purchase = state.streams[stream_id].purchase(state.accounts.get(viewer), *amount, timestamp)?;
+
+                let buyer = Self::account_mut(state, viewer);
+                buyer.balance -= purchase.charged;
+                if let Some((key, paid_until)) = &purchase.grant {
+                    buyer.grant_access(key, *paid_until, purchase.charged, timestamp);
+                }
+
+                Self::pay_stream(state, stream_id, purchase.charged, timestamp);
+            }
+
+            Transaction::ReportQuality { validator, relay, stream_id, metrics, .. } => {
+                // Scored now, paid out when the epoch ends
+                state.quality_reports.record(validator, relay, stream_id, rewards::score(metrics));
+            }
+
+            Transaction::OpenChannel { viewer, stream_id, deposit, nonce, .. } => {
+                let creator = state.streams[stream_id].creator.clone();
+                Self::account_mut(state, viewer).balance -= deposit;
+
+                let channel_id = channels::channel_id(viewer, *nonce);
+                state.channels.insert(channel_id.clone(), PaymentChannel {
+                    channel_id,
+                    viewer: viewer.clone(),
+                    creator,
+                    stream_id: stream_id.clone(),
+                    deposit: *deposit,
+                    opened_at: timestamp,
+                    closing: None,
+                });
+            }
+
+            Transaction::CloseChannel { closer, channel_id, update, .. } => {
+                let amount = update.as_ref().map_or(0, |update| update.amount);
+                let channel = &state.channels[channel_id];
+
+                if *closer == channel.creator {
+                    // A creator's close can only raise what a pending viewer close named
+                    let amount = channel.closing.as_ref().map_or(amount, |closing| closing.amount.max(amount));
+                    Self::settle_channel(state, channel_id, amount, timestamp);
+                } else {
+                    let settles_at = timestamp + chrono::Duration::seconds(state.params.channel_dispute_secs as i64);
+                    if let Some(channel) = state.channels.get_mut(channel_id) {
+                        channel.closing = Some(ChannelClosing { amount, settles_at });
+                    }
+                }
+            }
+
+            Transaction::Bond { staker, amount, role, .. } => {
+                Self::account_mut(state, staker).balance -= amount;
+
+                let stake = state.stakes.entry(staker.clone()).or_insert_with(|| Stake {
+                    address: staker.clone(),
+                    role: *role,
+                    bonded: 0,
+                    unbonding: Vec::new(),
+                    jailed_until: None,
+                });
+                stake.role = *role;
+                stake.bonded += amount;
+            }
+
+            Transaction::Unbond { staker, amount, .. } => {
+                let release_at = timestamp + chrono::Duration::seconds(state.params.staking.unbonding_secs as i64);
+                if let Some(stake) = state.stakes.get_mut(staker) {
+                    stake.bonded -= amount;
+                    stake.unbonding.push(Unbonding { amount: *amount, release_at });
+                }
+            }
+
+            Transaction::Withdraw { staker, .. } => {
+                // Whether anything has matured depends on the block time, so it is checked here
+                let released = state.stakes.get(staker).map_or(0, |stake| stake.withdrawable(timestamp));
+                if released == 0 {
+                    return Err(anyhow!("{} has no unbonded stake ready to withdraw", staker));
+                }
+
+                if let Some(stake) = state.stakes.get_mut(staker) {
+                    stake.unbonding.retain(|chunk| chunk.release_at > timestamp);
+                    if stake.is_empty() {
+                        state.stakes.remove(staker);
+                    }
+                }
+                Self::account_mut(state, staker).balance += released;
+            }
+
+            Transaction::SetRevenueSplit { stream_id, split, .. } => {
+                if let Some(stream) = state.streams.get_mut(stream_id) {
+                    stream.split = split.clone();
+                }
+            }
+
+            Transaction::ReportMisbehavior { reporter, evidence, .. } => {
+                Self::punish(state, reporter, evidence, timestamp);
+            }
+        }
+
+        let sender = Self::account_mut(state, tx.sender());
+        sender.balance -= tx.fee();
+        sender.nonce += 1;
+
+        Ok(())
+    }
+
+    /// Pay a block's fees to its author, burn the configured share and move the fee rate
+    ///
+    /// Runs once the block's transactions are applied. The relay share of the
+    /// author's fees goes to the reward pool, and the last block of an epoch
+    /// pays the pool out. Payment channels whose dispute window has passed by
+    /// the block's timestamp settle here too.
+    pub fn finish_block(
+        state: &mut BlockchainState,
+        transactions: &[Transaction],
+        author: &str,
+        timestamp: chrono::DateTime<chrono::Utc>,
+    ) -> Result<()> {
+        let fee_params = state.params.fees;
+
+        let weight = transactions.iter().map(Transaction::weight).sum::<u64>();
+        if weight > fee_params.max_block_weight() {
+            return Err(anyhow!("Block weight {} exceeds the maximum of {}", weight, fee_params.max_block_weight()));
+        }
+
+        let collected = transactions.iter().map(Transaction::fee).sum::<u64>();
+        let (reward, burned) = fee_params.split(collected);
+        let relay_share = (reward as u128 * state.params.rewards.relay_fee_share_percent.min(100) as u128 / 100) as u64;
+        if reward > relay_share {
+            Self::account_mut(state, author).balance += reward - relay_share;
+        }
+        state.relay_pool += relay_share;
+        state.burned_fees += burned;
+        state.fee_per_weight = fee_params.next_fee_per_weight(state.fee_per_weight, weight);
+
+        let settled: Vec<(String, u64)> = state.channels.values()
+            .filter_map(|channel| {
+                let closing = channel.closing.as_ref().filter(|closing| closing.settles_at <= timestamp)?;
+                Some((channel.channel_id.clone(), closing.amount))
+            })
+            .collect();
+        for (channel_id, amount) in settled {
+            Self::settle_channel(state, &channel_id, amount, timestamp);
+        }
+
+        // The parent's number, as `best_block` moves only once the block is committed
+        let number = state.best_block + 1;
+        if number.is_multiple_of(state.params.epoch_blocks.max(1)) {
+            Self::end_epoch(state, timestamp);
+        }
+
+        Ok(())
+    }
+
+    /// Pay the relay reward pool out by cross-checked quality scores and start a new epoch
+    ///
+    /// The epoch reward is minted only when some relay was scored; otherwise
+    /// the pool carries over to the next epoch.
+    fn end_epoch(state: &mut BlockchainState, timestamp: chrono::DateTime<chrono::Utc>) {
+        let params = state.params.rewards;
+        let check = state.quality_reports.cross_check(&params);
+
+        if check.relay_scores.values().any(|score| *score > 0) {
+            let pool = state.relay_pool + params.epoch_reward;
+            let mut paid = 0;
+            for (relay, amount) in rewards::payouts(pool, &check.relay_scores) {
+                Self::account_mut(state, &relay).balance += amount;
+                paid += amount;
+            }
+            state.relay_pool = pool - paid;
+        }
+
+        state.quality_reports = EpochReports::new(state.quality_reports.epoch + 1);
+
+        // Never leave a chain without validators; a dev chain has none to begin with
+        if !state.validators.is_empty() {
+            let elected = staking::elect_validators(&state.stakes, &state.params.staking, timestamp);
+            if !elected.is_empty() {
+                state.validators = elected;
+            }
+        }
+    }
+
+    /// Check every rule except the nonce
+    ///
+    /// `reserved` is balance already claimed by the sender's earlier
+    /// transactions that are not applied to `state` yet, such as pooled ones.
+    pub fn check(state: &BlockchainState, tx: &Transaction, reserved: u64) -> Result<()> {
+        let min_fee = tx.min_fee(state.fee_per_weight);
+        if tx.fee() < min_fee {
+            return Err(anyhow!("Fee {} from {} is below the minimum of {}", tx.fee(), tx.sender(), min_fee));
+        }
+
+        let available = state.accounts.get(tx.sender())
+            .map_or(0, |account| account.balance.saturating_sub(reserved));
+        if available < tx.spend() {
+            return Err(anyhow!("Insufficient balance for {}", tx.sender()));
+        }
+
+        match tx {
+            Transaction::RegisterStream { creator, stream_data, .. } => {
+                if state.streams.contains_key(&stream_data.stream_id) {
+                    return Err(anyhow!("Stream {} already exists", stream_data.stream_id));
+                }
+                if stream_data.creator != *creator {
+                    return Err(anyhow!("Stream creator does not match transaction sender"));
+                }
+                if stream_data.total_earnings > 0
+                    || !stream_data.paid_out.is_empty() || !stream_data.recent_payouts.is_empty()
+                {
+                    return Err(anyhow!("Stream {} must be registered without history", stream_data.stream_id));
+                }
+                stream_data.split.validate()?;
+                stream_data.pricing.validate()?;
+                if pricing::subscribed_creator(&stream_data.stream_id).is_some() {
+                    return Err(anyhow!("Stream id {} is reserved for subscriptions", stream_data.stream_id));
+                }
+                if stream_data.pricing == PricingModel::PerMinute
+                    && stream_data.price_per_minute < state.params.min_price_per_minute
+                {
+                    return Err(anyhow!("Stream price {} is below the minimum of {}",
+                                       stream_data.price_per_minute, state.params.min_price_per_minute));
+                }
+            }
+
+            Transaction::PurchaseAccess { stream_id, .. } => {
+                if !state.streams.contains_key(stream_id) {
+                    return Err(anyhow!("Stream {} not found", stream_id));
+                }
+            }
+
+            Transaction::OpenChannel { viewer, stream_id, deposit, .. } => {
+                let stream = state.streams.get(stream_id)
+                    .ok_or_else(|| anyhow!("Stream {} not found", stream_id))?;
+                if stream.creator == *viewer {
+                    return Err(anyhow!("Creator {} cannot open a channel to their own stream", viewer));
+                }
+                // Channel updates buy watch time by the minute
+                if stream.pricing != PricingModel::PerMinute {
+                    return Err(anyhow!("Stream {} is not priced per minute and takes no channels", stream_id));
+                }
+                if *deposit == 0 {
+                    return Err(anyhow!("Channel deposit must not be zero"));
+                }
+            }
+
+            Transaction::CloseChannel { closer, channel_id, update, .. } => {
+                let channel = state.channels.get(channel_id)
+                    .ok_or_else(|| anyhow!("Channel {} is not open", channel_id))?;
+                if *closer != channel.viewer && *closer != channel.creator {
+                    return Err(anyhow!("{} is not a party to channel {}", closer, channel_id));
+                }
+                if *closer == channel.viewer && channel.closing.is_some() {
+                    return Err(anyhow!("Channel {} is already closing", channel_id));
+                }
+                if let Some(update) = update {
+                    update.verify(channel, &state.chain_id)?;
+                }
+            }
+
+            Transaction::ReportQuality { validator, relay, stream_id, .. } => {
+                // A dev chain has no validator set; anyone may report there
+                if !state.validators.is_empty() && !state.validators.iter().any(|v| v.address == *validator) {
+                    return Err(anyhow!("{} is not a validator and cannot report stream quality", validator));
+                }
+                if relay == validator {
+                    return Err(anyhow!("Validator {} cannot report on its own relaying", validator));
+                }
+                if !state.streams.contains_key(stream_id) {
+                    return Err(anyhow!("Stream {} not found", stream_id));
+                }
+            }
+
+            Transaction::SetRevenueSplit { creator, stream_id, split, .. } => {
+                let stream = state.streams.get(stream_id)
+                    .ok_or_else(|| anyhow!("Stream {} not found", stream_id))?;
+                if stream.creator != *creator {
+                    return Err(anyhow!("Only the creator of stream {} can change its revenue split", stream_id));
+                }
+                split.validate()?;
+            }
+
+            Transaction::ReportMisbehavior { reporter, evidence, .. } => {
+                let offender = evidence.offender();
+                if reporter == offender {
+                    return Err(anyhow!("{} cannot report its own misbehavior", reporter));
+                }
+                if state.stakes.get(offender).is_none_or(|stake| stake.is_empty()) {
+                    return Err(anyhow!("{} has no stake to slash", offender));
+                }
+                evidence.verify(state)?;
+            }
+
+            Transaction::Bond { amount, role, .. } => {
+                if *amount == 0 {
+                    return Err(anyhow!("Bond amount must not be zero"));
+                }
+                if *role == (StakeRole::Relay { capacity: 0 }) {
+                    return Err(anyhow!("Relay capacity must not be zero"));
+                }
+            }
+
+            Transaction::Unbond { staker, amount, .. } => {
+                let bonded = state.stakes.get(staker).map_or(0, |stake| stake.bonded);
+                if *amount == 0 || *amount > bonded {
+                    return Err(anyhow!("Cannot unbond {} from {} with {} bonded", amount, staker, bonded));
+                }
+            }
+
+            Transaction::Withdraw { staker, .. } => {
+                if state.stakes.get(staker).is_none_or(|stake| stake.unbonding.is_empty()) {
+                    return Err(anyhow!("{} has no unbonding stake", staker));
+                }
+            }
+
+            Transaction::Transfer { .. } => {}
+        }
+
+        Ok(())
+    }
+
+    /// Credit a payment for a stream to the recipients of its revenue split
+    pub fn pay_stream(
+        state: &mut BlockchainState,
+        stream_id: &str,
+        amount: u64,
+        timestamp: chrono::DateTime<chrono::Utc>,
+    ) {
+        let Some(stream) = state.streams.get_mut(stream_id) else {
+            return;
+        };
+
+        for (recipient, paid) in stream.split_payment(amount, timestamp) {
+            Self::account_mut(state, &recipient).balance += paid;
+        }
+    }
+
+    /// Pay `amount` of a channel's deposit to the stream, refund the rest and close it
+    fn settle_channel(
+        state: &mut BlockchainState,
+        channel_id: &str,
+        amount: u64,
+        timestamp: chrono::DateTime<chrono::Utc>,
+    ) {
+        let Some(channel) = state.channels.remove(channel_id) else {
+            return;
+        };
+        let amount = amount.min(channel.deposit);
+
+        Self::account_mut(state, &channel.viewer).balance += channel.deposit - amount;
+        if state.streams.contains_key(&channel.stream_id) {
+            Self::pay_stream(state, &channel.stream_id, amount, timestamp);
+        } else {
+            Self::account_mut(state, &channel.creator).balance += amount;
+        }
+    }
+
+    /// Slash and jail the offender named by verified evidence, rewarding the reporter
+    fn punish(
+        state: &mut BlockchainState,
+        reporter: &str,
+        evidence: &Evidence,
+        timestamp: chrono::DateTime<chrono::Utc>,
+    ) {
+        let params = state.params.slashing;
+        let offender = evidence.offender().to_string();
+        let limit = evidence.slash_limit(state);
+        let Some(stake) = state.stakes.get_mut(&offender) else {
+            return;
+        };
+
+        let slashed = stake.slash(evidence.slash_percent(&params), limit);
+        stake.jailed_until = Some(timestamp + chrono::Duration::seconds(params.jail_secs as i64));
+
+        let reward = (slashed as u128 * params.reporter_reward_percent.min(100) as u128 / 100) as u64;
+        Self::account_mut(state, reporter).balance += reward;
+        state.slashed_stake += slashed - reward;
+
+        // Keep at least one validator so the chain can still produce blocks
+        if state.validators.iter().any(|validator| validator.address != offender) {
+            state.validators.retain(|validator| validator.address != offender);
+        }
+
+        match evidence {
+            Evidence::DoubleSign { first, .. } => {
+                state.double_signs.insert((offender, first.number));
+            }
+            Evidence::FalseQualityReport { .. } => {
+                state.quality_reports.remove_reporter(&offender);
+            }
+        }
+    }
+
+    /// Get an account, creating an empty one if needed
+    fn account_mut<'a>(state: &'a mut BlockchainState, address: &str) -> &'a mut Account {
+        state.accounts.entry(address.to_string())
+            .or_insert_with(|| Account {
+                address: address.to_string(),
+                balance: 0,
+                nonce: 0,
+                stream_access: HashMap::new(),
+                created_streams: Vec::new(),
+            })
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::channels::ChannelUpdate;
+    use crate::blockchain::genesis::GenesisSpec;
+    use crate::blockchain::StreamRegistration;
+
+    /// Chain where fees are free, with `viewer` funded and `creator` running a per-minute stream
+    fn state(viewer: &str) -> BlockchainState {
+        let spec = GenesisSpec {
+            balances: [(viewer.to_string(), 10_000), ("creator".to_string(), 0)].into_iter().collect(),
+            ..GenesisSpec::default()
+        };
+        let mut state = spec.build().0;
+        state.fee_per_weight = 0;
+        state.streams.insert("stream".to_string(), stream(PricingModel::PerMinute));
+        state
+    }
+
+    fn stream(pricing: PricingModel) -> StreamRegistration {
+        StreamRegistration {
+            stream_id: "stream".to_string(),
+            creator: "creator".to_string(),
+            title: "Stream".to_string(),
+            description: None,
+            price_per_minute: 10,
+            pricing,
+            preview_minutes: 0,
+            created_at: chrono::Utc::now(),
+            total_earnings: 0,
+            split: Default::default(),
+            paid_out: Default::default(),
+            recent_payouts: Vec::new(),
+        }
+    }
+
+    fn balance(state: &BlockchainState, address: &str) -> u64 {
+        state.accounts.get(address).map_or(0, |account| account.balance)
+    }
+
+    fn nonce(state: &BlockchainState, address: &str) -> u64 {
+        state.accounts.get(address).map_or(0, |account| account.nonce)
+    }
+
+    /// Open a channel from `viewer` to the stream, returning its id
+    fn open_channel(state: &mut BlockchainState, viewer: &str, deposit: u64) -> String {
+        let nonce = nonce(state, viewer);
+        let tx = Transaction::OpenChannel {
+            viewer: viewer.to_string(),
+            stream_id: "stream".to_string(),
+            deposit,
+            nonce,
+            fee: 0,
+            signature: String::new(),
+        };
+        TransactionProcessor::apply(state, &tx, chrono::Utc::now()).unwrap();
+        channels::channel_id(viewer, nonce)
+    }
+
+    fn close_channel(
+        state: &mut BlockchainState,
+        closer: &str,
+        channel_id: &str,
+        update: Option<ChannelUpdate>,
+        timestamp: chrono::DateTime<chrono::Utc>,
+    ) -> Result<()> {
+        let tx = Transaction::CloseChannel {
+            closer: closer.to_string(),
+            channel_id: channel_id.to_string(),
+            update,
+            nonce: nonce(state, closer),
+            fee: 0,
+            signature: String::new(),
+        };
+        TransactionProcessor::apply(state, &tx, timestamp)
+    }
+
+    fn update(key: &SigningKey, state: &BlockchainState, channel_id: &str, amount: u64) -> ChannelUpdate {
+        ChannelUpdate {
+            channel_id: channel_id.to_string(),
+            amount,
+            signature: crypto::sign(key, &ChannelUpdate::signing_payload(channel_id, amount, &state.chain_id)),
+        }
+    }
+
+    /// Apply the staking transaction `tx` builds for the staker's next nonce
+    fn stake_tx(
+        state: &mut BlockchainState,
+        staker: &str,
+        tx: impl Fn(u64) -> Transaction,
+        timestamp: chrono::DateTime<chrono::Utc>,
+    ) -> Result<()> {
+        let tx = tx(nonce(state, staker));
+        TransactionProcessor::apply(state, &tx, timestamp)
+    }
+
+    #[test]
+    fn viewer_closes_settle_after_the_dispute_window() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let viewer = crypto::address_from_public_key(&key.verifying_key());
+        let mut state = state(&viewer);
+        let channel_id = open_channel(&mut state, &viewer, 1_000);
+        assert_eq!(balance(&state, &viewer), 9_000);
+
+        let now = chrono::Utc::now();
+        let close = update(&key, &state, &channel_id, 300);
+        close_channel(&mut state, &viewer, &channel_id, Some(close), now).unwrap();
+        assert!(close_channel(&mut state, &viewer, &channel_id, None, now).is_err());
+
+        // Nothing settles before the window passes
+        TransactionProcessor::finish_block(&mut state, &[], "author", now).unwrap();
+        assert!(state.channels.contains_key(&channel_id));
+
+        let window = chrono::Duration::seconds(state.params.channel_dispute_secs as i64);
+        TransactionProcessor::finish_block(&mut state, &[], "author", now + window).unwrap();
+        assert!(!state.channels.contains_key(&channel_id));
+        assert_eq!(balance(&state, &viewer), 9_700);
+        assert_eq!(balance(&state, "creator"), 300);
+    }
+
+    #[test]
+    fn creators_answer_a_stale_close_with_a_newer_update() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let viewer = crypto::address_from_public_key(&key.verifying_key());
+        let mut state = state(&viewer);
+        let channel_id = open_channel(&mut state, &viewer, 1_000);
+        let (stale, latest) = (update(&key, &state, &channel_id, 100), update(&key, &state, &channel_id, 600));
+
+        let now = chrono::Utc::now();
+        close_channel(&mut state, &viewer, &channel_id, Some(stale), now).unwrap();
+        close_channel(&mut state, "creator", &channel_id, Some(latest), now).unwrap();
+        assert!(!state.channels.contains_key(&channel_id));
+        assert_eq!(balance(&state, &viewer), 9_400);
+        assert_eq!(balance(&state, "creator"), 600);
+    }
+
+    #[test]
+    fn closes_need_a_party_and_an_update_signed_by_the_viewer() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let viewer = crypto::address_from_public_key(&key.verifying_key());
+        let mut state = state(&viewer);
+        let channel_id = open_channel(&mut state, &viewer, 1_000);
+        let now = chrono::Utc::now();
+
+        assert!(close_channel(&mut state, "stranger", &channel_id, None, now).is_err());
+        let forged = update(&SigningKey::from_bytes(&[2; 32]), &state, &channel_id, 1_000);
+        assert!(close_channel(&mut state, "creator", &channel_id, Some(forged), now).is_err());
+        let excessive = update(&key, &state, &channel_id, 1_001);
+        assert!(close_channel(&mut state, "creator", &channel_id, Some(excessive), now).is_err());
+        assert!(state.channels.contains_key(&channel_id));
+    }
+
+    #[test]
+    fn bonded_stake_unbonds_and_withdraws_after_the_unbonding_period() {
+        let mut state = state("alice");
+        let now = chrono::Utc::now();
+        let bond = |amount| move |nonce| Transaction::Bond {
+            staker: "alice".to_string(),
+            amount,
+            role: StakeRole::Validator,
+            nonce,
+            fee: 0,
+            signature: String::new(),
+        };
+        let unbond = |amount| move |nonce| Transaction::Unbond {
+            staker: "alice".to_string(),
+            amount,
+            nonce,
+            fee: 0,
+            signature: String::new(),
+        };
+        let withdraw = |nonce| Transaction::Withdraw { staker: "alice".to_string(), nonce, fee: 0, signature: String::new() };
+
+        assert!(stake_tx(&mut state, "alice", bond(0), now).is_err());
+        assert!(stake_tx(&mut state, "alice", bond(10_001), now).is_err());
+        stake_tx(&mut state, "alice", bond(6_000), now).unwrap();
+        stake_tx(&mut state, "alice", bond(4_000), now).unwrap();
+        assert_eq!(balance(&state, "alice"), 0);
+        assert_eq!(state.stakes["alice"].bonded, 10_000);
+
+        assert!(stake_tx(&mut state, "alice", withdraw, now).is_err());
+        assert!(stake_tx(&mut state, "alice", unbond(10_001), now).is_err());
+        stake_tx(&mut state, "alice", unbond(3_000), now).unwrap();
+        assert_eq!(state.stakes["alice"].bonded, 7_000);
+
+        // Unbonding stake stays locked until its period passes
+        assert!(stake_tx(&mut state, "alice", withdraw, now).is_err());
+        let released = now + chrono::Duration::seconds(state.params.staking.unbonding_secs as i64);
+        stake_tx(&mut state, "alice", withdraw, released).unwrap();
+        assert_eq!(balance(&state, "alice"), 3_000);
+        assert!(state.stakes["alice"].unbonding.is_empty());
+
+        // Withdrawing everything removes the stake
+        stake_tx(&mut state, "alice", unbond(7_000), released).unwrap();
+        stake_tx(&mut state, "alice", withdraw, released + chrono::Duration::days(30)).unwrap();
+        assert!(!state.stakes.contains_key("alice"));
+        assert_eq!(balance(&state, "alice"), 10_000);
+    }
+
+    #[test]
+    fn only_per_minute_streams_take_channels() {
+        let mut state = state("alice");
+        let open = Transaction::OpenChannel {
+            viewer: "alice".to_string(),
+            stream_id: "stream".to_string(),
+            deposit: 1_000,
+            nonce: 0,
+            fee: 0,
+            signature: String::new(),
+        };
+        for pricing in [PricingModel::Free, PricingModel::Subscription { price: 100, period_days: 30 }] {
+            state.streams.insert("stream".to_string(), stream(pricing));
+            let err = TransactionProcessor::apply(&mut state, &open, chrono::Utc::now()).unwrap_err();
+            assert!(err.to_string().contains("not priced per minute"));
+        }
+        assert!(state.channels.is_empty());
+        assert_eq!(balance(&state, "alice"), 10_000);
+    }
+}
@@ -0,0 +1,293 @@
+// Metered billing
+//
+// The meter follows every viewer connected to a stream and adds up their
+// watch time. On each tick, a viewer whose `paid_until` is within the lead
+// time owes a payment: the next billing period at the stream's
+// `price_per_minute`, or the fixed price of a subscription period or event
+// ticket. Paid time counts on-chain grants and payment channel
+// updates alike, so a viewer who keeps their channel ahead is never billed.
+// The node never pays on a viewer's behalf: a payment counts once the viewer
+// signs a `PurchaseAccess` transaction or a channel update. A viewer is
+// revoked when the paid time runs out while a payment is still outstanding.
+
+use std::collections::HashMap;
+
+/// Billing cadence of the meter
+#[derive(Debug, Clone, Copy)]
+pub struct MeteringConfig {
+    /// Seconds between billing checks
+    pub tick_secs: u64,
+    /// Bill this many seconds before `paid_until` runs out
+    pub lead_secs: u64,
+    /// Minutes of access each payment buys
+    pub billing_minutes: u64,
+    /// Seconds to wait for an outstanding payment before asking again
+    pub payment_timeout_secs: u64,
+}
+
+impl Default for MeteringConfig {
+    fn default() -> Self {
+        Self {
+            tick_secs: 5,
+            lead_secs: 30,
+            billing_minutes: 1,
+            payment_timeout_secs: 30,
+        }
+    }
+}
+
+/// Terms a viewer is billed under
+#[derive(Debug, Clone, Copy)]
+pub struct BillingTerms {
+    pub price_per_minute: u64,
+    /// Price of a subscription period or ticket, asked for instead of watch time
+    pub fixed_price: Option<u64>,
+    /// End of the viewer's paid access, `None` before the first payment
+    pub paid_until: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl BillingTerms {
+    /// Amount each payment asks for; zero when the viewer is not billed
+    fn payment(&self, billing_minutes: u64) -> u64 {
+        self.fixed_price.unwrap_or(self.price_per_minute.saturating_mul(billing_minutes))
+    }
+}
+
+/// What the bridge should do after a billing tick
+#[derive(Debug, Clone, PartialEq)]
+pub enum MeterAction {
+    PaymentDue { stream_id: String, viewer: String, amount: u64 },
+    Revoke { stream_id: String, viewer: String, reason: String },
+}
+
+#[derive(Debug, Clone)]
+struct MeteredViewer {
+    connected_at: chrono::DateTime<chrono::Utc>,
+    last_tick: chrono::DateTime<chrono::Utc>,
+    watch_seconds: u64,
+    total_paid: u64,
+    /// When the outstanding payment was requested
+    payment_due_since: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Watch time and billing state of connected viewers
+pub struct Meter {
+    config: MeteringConfig,
+    viewers: HashMap<(String, String), MeteredViewer>,
+}
+
+impl Meter {
+    pub fn new(config: MeteringConfig) -> Self {
+        Self {
+            config,
+            viewers: HashMap::new(),
+        }
+    }
+
+    /// Start metering a viewer; reconnecting keeps the existing record
+    pub fn viewer_connected(&mut self, stream_id: &str, viewer: &str, now: chrono::DateTime<chrono::Utc>) {
+        self.viewers.entry((stream_id.to_string(), viewer.to_string()))
+            .or_insert(MeteredViewer {
+                connected_at: now,
+                last_tick: now,
+                watch_seconds: 0,
+                total_paid: 0,
+                payment_due_since: None,
+            });
+    }
+
+    /// Stop metering a viewer, returning their watch time in seconds and what they paid
+    pub fn viewer_disconnected(&mut self, stream_id: &str, viewer: &str, now: chrono::DateTime<chrono::Utc>) -> Option<(u64, u64)> {
+        let metered = self.viewers.remove(&(stream_id.to_string(), viewer.to_string()))?;
+        Some((metered.watch_seconds + elapsed_secs(metered.last_tick, now), metered.total_paid))
+    }
+
+    /// Stop metering every viewer of a stream
+    pub fn stream_ended(&mut self, stream_id: &str) {
+        self.viewers.retain(|(stream, _), _| stream != stream_id);
+    }
+
+    /// Record a successful payment, clearing the outstanding request
+    pub fn payment_settled(&mut self, stream_id: &str, viewer: &str, amount: u64) {
+        if let Some(metered) = self.viewers.get_mut(&(stream_id.to_string(), viewer.to_string())) {
+            metered.total_paid += amount;
+            metered.payment_due_since = None;
+        }
+    }
+
+    /// Stop billing a viewer who lost access; returns whether they were metered
+    pub fn remove_viewer(&mut self, stream_id: &str, viewer: &str) -> bool {
+        self.viewers.remove(&(stream_id.to_string(), viewer.to_string())).is_some()
+    }
+
+    /// Metered (stream, viewer) pairs, for looking up their billing terms
+    pub fn viewers(&self) -> Vec<(String, String)> {
+        self.viewers.keys().cloned().collect()
+    }
+
+    /// Advance watch time and decide which viewers owe a payment or lose access
+    ///
+    /// Viewers without terms (streams not registered on-chain) or watching a
+    /// free stream are not billed.
+    pub fn tick(
+        &mut self,
+        now: chrono::DateTime<chrono::Utc>,
+        terms: &HashMap<(String, String), BillingTerms>,
+    ) -> Vec<MeterAction> {
+        let lead = chrono::Duration::seconds(self.config.lead_secs as i64);
+        let timeout = chrono::Duration::seconds(self.config.payment_timeout_secs as i64);
+
+        let mut actions = Vec::new();
+        let mut revoked = Vec::new();
+        for (key, metered) in self.viewers.iter_mut() {
+            // Carry the sub-second remainder over to the next tick
+            let elapsed = elapsed_secs(metered.last_tick, now);
+            metered.watch_seconds += elapsed;
+            metered.last_tick += chrono::Duration::seconds(elapsed as i64);
+
+            let Some(terms) = terms.get(key).filter(|terms| terms.payment(self.config.billing_minutes) > 0) else {
+                continue;
+            };
+            let (stream_id, viewer) = key.clone();
+
+            // A viewer who never paid is billed at once and has the lead time to pay
+            let paid_until = terms.paid_until.unwrap_or(metered.connected_at + lead);
+            if paid_until - now > lead {
+                // Paid up, so any outstanding request has been met
+                metered.payment_due_since = None;
+                continue;
+            }
+
+            match metered.payment_due_since {
+                Some(since) if now - since < timeout => {}
+                Some(_) if paid_until <= now => {
+                    actions.push(MeterAction::Revoke {
+                        stream_id,
+                        viewer,
+                        reason: "Payment overdue".to_string(),
+                    });
+                    revoked.push(key.clone());
+                }
+                _ => {
+                    metered.payment_due_since = Some(now);
+                    actions.push(MeterAction::PaymentDue {
+                        stream_id,
+                        viewer,
+                        amount: terms.payment(self.config.billing_minutes),
+                    });
+                }
+            }
+        }
+
+        for key in revoked {
+            self.viewers.remove(&key);
+        }
+        actions
+    }
+}
+
+fn elapsed_secs(from: chrono::DateTime<chrono::Utc>, to: chrono::DateTime<chrono::Utc>) -> u64 {
+    (to - from).num_seconds().max(0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> (String, String) {
+        ("stream".to_string(), "viewer".to_string())
+    }
+
+    fn terms(price_per_minute: u64, fixed_price: Option<u64>, paid_until: Option<chrono::DateTime<chrono::Utc>>)
+        -> HashMap<(String, String), BillingTerms>
+    {
+        HashMap::from([(key(), BillingTerms { price_per_minute, fixed_price, paid_until })])
+    }
+
+    fn seconds(secs: i64) -> chrono::Duration {
+        chrono::Duration::seconds(secs)
+    }
+
+    fn due(amount: u64) -> MeterAction {
+        MeterAction::PaymentDue { stream_id: "stream".to_string(), viewer: "viewer".to_string(), amount }
+    }
+
+    #[test]
+    fn bills_an_unpaid_viewer_once_per_timeout() {
+        let start = chrono::Utc::now();
+        let mut meter = Meter::new(MeteringConfig { billing_minutes: 2, ..MeteringConfig::default() });
+        meter.viewer_connected("stream", "viewer", start);
+
+        assert_eq!(meter.tick(start, &terms(5, None, None)), [due(10)]);
+        assert!(meter.tick(start + seconds(10), &terms(5, None, None)).is_empty());
+    }
+
+    #[test]
+    fn revokes_when_paid_time_runs_out_with_a_payment_outstanding() {
+        let start = chrono::Utc::now();
+        let mut meter = Meter::new(MeteringConfig::default());
+        meter.viewer_connected("stream", "viewer", start);
+
+        assert_eq!(meter.tick(start, &terms(5, None, None)).len(), 1);
+
+        let now = start + seconds(31);
+        let revoke = MeterAction::Revoke {
+            stream_id: "stream".to_string(),
+            viewer: "viewer".to_string(),
+            reason: "Payment overdue".to_string(),
+        };
+        assert_eq!(meter.tick(now, &terms(5, None, None)), [revoke]);
+        assert!(meter.viewers().is_empty());
+    }
+
+    #[test]
+    fn asks_again_after_the_timeout_while_time_remains() {
+        let start = chrono::Utc::now();
+        let mut meter = Meter::new(MeteringConfig { payment_timeout_secs: 10, ..MeteringConfig::default() });
+        meter.viewer_connected("stream", "viewer", start);
+        let paid_until = Some(start + seconds(100));
+
+        assert!(meter.tick(start + seconds(60), &terms(5, None, paid_until)).is_empty());
+        assert_eq!(meter.tick(start + seconds(75), &terms(5, None, paid_until)), [due(5)]);
+        assert!(meter.tick(start + seconds(80), &terms(5, None, paid_until)).is_empty());
+        assert_eq!(meter.tick(start + seconds(86), &terms(5, None, paid_until)), [due(5)]);
+    }
+
+    #[test]
+    fn paid_up_viewers_are_not_billed() {
+        let start = chrono::Utc::now();
+        let mut meter = Meter::new(MeteringConfig::default());
+        meter.viewer_connected("stream", "viewer", start);
+
+        assert!(meter.tick(start, &terms(5, None, Some(start + seconds(600)))).is_empty());
+        assert!(meter.tick(start, &terms(0, None, None)).is_empty());
+        assert!(meter.tick(start, &HashMap::new()).is_empty());
+    }
+
+    #[test]
+    fn payment_clears_the_outstanding_request() {
+        let start = chrono::Utc::now();
+        let mut meter = Meter::new(MeteringConfig::default());
+        meter.viewer_connected("stream", "viewer", start);
+
+        assert_eq!(meter.tick(start, &terms(0, Some(300), None)), [due(300)]);
+        meter.payment_settled("stream", "viewer", 300);
+
+        // Paid up now, and billed again once the period nears its end
+        let paid_until = Some(start + seconds(3_600));
+        assert!(meter.tick(start + seconds(5), &terms(0, Some(300), paid_until)).is_empty());
+        assert_eq!(meter.tick(start + seconds(3_580), &terms(0, Some(300), paid_until)), [due(300)]);
+    }
+
+    #[test]
+    fn counts_watch_time_until_disconnect() {
+        let start = chrono::Utc::now();
+        let mut meter = Meter::new(MeteringConfig::default());
+        meter.viewer_connected("stream", "viewer", start);
+        meter.tick(start + seconds(5), &HashMap::new());
+        meter.payment_settled("stream", "viewer", 7);
+
+        assert_eq!(meter.viewer_disconnected("stream", "viewer", start + seconds(12)), Some((12, 7)));
+        assert_eq!(meter.viewer_disconnected("stream", "viewer", start + seconds(12)), None);
+    }
+}
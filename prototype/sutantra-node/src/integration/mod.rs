@@ -1,16 +1,22 @@
 use anyhow::Result;
+use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
-use tracing::{info, error};
+use tokio::sync::{broadcast, mpsc, RwLock};
+use tracing::{info, debug, warn, error};
 
-use crate::blockchain::{BlockchainEngine, BlockchainEvent, BlockchainCommand};
-use crate::streaming::{StreamingEngine, StreamingEvent, StreamingCommand};
+use crate::blockchain::{BlockchainEngine, BlockchainEvent, BlockchainCommand, ChainReader};
+use crate::blockchain::pricing::PricingModel;
+use crate::streaming::{Signaling, StreamingEngine, StreamingEvent, StreamingCommand};
 
 pub mod events;
+pub mod metering;
 pub mod node;
 
+pub use metering::MeteringConfig;
 pub use node::SutantraNode;
 
+use metering::{BillingTerms, Meter, MeterAction};
+
 /// Central event that coordinates between blockchain and streaming layers
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub enum SutantraEvent {
@@ -57,6 +63,17 @@ pub struct EventBridge {
     
     // Shared state
     active_streams: Arc<RwLock<std::collections::HashMap<String, StreamState>>>,
+    
+    // Pay-per-minute billing of connected viewers
+    chain: ChainReader,
+    meter: Arc<RwLock<Meter>>,
+    billing_interval: tokio::time::Duration,
+    
+    // Peer connections negotiated over `/ws` and WHEP, keyed by verified address
+    signaling: Arc<tokio::sync::OnceCell<Signaling>>,
+    
+    // Events relayed to viewers' `/ws` clients
+    viewer_events: broadcast::Sender<SutantraEvent>,
 }
 
 #[derive(Debug, Clone)]
@@ -71,11 +88,16 @@ pub struct StreamState {
 }
 
 impl EventBridge {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         blockchain_tx: mpsc::Sender<BlockchainCommand>,
         streaming_tx: mpsc::Sender<StreamingCommand>,
         blockchain_events: mpsc::Receiver<BlockchainEvent>,
         streaming_events: mpsc::Receiver<StreamingEvent>,
+        chain: ChainReader,
+        signaling: Arc<tokio::sync::OnceCell<Signaling>>,
+        viewer_events: broadcast::Sender<SutantraEvent>,
+        metering: MeteringConfig,
     ) -> Self {
         let (event_bus, event_rx) = mpsc::channel(1000);
         
@@ -87,6 +109,11 @@ impl EventBridge {
             event_bus,
             event_rx,
             active_streams: Arc::new(RwLock::new(std::collections::HashMap::new())),
+            chain,
+            meter: Arc::new(RwLock::new(Meter::new(metering))),
+            billing_interval: tokio::time::Duration::from_secs(metering.tick_secs.max(1)),
+            signaling,
+            viewer_events,
         }
     }
     
@@ -94,6 +121,8 @@ impl EventBridge {
     pub async fn run(&mut self) -> Result<()> {
         info!("🌉 Starting Event Bridge");
         
+        let mut billing = tokio::time::interval(self.billing_interval);
+        
         loop {
             tokio::select! {
                 // Handle blockchain events
@@ -111,6 +140,11 @@ impl EventBridge {
                     self.handle_sutantra_event(sutantra_event).await?;
                 }
                 
+                // Bill connected viewers
+                _ = billing.tick() => {
+                    self.run_billing().await?;
+                }
+                
                 else => {
                     error!("All event channels closed");
                     break;
@@ -156,6 +190,8 @@ impl EventBridge {
                 };
                 self.streaming_tx.send(cmd).await?;
                 
+                self.meter.write().await.payment_settled(&stream_id, &viewer, amount);
+                
                 // Update earnings
                 let mut streams = self.active_streams.write().await;
                 if let Some(stream) = streams.get_mut(&stream_id) {
@@ -163,6 +199,57 @@ impl EventBridge {
                 }
             }
             
+            BlockchainEvent::AccessGranted { stream_id, viewer } => {
+                // Only connect viewers the streaming layer does not have yet;
+                // connecting triggers another access check
+                let mut streams = self.active_streams.write().await;
+                if let Some(stream) = streams.get_mut(&stream_id) {
+                    if !stream.viewers.contains(&viewer) {
+                        info!("🔓 Access granted on-chain: {} for {}", viewer, stream_id);
+                        stream.viewers.push(viewer.clone());
+                        self.streaming_tx.send(StreamingCommand::GrantAccess { stream_id, viewer }).await?;
+                    }
+                }
+            }
+            
+            BlockchainEvent::AccessRevoked { stream_id, viewer, reason } => {
+                let mut streams = self.active_streams.write().await;
+                if let Some(stream) = streams.get_mut(&stream_id) {
+                    if let Some(index) = stream.viewers.iter().position(|v| *v == viewer) {
+                        info!("🔒 Access revoked on-chain: {} for {} ({})", viewer, stream_id, reason);
+                        stream.viewers.remove(index);
+                        self.meter.write().await.remove_viewer(&stream_id, &viewer);
+                        self.streaming_tx.send(StreamingCommand::RevokeAccess { stream_id, viewer }).await?;
+                        return Ok(());
+                    }
+                }
+                drop(streams);
+                
+                // Viewers who negotiated over `/ws` or WHEP are only known to signaling
+                if let Some(signaling) = self.signaling.get() {
+                    if signaling.close(&stream_id, &viewer, &reason).await {
+                        info!("🔒 Access revoked on-chain: {} for {} ({})", viewer, stream_id, reason);
+                        self.meter.write().await.remove_viewer(&stream_id, &viewer);
+                    }
+                }
+            }
+            
+            BlockchainEvent::BlockImported { block_number, block_hash } => {
+                debug!("📦 Imported block #{} ({})", block_number, block_hash);
+            }
+            
+            BlockchainEvent::VoteCast { vote } => {
+                debug!("🗳️  Voted for block #{} ({})", vote.block_number, vote.block_hash);
+            }
+            
+            BlockchainEvent::BlockFinalized { block_number, block_hash } => {
+                debug!("🔒 Chain finalized up to #{} ({})", block_number, block_hash);
+            }
+            
+            BlockchainEvent::SyncStatusChanged { is_synced, best_block } => {
+                debug!("🔄 Sync status at block #{}: {}", best_block, if is_synced { "synced" } else { "syncing" });
+            }
+            
             _ => {
                 // Handle other blockchain events
             }
@@ -201,6 +288,13 @@ impl EventBridge {
                 };
                 self.blockchain_tx.send(cmd).await?;
                 
+                self.blockchain_tx.send(BlockchainCommand::RecordViewerJoin {
+                    stream_id: stream_id.clone(),
+                    viewer: viewer_id.clone(),
+                }).await?;
+                
+                self.meter.write().await.viewer_connected(&stream_id, &viewer_id, chrono::Utc::now());
+                
                 // Update local state
                 let mut streams = self.active_streams.write().await;
                 if let Some(stream) = streams.get_mut(&stream_id) {
@@ -210,6 +304,42 @@ impl EventBridge {
                 }
             }
             
+            StreamingEvent::ViewerDisconnected { stream_id, viewer_id, reason } => {
+                info!("👋 Viewer disconnected: {} from stream {} ({})", viewer_id, stream_id, reason);
+                
+                if let Some((watch_seconds, paid)) = self.meter.write().await
+                    .viewer_disconnected(&stream_id, &viewer_id, chrono::Utc::now())
+                {
+                    info!("⏱️  {} watched {} for {}s and paid {} STREAM", viewer_id, stream_id, watch_seconds, paid);
+                }
+                
+                let mut streams = self.active_streams.write().await;
+                if let Some(stream) = streams.get_mut(&stream_id) {
+                    stream.viewers.retain(|viewer| *viewer != viewer_id);
+                }
+            }
+            
+            StreamingEvent::StreamEnded { stream_id, duration_seconds } => {
+                info!("🏁 Stream ended: {} after {}s", stream_id, duration_seconds);
+                
+                // Close the broadcast session on-chain
+                let cmd = BlockchainCommand::RecordStreamEnd {
+                    stream_id: stream_id.clone(),
+                    timestamp: chrono::Utc::now(),
+                    duration_seconds,
+                };
+                self.blockchain_tx.send(cmd).await?;
+                
+                self.meter.write().await.stream_ended(&stream_id);
+                
+                // Update local state
+                let mut streams = self.active_streams.write().await;
+                if let Some(stream) = streams.get_mut(&stream_id) {
+                    stream.is_active = false;
+                    stream.viewers.clear();
+                }
+            }
+            
             StreamingEvent::QualityUpdate { stream_id, metrics } => {
                 // Convert to internal metrics format
                 let quality_metrics = StreamQualityMetrics {
@@ -245,15 +375,12 @@ impl EventBridge {
     async fn handle_sutantra_event(&self, event: SutantraEvent) -> Result<()> {
         match event {
             SutantraEvent::PaymentDue { stream_id, viewer, amount } => {
+                // Only the viewer can pay, with a signed `PurchaseAccess` or a channel update;
+                // the meter revokes them if their paid time runs out first
                 info!("⏰ Payment due: {} STREAM from {} for {}", amount, viewer, stream_id);
-                
-                // Trigger payment processing
-                let cmd = BlockchainCommand::ProcessPayment { 
-                    stream_id, 
-                    viewer, 
-                    amount 
-                };
-                self.blockchain_tx.send(cmd).await?;
+                if self.viewer_events.send(SutantraEvent::PaymentDue { stream_id, viewer, amount }).is_err() {
+                    debug!("No WebSocket client to ask for the payment");
+                }
             }
             
             _ => {
@@ -264,6 +391,62 @@ impl EventBridge {
         Ok(())
     }
     
+    /// Bill connected viewers whose paid time is running out
+    ///
+    /// Emits `PaymentDue` for each payment owed and revokes viewers whose
+    /// paid time ran out with a payment still outstanding.
+    async fn run_billing(&self) -> Result<()> {
+        let viewers = self.meter.read().await.viewers();
+        
+        let mut terms = HashMap::new();
+        for (stream_id, viewer) in viewers {
+            let Some(stream) = self.chain.stream(&stream_id).await else {
+                continue;
+            };
+            let paid_until = self.chain.access_until(&viewer, &stream_id).await;
+            let (price_per_minute, fixed_price) = match stream.pricing {
+                PricingModel::PerMinute => (stream.price_per_minute, None),
+                PricingModel::Subscription { price, .. } => (0, Some(price)),
+                // A ticket lasts until the event ends; its holders are not billed again
+                PricingModel::Ticket { ends_at, .. } if paid_until.is_some_and(|until| until >= ends_at) => (0, None),
+                PricingModel::Ticket { price, .. } => (0, Some(price)),
+                PricingModel::Free => (0, None),
+            };
+            terms.insert((stream_id, viewer), BillingTerms {
+                price_per_minute,
+                fixed_price,
+                paid_until,
+            });
+        }
+        
+        let actions = self.meter.write().await.tick(chrono::Utc::now(), &terms);
+        for action in actions {
+            match action {
+                MeterAction::PaymentDue { stream_id, viewer, amount } => {
+                    self.event_bus.send(SutantraEvent::PaymentDue { stream_id, viewer, amount }).await?;
+                }
+                MeterAction::Revoke { stream_id, viewer, reason } => {
+                    warn!("⌛ Revoking {} from {}: {}", viewer, stream_id, reason);
+                    self.revoke_viewer(stream_id, viewer).await?;
+                }
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Disconnect a viewer from a stream in the streaming layer
+    async fn revoke_viewer(&self, stream_id: String, viewer: String) -> Result<()> {
+        let mut streams = self.active_streams.write().await;
+        if let Some(stream) = streams.get_mut(&stream_id) {
+            stream.viewers.retain(|v| *v != viewer);
+        }
+        drop(streams);
+        
+        self.streaming_tx.send(StreamingCommand::RevokeAccess { stream_id, viewer }).await?;
+        Ok(())
+    }
+    
     /// Get current stream status
     pub async fn get_stream_status(&self, stream_id: &str) -> Option<StreamState> {
         let streams = self.active_streams.read().await;
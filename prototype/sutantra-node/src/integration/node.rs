@@ -1,12 +1,16 @@
 use anyhow::Result;
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc};
 use tracing::{info, error};
 
-use crate::blockchain::{BlockchainEngine, BlockchainEvent, BlockchainCommand, BlockchainConfig};
-use crate::streaming::{StreamingEngine, StreamingEvent, StreamingCommand, StreamingConfig};
-use crate::integration::EventBridge;
+use crate::blockchain::{BlockchainEngine, BlockchainEvent, BlockchainCommand, BlockchainConfig, ChainReader, GenesisSpec, MempoolConfig};
+use crate::streaming::{IceConfig, Signaling, StreamingEngine, StreamingEvent, StreamingCommand, StreamingConfig};
+use crate::integration::{EventBridge, MeteringConfig, SutantraEvent};
 use crate::mobile::LightClient;
+use crate::network::{NetworkConfig, NetworkService};
+
+/// Most viewer events buffered for a slow WebSocket client
+const VIEWER_EVENT_CAPACITY: usize = 256;
 
 /// Main Sutantra node that integrates blockchain and streaming
 pub struct SutantraNode {
@@ -14,6 +18,13 @@ pub struct SutantraNode {
     port: u16,
     is_validator: bool,
     enable_streaming: bool,
+    genesis: GenesisSpec,
+    bootnodes: Vec<String>,
+    access_grace_secs: u64,
+    ice: IceConfig,
+    chain: Arc<tokio::sync::OnceCell<ChainReader>>,
+    signaling: Arc<tokio::sync::OnceCell<Signaling>>,
+    viewer_events: broadcast::Sender<SutantraEvent>,
 }
 
 #[derive(Debug, Clone)]
@@ -53,23 +64,60 @@ impl SutantraNode {
         tx
     }
 
+    /// Read-only chain view for the web API, filled in once the blockchain engine starts
+    pub fn get_chain_reader(&self) -> Arc<tokio::sync::OnceCell<ChainReader>> {
+        Arc::clone(&self.chain)
+    }
+
+    /// WebRTC signaling for the web API, filled in once a real WebRTC engine starts
+    pub fn get_signaling(&self) -> Arc<tokio::sync::OnceCell<Signaling>> {
+        Arc::clone(&self.signaling)
+    }
+
+    /// Events for viewers' WebSocket clients, such as payments they owe
+    pub fn get_viewer_events(&self) -> broadcast::Sender<SutantraEvent> {
+        self.viewer_events.clone()
+    }
+
     /// Create a new full node
-    pub async fn new(port: u16, is_validator: bool, enable_streaming: bool) -> Result<Self> {
+    pub async fn new(
+        port: u16,
+        is_validator: bool,
+        enable_streaming: bool,
+        genesis: GenesisSpec,
+        bootnodes: Vec<String>,
+        access_grace_secs: u64,
+        ice: IceConfig,
+    ) -> Result<Self> {
         Ok(Self {
             node_type: NodeType::Full,
             port,
             is_validator,
             enable_streaming,
+            genesis,
+            bootnodes,
+            access_grace_secs,
+            ice,
+            chain: Arc::new(tokio::sync::OnceCell::new()),
+            signaling: Arc::new(tokio::sync::OnceCell::new()),
+            viewer_events: broadcast::channel(VIEWER_EVENT_CAPACITY).0,
         })
     }
     
     /// Create a new light node (mobile-optimized)
     pub async fn new_light(port: u16, bootnodes: Vec<String>) -> Result<Self> {
         Ok(Self {
-            node_type: NodeType::Light { bootnodes },
+            node_type: NodeType::Light { bootnodes: bootnodes.clone() },
             port,
             is_validator: false,
             enable_streaming: true,
+            genesis: GenesisSpec::default(),
+            bootnodes,
+            access_grace_secs: 0,
+            ice: IceConfig::default(),
+            chain: Arc::new(tokio::sync::OnceCell::new()),
+            signaling: Arc::new(tokio::sync::OnceCell::new()),
+            viewer_events: broadcast::channel(VIEWER_EVENT_CAPACITY).0,
         })
     }
     
@@ -92,12 +140,17 @@ impl SutantraNode {
         let (blockchain_event_tx, blockchain_event_rx) = mpsc::channel(1000);
         let (streaming_cmd_tx, streaming_cmd_rx) = mpsc::channel(1000);
         let (streaming_event_tx, streaming_event_rx) = mpsc::channel(1000);
+        let (network_cmd_tx, network_cmd_rx) = mpsc::channel(1000);
         
         // Configure blockchain engine
+        let data_dir = format!("./data/blockchain_{}", self.port);
         let blockchain_config = BlockchainConfig {
             port: self.port,
             is_validator: self.is_validator,
-            data_dir: format!("./data/blockchain_{}", self.port),
+            data_dir: data_dir.clone(),
+            genesis: self.genesis.clone(),
+            mempool: MempoolConfig::default(),
+            access_grace_secs: self.access_grace_secs,
         };
         
         // Configure streaming engine  
@@ -109,6 +162,7 @@ impl SutantraNode {
             relay_capacity: 1000,
             discovery_interval_seconds: 30,
             use_real_webrtc: true, // Use fixed real WebRTC implementation
+            ice: self.ice.clone(),
         };
         
         // Initialize engines
@@ -117,6 +171,22 @@ impl SutantraNode {
             blockchain_cmd_rx,
             blockchain_event_tx,
         ).await?;
+        blockchain_engine.set_network(network_cmd_tx);
+        let _ = self.chain.set(blockchain_engine.chain_reader()?);
+        
+        // Configure P2P networking
+        let network_config = NetworkConfig {
+            port: self.port,
+            bootnodes: self.bootnodes.clone(),
+            data_dir,
+            chain_id: blockchain_engine.chain_id().await,
+        };
+        let mut network_service = NetworkService::new(
+            network_config,
+            network_cmd_rx,
+            blockchain_cmd_tx.clone(),
+            blockchain_engine.chain_reader()?,
+        ).await?;
         
         let streaming_engine = if self.enable_streaming {
             let engine = StreamingEngine::new(
@@ -124,6 +194,9 @@ impl SutantraNode {
                 streaming_cmd_rx,
                 streaming_event_tx,
             ).await?;
+            if let Some(signaling) = engine.signaling() {
+                let _ = self.signaling.set(signaling);
+            }
             
             // Auto-trigger test stream if this is the first node (port 30333)
             if self.port == 30333 {
@@ -150,6 +223,10 @@ impl SutantraNode {
             streaming_cmd_tx,
             blockchain_event_rx,
             streaming_event_rx,
+            blockchain_engine.chain_reader()?,
+            Arc::clone(&self.signaling),
+            self.viewer_events.clone(),
+            MeteringConfig::default(),
         );
         
         // Start all components
@@ -159,15 +236,19 @@ impl SutantraNode {
             }
         });
         
-        let streaming_handle = if let Some(mut engine) = streaming_engine {
-            Some(tokio::spawn(async move {
+        let network_handle = tokio::spawn(async move {
+            if let Err(e) = network_service.run().await {
+                error!("Network service error: {}", e);
+            }
+        });
+        
+        let streaming_handle = streaming_engine.map(|mut engine| {
+            tokio::spawn(async move {
                 if let Err(e) = engine.run().await {
                     error!("Streaming engine error: {}", e);
                 }
-            }))
-        } else {
-            None
-        };
+            })
+        });
         
         let bridge_handle = tokio::spawn(async move {
             if let Err(e) = event_bridge.run().await {
@@ -183,6 +264,7 @@ impl SutantraNode {
         
         // Graceful shutdown
         blockchain_handle.abort();
+        network_handle.abort();
         if let Some(handle) = streaming_handle {
             handle.abort();
         }
@@ -7,8 +7,14 @@ mod streaming;
 mod integration;
 mod mobile;
 mod web_simple;
+mod whip;
+mod network;
 
 use crate::integration::SutantraNode;
+use crate::blockchain::consensus::{LeaderSelection, Validator};
+use crate::blockchain::fees::FeeParams;
+use crate::blockchain::genesis::{GenesisConsensus, GenesisSpec, ProtocolParams};
+use crate::streaming::{IceConfig, IceServerConfig};
 
 /// Sutantra: Integrated Layer 1 Streaming Blockchain
 #[derive(Parser)]
@@ -31,6 +37,10 @@ enum Commands {
         #[arg(long)]
         validator: bool,
         
+        /// Bootstrap peers as multiaddrs (e.g. /ip4/127.0.0.1/tcp/30333)
+        #[arg(long, value_delimiter = ',')]
+        bootnodes: Vec<String>,
+        
         /// Enable streaming relay
         #[arg(long, default_value = "true")]
         streaming: bool,
@@ -42,6 +52,101 @@ enum Commands {
         /// Web UI port
         #[arg(long, default_value = "8080")]
         web_port: u16,
+        
+        /// Seconds a viewer keeps access after their paid time runs out
+        #[arg(long, default_value = "10")]
+        access_grace: u64,
+        
+        /// Genesis spec file defining the chain, JSON or `.toml` (a dev chain is built from the flags below otherwise)
+        #[arg(long, conflicts_with_all = ["block_time", "validator_set", "leader_selection"])]
+        genesis: Option<String>,
+        
+        /// Slot duration in seconds
+        #[arg(long, default_value = "6")]
+        block_time: u64,
+        
+        /// Validator set as ADDRESS[:STAKE] entries (single-node dev consensus when empty)
+        #[arg(long, value_delimiter = ',')]
+        validator_set: Vec<String>,
+        
+        /// Slot leader selection: round-robin or stake-weighted
+        #[arg(long, default_value = "round-robin")]
+        leader_selection: String,
+        
+        /// ICE config file with STUN/TURN servers, NAT mapping and UDP ports
+        #[arg(long)]
+        ice_config: Option<String>,
+        
+        /// STUN server URLs, added to those in the ICE config
+        #[arg(long, value_delimiter = ',')]
+        stun_server: Vec<String>,
+        
+        /// TURN server URLs, added to those in the ICE config
+        #[arg(long, value_delimiter = ',', requires_all = ["turn_username", "turn_credential"])]
+        turn_server: Vec<String>,
+        
+        /// Username for the TURN servers given on the command line
+        #[arg(long)]
+        turn_username: Option<String>,
+        
+        /// Password for the TURN servers given on the command line
+        #[arg(long)]
+        turn_credential: Option<String>,
+        
+        /// Gather host ICE candidates only, ignoring STUN and TURN servers
+        #[arg(long)]
+        ice_host_only: bool,
+        
+        /// Public IPs the node's host addresses map to 1:1 behind NAT
+        #[arg(long, value_delimiter = ',')]
+        nat_ip: Vec<String>,
+        
+        /// UDP ports ICE may bind, counting up from the WebRTC port (port + 1000)
+        #[arg(long)]
+        webrtc_udp_ports: Option<u16>,
+    },
+    
+    /// Generate a genesis spec file
+    Genesis {
+        /// Chain identifier
+        #[arg(long, default_value = "sutantra-testnet")]
+        chain_id: String,
+        
+        /// Initial balances as ADDRESS:AMOUNT entries
+        #[arg(long, value_delimiter = ',')]
+        balance: Vec<String>,
+        
+        /// Slot duration in seconds
+        #[arg(long, default_value = "6")]
+        block_time: u64,
+        
+        /// Validator set as ADDRESS[:STAKE] entries (single-node dev consensus when empty)
+        #[arg(long, value_delimiter = ',')]
+        validator_set: Vec<String>,
+        
+        /// Slot leader selection: round-robin or stake-weighted
+        #[arg(long, default_value = "round-robin")]
+        leader_selection: String,
+        
+        /// Minimum stream price per minute in STREAM tokens
+        #[arg(long, default_value = "1")]
+        min_price: u64,
+        
+        /// Minimum transaction fee per unit of weight
+        #[arg(long, default_value = "1")]
+        min_fee_rate: u64,
+        
+        /// Block weight the fee rate steers towards
+        #[arg(long, default_value = "100000")]
+        target_block_weight: u64,
+        
+        /// Percentage of every transaction fee that is burned
+        #[arg(long, default_value = "0")]
+        fee_burn_percent: u8,
+        
+        /// Output file (printed to stdout when omitted)
+        #[arg(long)]
+        output: Option<String>,
     },
     
     /// Start a light node (mobile-optimized)
@@ -109,19 +214,60 @@ async fn main() -> Result<()> {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::Start { port, validator, streaming, web_ui, web_port } => {
+        Commands::Start {
+            port, validator, bootnodes, streaming, web_ui, web_port, access_grace,
+            genesis, block_time, validator_set, leader_selection,
+            ice_config, stun_server, turn_server, turn_username, turn_credential,
+            ice_host_only, nat_ip, webrtc_udp_ports,
+        } => {
             info!("🚀 Starting Sutantra full node on port {}", port);
             info!("📡 Validator mode: {}", validator);
+            info!("🔗 Bootstrap nodes: {:?}", bootnodes);
             info!("🎥 Streaming relay: {}", streaming);
             info!("🌐 Web UI: {} (port: {})", web_ui, web_port);
             
-            let node = SutantraNode::new(port, validator, streaming).await?;
+            let genesis = match genesis {
+                Some(path) => GenesisSpec::load(path)?,
+                None => GenesisSpec {
+                    consensus: parse_genesis_consensus(block_time, &validator_set, &leader_selection)?,
+                    ..GenesisSpec::default()
+                },
+            };
+            info!("🌱 Chain: {} (genesis {})", genesis.chain_id, genesis.hash());
+            
+            let mut ice = match ice_config {
+                Some(path) => IceConfig::load(path)?,
+                None => IceConfig::default(),
+            };
+            if !stun_server.is_empty() {
+                ice.servers.push(IceServerConfig { urls: stun_server, ..IceServerConfig::default() });
+            }
+            if !turn_server.is_empty() {
+                ice.servers.push(IceServerConfig {
+                    urls: turn_server,
+                    username: turn_username.unwrap_or_default(),
+                    credential: turn_credential.unwrap_or_default(),
+                });
+            }
+            ice.host_only |= ice_host_only;
+            ice.nat_1to1_ips.extend(nat_ip);
+            if let Some(udp_ports) = webrtc_udp_ports {
+                ice.udp_ports = udp_ports;
+            }
+            ice.validate()?;
+            
+            let node = SutantraNode::new(port, validator, streaming, genesis, bootnodes, access_grace, ice).await?;
             
             if web_ui {
                 // Start simple web server in background
                 let event_sender = node.get_event_sender();
                 let streaming_sender = node.get_streaming_sender();
-                let web_server = crate::web_simple::SimpleWebServer::new(web_port, event_sender, streaming_sender);
+                let chain = node.get_chain_reader();
+                let signaling = node.get_signaling();
+                let viewer_events = node.get_viewer_events();
+                let web_server = crate::web_simple::SimpleWebServer::new(
+                    web_port, event_sender, streaming_sender, chain, signaling, viewer_events,
+                );
                 
                 tokio::spawn(async move {
                     if let Err(e) = web_server.start().await {
@@ -144,7 +290,12 @@ async fn main() -> Result<()> {
                 // Start simple web server in background
                 let event_sender = node.get_event_sender();
                 let streaming_sender = node.get_streaming_sender();
-                let web_server = crate::web_simple::SimpleWebServer::new(web_port, event_sender, streaming_sender);
+                let chain = node.get_chain_reader();
+                let signaling = node.get_signaling();
+                let viewer_events = node.get_viewer_events();
+                let web_server = crate::web_simple::SimpleWebServer::new(
+                    web_port, event_sender, streaming_sender, chain, signaling, viewer_events,
+                );
                 
                 tokio::spawn(async move {
                     if let Err(e) = web_server.start().await {
@@ -156,6 +307,45 @@ async fn main() -> Result<()> {
             node.run().await?;
         }
         
+        Commands::Genesis {
+            chain_id, balance, block_time, validator_set, leader_selection,
+            min_price, min_fee_rate, target_block_weight, fee_burn_percent, output,
+        } => {
+            let balances = balance.iter()
+                .map(|entry| {
+                    let (address, amount) = entry.split_once(':')
+                        .ok_or_else(|| anyhow::anyhow!("Expected ADDRESS:AMOUNT, got {}", entry))?;
+                    Ok((address.to_string(), amount.parse()?))
+                })
+                .collect::<Result<_>>()?;
+            
+            let spec = GenesisSpec {
+                chain_id,
+                balances,
+                consensus: parse_genesis_consensus(block_time, &validator_set, &leader_selection)?,
+                params: ProtocolParams {
+                    min_price_per_minute: min_price,
+                    fees: FeeParams {
+                        min_fee_per_weight: min_fee_rate,
+                        target_block_weight,
+                        burn_percent: fee_burn_percent,
+                    },
+                    ..ProtocolParams::default()
+                },
+                ..GenesisSpec::default()
+            };
+            spec.validate()?;
+            
+            match output {
+                Some(path) => {
+                    spec.save(&path)?;
+                    println!("Wrote genesis spec to {}", path);
+                }
+                None => println!("{}", spec.to_json()?),
+            }
+            eprintln!("Genesis hash: {}", spec.hash());
+        }
+        
         Commands::Stream { action } => {
             handle_stream_command(action).await?;
         }
@@ -168,6 +358,31 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Build genesis consensus parameters from CLI flags
+fn parse_genesis_consensus(
+    block_time_secs: u64,
+    validator_set: &[String],
+    leader_selection: &str,
+) -> Result<GenesisConsensus> {
+    let validators = validator_set.iter()
+        .map(|entry| {
+            let (address, stake) = match entry.split_once(':') {
+                Some((address, stake)) => (address, stake.parse()?),
+                None => (entry.as_str(), 1),
+            };
+            Ok(Validator { address: address.to_string(), stake })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    
+    let leader_selection = match leader_selection {
+        "round-robin" => LeaderSelection::RoundRobin,
+        "stake-weighted" => LeaderSelection::StakeWeighted,
+        other => anyhow::bail!("Unknown leader selection: {}", other),
+    };
+    
+    Ok(GenesisConsensus { block_time_secs, validators, leader_selection })
+}
+
 async fn handle_stream_command(action: StreamCommands) -> Result<()> {
     match action {
         StreamCommands::Create { title, description: _, price } => {
@@ -0,0 +1,422 @@
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use libp2p::{
+    gossipsub, identify, identity, kad, noise, ping, request_response, tcp, yamux,
+    swarm::{NetworkBehaviour, SwarmEvent},
+    Multiaddr, PeerId, StreamProtocol, Swarm,
+};
+use std::collections::{HashSet, VecDeque};
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{info, debug, warn, error};
+
+use crate::blockchain::{crypto, Block, BlockchainCommand, ChainReader, Transaction, Vote};
+
+pub mod sync;
+
+use sync::{SyncManager, SyncRequest, SyncResponse};
+
+const NETWORK_KEY_FILE: &str = "network.key";
+const PROTOCOL_VERSION: &str = "/sutantra/1.0.0";
+/// Number of received gossip payloads remembered to avoid re-publishing them
+const SEEN_CACHE_SIZE: usize = 4096;
+
+/// Configuration for the P2P networking service
+#[derive(Debug, Clone)]
+pub struct NetworkConfig {
+    pub port: u16,
+    pub bootnodes: Vec<String>,
+    pub data_dir: String,
+    pub chain_id: String,
+}
+
+/// Commands that can be sent to the networking layer
+#[derive(Debug, Clone)]
+pub enum NetworkCommand {
+    /// Gossip a transaction accepted into the local pool
+    Transaction {
+        transaction: Transaction,
+    },
+
+    /// Gossip a block produced or imported locally
+    Block {
+        block: Block,
+    },
+
+    /// Gossip a finality vote
+    Vote {
+        vote: Vote,
+    },
+}
+
+/// Combined libp2p behaviour for Sutantra nodes
+#[derive(NetworkBehaviour)]
+struct SutantraBehaviour {
+    gossipsub: gossipsub::Behaviour,
+    kademlia: kad::Behaviour<kad::store::MemoryStore>,
+    identify: identify::Behaviour,
+    ping: ping::Behaviour,
+    sync: request_response::cbor::Behaviour<SyncRequest, SyncResponse>,
+}
+
+/// Gossip topics, namespaced by chain id so separate chains never mix
+struct Topics {
+    transactions: gossipsub::IdentTopic,
+    blocks: gossipsub::IdentTopic,
+    votes: gossipsub::IdentTopic,
+}
+
+impl Topics {
+    fn new(chain_id: &str) -> Self {
+        Self {
+            transactions: gossipsub::IdentTopic::new(format!("/sutantra/{}/transactions/1", chain_id)),
+            blocks: gossipsub::IdentTopic::new(format!("/sutantra/{}/blocks/1", chain_id)),
+            votes: gossipsub::IdentTopic::new(format!("/sutantra/{}/votes/1", chain_id)),
+        }
+    }
+}
+
+/// P2P networking service that gossips transactions, blocks and votes
+pub struct NetworkService {
+    config: NetworkConfig,
+    swarm: Swarm<SutantraBehaviour>,
+    topics: Topics,
+    seen: SeenCache,
+    chain: ChainReader,
+    sync: SyncManager,
+
+    // Communication channels
+    command_rx: mpsc::Receiver<NetworkCommand>,
+    blockchain_tx: mpsc::Sender<BlockchainCommand>,
+}
+
+impl NetworkService {
+    pub async fn new(
+        config: NetworkConfig,
+        command_rx: mpsc::Receiver<NetworkCommand>,
+        blockchain_tx: mpsc::Sender<BlockchainCommand>,
+        chain: ChainReader,
+    ) -> Result<Self> {
+        info!("🌐 Initializing P2P Network");
+
+        let keypair = load_or_generate_network_key(&config.data_dir)?;
+        let local_peer_id = keypair.public().to_peer_id();
+        info!("🆔 Local peer id: {}", local_peer_id);
+
+        let swarm = libp2p::SwarmBuilder::with_existing_identity(keypair)
+            .with_tokio()
+            .with_tcp(tcp::Config::default(), noise::Config::new, yamux::Config::default)?
+            .with_behaviour(|key| {
+                // Content-addressed message ids let gossipsub drop duplicates
+                let gossipsub_config = gossipsub::ConfigBuilder::default()
+                    .heartbeat_interval(Duration::from_secs(1))
+                    .validation_mode(gossipsub::ValidationMode::Strict)
+                    .max_transmit_size(4 * 1024 * 1024)
+                    .message_id_fn(|message: &gossipsub::Message| {
+                        gossipsub::MessageId::from(blake3::hash(&message.data).to_hex().to_string())
+                    })
+                    .build()?;
+
+                let gossipsub = gossipsub::Behaviour::new(
+                    gossipsub::MessageAuthenticity::Signed(key.clone()),
+                    gossipsub_config,
+                )?;
+
+                let peer_id = key.public().to_peer_id();
+                let mut kademlia = kad::Behaviour::new(peer_id, kad::store::MemoryStore::new(peer_id));
+                kademlia.set_mode(Some(kad::Mode::Server));
+
+                let identify = identify::Behaviour::new(
+                    identify::Config::new(PROTOCOL_VERSION.to_string(), key.public())
+                );
+
+                let sync = request_response::cbor::Behaviour::new(
+                    [(StreamProtocol::new(sync::SYNC_PROTOCOL), request_response::ProtocolSupport::Full)],
+                    request_response::Config::default(),
+                );
+
+                Ok(SutantraBehaviour {
+                    gossipsub,
+                    kademlia,
+                    identify,
+                    ping: ping::Behaviour::default(),
+                    sync,
+                })
+            })?
+            .with_swarm_config(|cfg| cfg.with_idle_connection_timeout(Duration::from_secs(60)))
+            .build();
+
+        let topics = Topics::new(&config.chain_id);
+        let sync = SyncManager::new(chain.clone(), !config.bootnodes.is_empty());
+
+        Ok(Self {
+            config,
+            swarm,
+            topics,
+            seen: SeenCache::default(),
+            chain,
+            sync,
+            command_rx,
+            blockchain_tx,
+        })
+    }
+
+    pub async fn run(&mut self) -> Result<()> {
+        info!("🚀 Starting P2P Network on port {}", self.config.port);
+
+        let listen_addr: Multiaddr = format!("/ip4/0.0.0.0/tcp/{}", self.config.port).parse()?;
+        self.swarm.listen_on(listen_addr)?;
+
+        for topic in [&self.topics.transactions, &self.topics.blocks, &self.topics.votes] {
+            self.swarm.behaviour_mut().gossipsub.subscribe(topic)?;
+        }
+
+        self.dial_bootnodes();
+
+        let mut sync_interval = tokio::time::interval(sync::SYNC_INTERVAL);
+
+        loop {
+            tokio::select! {
+                Some(command) = self.command_rx.recv() => {
+                    self.handle_command(command);
+                }
+
+                _ = sync_interval.tick() => {
+                    let peers: Vec<PeerId> = self.swarm.connected_peers().copied().collect();
+                    for (peer, request) in self.sync.tick(peers.into_iter()) {
+                        self.swarm.behaviour_mut().sync.send_request(&peer, request);
+                    }
+                    if let Err(e) = self.report_sync_target().await {
+                        error!("Failed to report sync target: {}", e);
+                    }
+                }
+
+                event = self.swarm.select_next_some() => {
+                    if let Err(e) = self.handle_swarm_event(event).await {
+                        error!("Error handling network event: {}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    fn dial_bootnodes(&mut self) {
+        for bootnode in &self.config.bootnodes {
+            let addr: Multiaddr = match bootnode.parse() {
+                Ok(addr) => addr,
+                Err(e) => {
+                    warn!("Invalid bootnode address {}: {}", bootnode, e);
+                    continue;
+                }
+            };
+
+            // Bootnodes with a /p2p/<peer id> suffix also seed the DHT
+            if let Some(libp2p::multiaddr::Protocol::P2p(peer_id)) = addr.iter().last() {
+                self.swarm.behaviour_mut().kademlia.add_address(&peer_id, addr.clone());
+            }
+
+            info!("🔗 Dialing bootnode {}", addr);
+            if let Err(e) = self.swarm.dial(addr) {
+                warn!("Failed to dial bootnode {}: {}", bootnode, e);
+            }
+        }
+    }
+
+    fn handle_command(&mut self, command: NetworkCommand) {
+        let (topic, data) = match &command {
+            NetworkCommand::Transaction { transaction } => {
+                (self.topics.transactions.clone(), bincode::serialize(transaction))
+            }
+            NetworkCommand::Block { block } => {
+                (self.topics.blocks.clone(), bincode::serialize(block))
+            }
+            NetworkCommand::Vote { vote } => {
+                (self.topics.votes.clone(), bincode::serialize(vote))
+            }
+        };
+
+        let data = match data {
+            Ok(data) => data,
+            Err(e) => {
+                error!("Failed to encode gossip message: {}", e);
+                return;
+            }
+        };
+
+        // The mesh already forwards what we received from peers
+        if self.seen.contains(&data) {
+            return;
+        }
+
+        match self.swarm.behaviour_mut().gossipsub.publish(topic.clone(), data) {
+            Ok(_) => debug!("📣 Published to {}", topic),
+            // Messages we received from peers are already known to the mesh
+            Err(gossipsub::PublishError::Duplicate) => {}
+            Err(gossipsub::PublishError::InsufficientPeers) => {
+                debug!("No peers to gossip {} to", topic);
+            }
+            Err(e) => warn!("Failed to publish to {}: {}", topic, e),
+        }
+    }
+
+    async fn handle_swarm_event(&mut self, event: SwarmEvent<SutantraBehaviourEvent>) -> Result<()> {
+        match event {
+            SwarmEvent::NewListenAddr { address, .. } => {
+                info!("👂 Listening on {}/p2p/{}", address, self.swarm.local_peer_id());
+            }
+
+            SwarmEvent::ConnectionEstablished { peer_id, endpoint, num_established, .. } => {
+                info!("🤝 Connected to peer {} ({})", peer_id, endpoint.get_remote_address());
+                if num_established.get() == 1 {
+                    self.swarm.behaviour_mut().sync.send_request(&peer_id, SyncRequest::Status);
+                }
+            }
+
+            SwarmEvent::ConnectionClosed { peer_id, num_established, .. } => {
+                debug!("👋 Disconnected from peer {}", peer_id);
+                if num_established == 0 {
+                    self.sync.peer_disconnected(&peer_id);
+                }
+            }
+
+            SwarmEvent::Behaviour(SutantraBehaviourEvent::Identify(identify::Event::Received { peer_id, info })) => {
+                // Make peers discoverable through the DHT
+                for addr in info.listen_addrs {
+                    self.swarm.behaviour_mut().kademlia.add_address(&peer_id, addr);
+                }
+            }
+
+            SwarmEvent::Behaviour(SutantraBehaviourEvent::Gossipsub(gossipsub::Event::Message {
+                propagation_source,
+                message,
+                ..
+            })) => {
+                self.handle_gossip(propagation_source, message).await?;
+            }
+
+            SwarmEvent::Behaviour(SutantraBehaviourEvent::Sync(event)) => {
+                self.handle_sync_event(event).await?;
+            }
+
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    async fn handle_sync_event(&mut self, event: request_response::Event<SyncRequest, SyncResponse>) -> Result<()> {
+        match event {
+            request_response::Event::Message { peer, message } => match message {
+                request_response::Message::Request { request, channel, .. } => {
+                    debug!("📤 Serving {:?} to {}", request, peer);
+                    let response = sync::serve(&self.chain, request).await;
+                    if self.swarm.behaviour_mut().sync.send_response(channel, response).is_err() {
+                        debug!("Peer {} went away before the sync response was sent", peer);
+                    }
+                }
+
+                request_response::Message::Response { response, .. } => {
+                    let (blocks, next) = self.sync.on_response(peer, response).await?;
+                    for block in blocks {
+                        self.blockchain_tx.send(BlockchainCommand::ImportBlock { block }).await?;
+                    }
+                    if let Some((peer, request)) = next {
+                        self.swarm.behaviour_mut().sync.send_request(&peer, request);
+                    }
+                    self.report_sync_target().await?;
+                }
+            },
+
+            request_response::Event::OutboundFailure { peer, error, .. } => {
+                warn!("Sync request to {} failed: {}", peer, error);
+                self.sync.cancel(&peer);
+            }
+
+            request_response::Event::InboundFailure { peer, error, .. } => {
+                debug!("Sync request from {} failed: {}", peer, error);
+            }
+
+            request_response::Event::ResponseSent { .. } => {}
+        }
+
+        Ok(())
+    }
+
+    /// Tell the blockchain engine how far the best peer is ahead
+    async fn report_sync_target(&mut self) -> Result<()> {
+        if let Some(best_block) = self.sync.take_target() {
+            self.blockchain_tx.send(BlockchainCommand::UpdateSyncTarget { best_block }).await?;
+        }
+        Ok(())
+    }
+
+    async fn handle_gossip(&mut self, source: PeerId, message: gossipsub::Message) -> Result<()> {
+        self.seen.insert(&message.data);
+
+        let command = if message.topic == self.topics.transactions.hash() {
+            let transaction = bincode::deserialize(&message.data)
+                .with_context(|| format!("Malformed transaction from {}", source))?;
+            BlockchainCommand::SubmitTransaction { transaction }
+        } else if message.topic == self.topics.blocks.hash() {
+            let block: Block = bincode::deserialize(&message.data)
+                .with_context(|| format!("Malformed block from {}", source))?;
+            debug!("📦 Received block #{} from {}", block.number, source);
+            BlockchainCommand::ImportBlock { block }
+        } else if message.topic == self.topics.votes.hash() {
+            let vote = bincode::deserialize(&message.data)
+                .with_context(|| format!("Malformed vote from {}", source))?;
+            BlockchainCommand::SubmitVote { vote }
+        } else {
+            return Ok(());
+        };
+
+        self.blockchain_tx.send(command).await?;
+        Ok(())
+    }
+}
+
+/// Bounded set of payload hashes received over gossip
+#[derive(Default)]
+struct SeenCache {
+    hashes: HashSet<blake3::Hash>,
+    order: VecDeque<blake3::Hash>,
+}
+
+impl SeenCache {
+    fn insert(&mut self, data: &[u8]) {
+        let hash = blake3::hash(data);
+        if !self.hashes.insert(hash) {
+            return;
+        }
+        self.order.push_back(hash);
+        if self.order.len() > SEEN_CACHE_SIZE {
+            if let Some(oldest) = self.order.pop_front() {
+                self.hashes.remove(&oldest);
+            }
+        }
+    }
+
+    fn contains(&self, data: &[u8]) -> bool {
+        self.hashes.contains(&blake3::hash(data))
+    }
+}
+
+/// Load the libp2p identity from `data_dir`, generating one on first start
+fn load_or_generate_network_key(data_dir: impl AsRef<Path>) -> Result<identity::Keypair> {
+    let path = data_dir.as_ref().join(NETWORK_KEY_FILE);
+
+    let secret = if path.exists() {
+        std::fs::read(&path)
+            .with_context(|| format!("Failed to read network key {}", path.display()))?
+    } else {
+        let secret: [u8; 32] = rand::random();
+        std::fs::create_dir_all(data_dir.as_ref())?;
+        crypto::write_secret(&path, &secret)
+            .with_context(|| format!("Failed to write network key {}", path.display()))?;
+        secret.to_vec()
+    };
+
+    identity::Keypair::ed25519_from_bytes(secret)
+        .map_err(|e| anyhow::anyhow!("Invalid network key {}: {}", path.display(), e))
+}
@@ -0,0 +1,249 @@
+// Block sync for nodes that join or fall behind the chain tip
+//
+// Peers exchange their chain status when they connect and periodically after
+// that. When a peer is ahead, headers are fetched by range starting after the
+// local finalized block and checked to link up with it, so a peer on a
+// competing branch is synced too; the matching blocks are then fetched and
+// handed to the blockchain engine, which fully validates them and lets fork
+// choice pick the branch. Downloads run one batch at a time from one peer.
+
+use anyhow::{anyhow, Result};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::blockchain::block::SignedHeader;
+use crate::blockchain::{Block, ChainReader, ChainStatus};
+
+/// Request/response protocol name
+pub const SYNC_PROTOCOL: &str = "/sutantra/sync/1";
+
+/// How often connected peers are asked for their chain status
+pub const SYNC_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Blocks requested per batch
+const SYNC_BATCH_SIZE: u64 = 64;
+
+/// Blocks to import and the next request to send after a response
+type SyncStep = (Vec<Block>, Option<(PeerId, SyncRequest)>);
+
+/// Requests a syncing node sends to its peers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SyncRequest {
+    Status,
+    Headers { from: u64, count: u64 },
+    Blocks { from: u64, count: u64 },
+}
+
+/// Answers to [`SyncRequest`]s
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SyncResponse {
+    Status(ChainStatus),
+    Headers(Vec<SignedHeader>),
+    Blocks(Vec<Block>),
+    Error(String),
+}
+
+/// Answer a peer's sync request from the local chain
+pub async fn serve(reader: &ChainReader, request: SyncRequest) -> SyncResponse {
+    let result = match request {
+        SyncRequest::Status => reader.status().await.map(SyncResponse::Status),
+        SyncRequest::Headers { from, count } => reader.blocks(from, count).await
+            .map(|blocks| SyncResponse::Headers(blocks.iter().map(Block::header).collect())),
+        SyncRequest::Blocks { from, count } => reader.blocks(from, count).await
+            .map(SyncResponse::Blocks),
+    };
+
+    result.unwrap_or_else(|e| SyncResponse::Error(e.to_string()))
+}
+
+/// A batch being downloaded from one peer
+struct Download {
+    peer: PeerId,
+    from: u64,
+    parent_hash: String,
+    headers: Vec<SignedHeader>,
+}
+
+/// Tracks peer chain tips and drives block downloads
+pub struct SyncManager {
+    local: ChainReader,
+    peers: HashMap<PeerId, ChainStatus>,
+    download: Option<Download>,
+    reported_target: Option<u64>,
+    /// Status rounds to wait for bootnodes before trusting the local tip
+    grace_ticks: u32,
+}
+
+impl SyncManager {
+    pub fn new(local: ChainReader, has_bootnodes: bool) -> Self {
+        Self {
+            local,
+            peers: HashMap::new(),
+            download: None,
+            reported_target: None,
+            grace_ticks: if has_bootnodes { 2 } else { 0 },
+        }
+    }
+
+    /// Peers to poll for their status
+    pub fn tick(&mut self, connected: impl Iterator<Item = PeerId>) -> Vec<(PeerId, SyncRequest)> {
+        self.grace_ticks = self.grace_ticks.saturating_sub(1);
+        connected.map(|peer| (peer, SyncRequest::Status)).collect()
+    }
+
+    pub fn peer_disconnected(&mut self, peer: &PeerId) {
+        self.peers.remove(peer);
+        self.cancel(peer);
+    }
+
+    /// Abandon the download from `peer` after a failed request
+    pub fn cancel(&mut self, peer: &PeerId) {
+        if self.download.as_ref().is_some_and(|download| download.peer == *peer) {
+            debug!("Abandoning sync batch from {}", peer);
+            self.download = None;
+        }
+    }
+
+    /// Highest block reported by any peer, when it changed since last asked
+    pub fn take_target(&mut self) -> Option<u64> {
+        if self.grace_ticks > 0 && self.peers.is_empty() {
+            return None;
+        }
+
+        let target = self.peers.values().map(|status| status.best_block).max().unwrap_or(0);
+        if self.reported_target == Some(target) {
+            return None;
+        }
+        self.reported_target = Some(target);
+        Some(target)
+    }
+
+    /// Handle a response, returning blocks to import and the next request to send
+    pub async fn on_response(
+        &mut self,
+        peer: PeerId,
+        response: SyncResponse,
+    ) -> Result<SyncStep> {
+        match response {
+            SyncResponse::Status(status) => {
+                let local = self.local.status().await?;
+                if status.genesis_hash != local.genesis_hash || status.chain_id != local.chain_id {
+                    warn!("Peer {} is on a different chain ({} / {})", peer, status.chain_id, status.genesis_hash);
+                    return Ok((Vec::new(), None));
+                }
+
+                self.peers.insert(peer, status);
+                Ok((Vec::new(), self.next_request(&local)))
+            }
+
+            SyncResponse::Headers(headers) => {
+                let request = self.on_headers(peer, headers);
+                if request.is_err() {
+                    self.cancel(&peer);
+                }
+                Ok((Vec::new(), Some(request?)))
+            }
+
+            SyncResponse::Blocks(blocks) => {
+                let result = self.on_blocks(peer, blocks);
+                if result.is_err() {
+                    self.cancel(&peer);
+                }
+                result
+            }
+
+            SyncResponse::Error(e) => {
+                self.cancel(&peer);
+                Err(anyhow!("Peer {} failed sync request: {}", peer, e))
+            }
+        }
+    }
+
+    /// Start a download from the best peer if one is ahead of us
+    fn next_request(&mut self, local: &ChainStatus) -> Option<(PeerId, SyncRequest)> {
+        if self.download.is_some() {
+            return None;
+        }
+
+        let (peer, status) = self.peers.iter()
+            .filter(|(_, status)| status.best_block > local.best_block)
+            .max_by_key(|(_, status)| status.best_block)?;
+
+        // Our unfinalized blocks may be on another branch than the peer's
+        let from = local.finalized_block + 1;
+        info!("🔄 Peer {} is at block #{}, syncing from #{}", peer, status.best_block, from);
+
+        let count = (status.best_block - local.finalized_block).min(SYNC_BATCH_SIZE);
+        self.download = Some(Download {
+            peer: *peer,
+            from,
+            parent_hash: local.finalized_hash.clone(),
+            headers: Vec::new(),
+        });
+        Some((*peer, SyncRequest::Headers { from, count }))
+    }
+
+    /// Check that headers link up with the download position, then fetch their blocks
+    fn on_headers(&mut self, peer: PeerId, headers: Vec<SignedHeader>) -> Result<(PeerId, SyncRequest)> {
+        let download = self.download.as_mut()
+            .filter(|download| download.peer == peer)
+            .ok_or_else(|| anyhow!("Unexpected headers from {}", peer))?;
+
+        if headers.is_empty() {
+            return Err(anyhow!("Peer {} returned no headers from #{}", peer, download.from));
+        }
+
+        let mut parent_hash = &download.parent_hash;
+        for (offset, header) in headers.iter().enumerate() {
+            if header.number != download.from + offset as u64 || header.parent_hash != *parent_hash {
+                return Err(anyhow!("Header #{} from {} does not link to its parent", header.number, peer));
+            }
+            if header.compute_hash() != header.hash {
+                return Err(anyhow!("Header #{} from {} has an invalid hash", header.number, peer));
+            }
+            parent_hash = &header.hash;
+        }
+
+        let request = SyncRequest::Blocks { from: download.from, count: headers.len() as u64 };
+        download.headers = headers;
+        Ok((peer, request))
+    }
+
+    /// Match blocks against the fetched headers and advance the download
+    fn on_blocks(&mut self, peer: PeerId, blocks: Vec<Block>) -> Result<SyncStep> {
+        let download = self.download.as_mut()
+            .filter(|download| download.peer == peer && !download.headers.is_empty())
+            .ok_or_else(|| anyhow!("Unexpected blocks from {}", peer))?;
+
+        if blocks.len() != download.headers.len() {
+            return Err(anyhow!("Peer {} returned {} blocks for {} headers", peer, blocks.len(), download.headers.len()));
+        }
+        for (block, header) in blocks.iter().zip(&download.headers) {
+            if block.hash != header.hash || block.compute_hash() != block.hash {
+                return Err(anyhow!("Block #{} from {} does not match its header", block.number, peer));
+            }
+        }
+
+        let last = download.headers.last().expect("headers are not empty");
+        debug!("📥 Synced blocks #{}..=#{} from {}", download.from, last.number, peer);
+
+        download.from = last.number + 1;
+        download.parent_hash = last.hash.clone();
+        download.headers.clear();
+
+        // Keep going while the peer is still ahead
+        let peer_best = self.peers.get(&peer).map_or(0, |status| status.best_block);
+        let next = if peer_best >= download.from {
+            let count = (peer_best - download.from + 1).min(SYNC_BATCH_SIZE);
+            Some((peer, SyncRequest::Headers { from: download.from, count }))
+        } else {
+            self.download = None;
+            None
+        };
+
+        Ok((blocks, next))
+    }
+}
@@ -57,6 +57,13 @@ impl WebRTCEngine {
             WebRTCEngine::Real(engine) => engine.get_stream_metrics(stream_id).await,
         }
     }
+    /// Signaling handle for viewer peer connections; the mock engine has none
+    pub fn signaling(&self) -> Option<super::Signaling> {
+        match self {
+            WebRTCEngine::Mock(_) => None,
+            WebRTCEngine::Real(engine) => Some(engine.signaling()),
+        }
+    }
 }
 
 impl StreamingEngine {
@@ -72,7 +79,7 @@ impl StreamingEngine {
         let webrtc_engine = if config.use_real_webrtc {
             info!("🎥 SELECTED: REAL WebRTC Engine (Production Mode)");
             info!("📡 Initializing webrtc-rs crate v0.7.3");
-            let mut engine = RealWebRTCEngine::new(config.webrtc_port).await?;
+            let mut engine = RealWebRTCEngine::new(config.webrtc_port, &config.ice, event_tx.clone()).await?;
             engine.start().await?;
             WebRTCEngine::Real(engine)
         } else {
@@ -221,4 +228,8 @@ impl StreamingEngine {
     pub async fn get_stream_metrics(&self, stream_id: &str) -> Option<super::StreamMetrics> {
         self.webrtc_engine.get_stream_metrics(stream_id).await
     }
+    /// Signaling handle the web server negotiates viewer connections through
+    pub fn signaling(&self) -> Option<super::Signaling> {
+        self.webrtc_engine.signaling()
+    }
 }
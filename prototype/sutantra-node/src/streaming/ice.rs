@@ -0,0 +1,219 @@
+// ICE configuration
+//
+// Peers reach the node through the ICE candidates it gathers. By default the
+// node gathers only its host addresses and uses no outside servers. An
+// operator can list STUN servers, which tell the node its address behind NAT,
+// and TURN servers, which relay media when no direct path works. These come
+// from a JSON file, from CLI flags, or from both. A node behind a 1:1 NAT can
+// instead be told its public IPs, which replace its host addresses in the
+// candidates it offers. Host-only mode ignores every server, for LAN and test
+// use. ICE binds to UDP ports that start at the streaming engine's WebRTC
+// port, so a firewall only needs to open that range.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tracing::warn;
+
+use webrtc::api::setting_engine::SettingEngine;
+use webrtc::ice::udp_network::{EphemeralUDP, UDPNetwork};
+use webrtc::ice_transport::ice_candidate_type::RTCIceCandidateType;
+use webrtc::ice_transport::ice_credential_type::RTCIceCredentialType;
+use webrtc::ice_transport::ice_server::RTCIceServer;
+
+/// UDP ports ICE may bind when none are configured
+const DEFAULT_UDP_PORTS: u16 = 100;
+
+/// A STUN or TURN server offered to the node's peer connections
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IceServerConfig {
+    /// `stun:`, `stuns:`, `turn:` or `turns:` URLs of the server
+    pub urls: Vec<String>,
+    /// TURN username
+    pub username: String,
+    /// TURN password
+    pub credential: String,
+}
+
+/// How the node gathers ICE candidates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IceConfig {
+    pub servers: Vec<IceServerConfig>,
+    /// Gather host candidates only and ignore `servers`
+    pub host_only: bool,
+    /// Public IPs that the node's host addresses map to 1:1
+    pub nat_1to1_ips: Vec<String>,
+    /// UDP ports ICE may bind, counting up from the WebRTC port
+    pub udp_ports: u16,
+}
+
+impl Default for IceConfig {
+    fn default() -> Self {
+        Self {
+            servers: Vec::new(),
+            host_only: false,
+            nat_1to1_ips: Vec::new(),
+            udp_ports: DEFAULT_UDP_PORTS,
+        }
+    }
+}
+
+impl IceConfig {
+    /// Read a JSON ICE config file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read ICE config {}", path.display()))?;
+        let config: Self = serde_json::from_str(&json)
+            .with_context(|| format!("Invalid ICE config {}", path.display()))?;
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check server URLs, TURN credentials and NAT IPs
+    pub fn validate(&self) -> Result<()> {
+        for server in &self.servers {
+            if server.urls.is_empty() {
+                return Err(anyhow!("ICE server without URLs"));
+            }
+            for url in &server.urls {
+                let scheme = url.split(':').next().unwrap_or_default();
+                match scheme {
+                    "stun" | "stuns" => {}
+                    "turn" | "turns" if server.username.is_empty() || server.credential.is_empty() => {
+                        return Err(anyhow!("TURN server {} needs a username and credential", url));
+                    }
+                    "turn" | "turns" => {}
+                    _ => return Err(anyhow!("ICE server URL {} is not a STUN or TURN URL", url)),
+                }
+            }
+        }
+
+        for ip in &self.nat_1to1_ips {
+            ip.parse::<std::net::IpAddr>()
+                .map_err(|_| anyhow!("NAT 1:1 address {} is not an IP address", ip))?;
+        }
+        Ok(())
+    }
+
+    /// Servers given to each peer connection; none in host-only mode
+    pub fn ice_servers(&self) -> Vec<RTCIceServer> {
+        if self.host_only {
+            return Vec::new();
+        }
+
+        self.servers.iter()
+            .map(|server| RTCIceServer {
+                urls: server.urls.clone(),
+                username: server.username.clone(),
+                credential: server.credential.clone(),
+                credential_type: if server.credential.is_empty() {
+                    RTCIceCredentialType::Unspecified
+                } else {
+                    RTCIceCredentialType::Password
+                },
+            })
+            .collect()
+    }
+
+    /// Inclusive UDP port range starting at `webrtc_port`
+    pub fn port_range(&self, webrtc_port: u16) -> Result<(u16, u16)> {
+        if self.udp_ports == 0 {
+            return Err(anyhow!("ICE needs at least one UDP port"));
+        }
+        let last = webrtc_port.checked_add(self.udp_ports - 1)
+            .ok_or_else(|| anyhow!("{} UDP ports from {} run past port 65535", self.udp_ports, webrtc_port))?;
+        Ok((webrtc_port, last))
+    }
+
+    /// Setting engine that binds ICE to the port range and applies the NAT mapping
+    pub fn setting_engine(&self, webrtc_port: u16) -> Result<SettingEngine> {
+        let (first, last) = self.port_range(webrtc_port)?;
+        let mut setting_engine = SettingEngine::default();
+        setting_engine.set_udp_network(UDPNetwork::Ephemeral(EphemeralUDP::new(first, last)?));
+
+        if !self.nat_1to1_ips.is_empty() {
+            setting_engine.set_nat_1to1_ips(self.nat_1to1_ips.clone(), RTCIceCandidateType::Host);
+        }
+        if self.host_only && !self.servers.is_empty() {
+            warn!("🧊 Host-only ICE: ignoring {} configured STUN/TURN servers", self.servers.len());
+        }
+        Ok(setting_engine)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(url: &str, username: &str, credential: &str) -> IceServerConfig {
+        IceServerConfig {
+            urls: vec![url.to_string()],
+            username: username.to_string(),
+            credential: credential.to_string(),
+        }
+    }
+
+    #[test]
+    fn port_range_counts_up_from_the_webrtc_port() {
+        let config = IceConfig { udp_ports: 10, ..IceConfig::default() };
+        assert_eq!(config.port_range(31333).unwrap(), (31333, 31342));
+        assert_eq!(config.port_range(65526).unwrap(), (65526, 65535));
+        assert!(config.port_range(65527).is_err());
+
+        let single = IceConfig { udp_ports: 1, ..IceConfig::default() };
+        assert_eq!(single.port_range(65535).unwrap(), (65535, 65535));
+
+        let none = IceConfig { udp_ports: 0, ..IceConfig::default() };
+        assert!(none.port_range(31333).is_err());
+    }
+
+    #[test]
+    fn turn_servers_need_credentials() {
+        let stun = IceConfig { servers: vec![server("stun:stun.example.org:3478", "", "")], ..IceConfig::default() };
+        assert!(stun.validate().is_ok());
+
+        for (username, credential) in [("", ""), ("user", ""), ("", "secret")] {
+            let turn = IceConfig {
+                servers: vec![server("turn:turn.example.org:3478", username, credential)],
+                ..IceConfig::default()
+            };
+            assert!(turn.validate().is_err());
+        }
+
+        let turn = IceConfig {
+            servers: vec![server("turns:turn.example.org:5349", "user", "secret")],
+            ..IceConfig::default()
+        };
+        assert!(turn.validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_bad_urls_and_nat_ips() {
+        let no_urls = IceConfig { servers: vec![IceServerConfig::default()], ..IceConfig::default() };
+        assert!(no_urls.validate().is_err());
+
+        let http = IceConfig { servers: vec![server("https://stun.example.org", "", "")], ..IceConfig::default() };
+        assert!(http.validate().is_err());
+
+        let nat = IceConfig { nat_1to1_ips: vec!["203.0.113.7".to_string(), "2001:db8::1".to_string()], ..IceConfig::default() };
+        assert!(nat.validate().is_ok());
+
+        let hostname = IceConfig { nat_1to1_ips: vec!["node.example.org".to_string()], ..IceConfig::default() };
+        assert!(hostname.validate().is_err());
+    }
+
+    #[test]
+    fn host_only_offers_no_servers() {
+        let config = IceConfig {
+            servers: vec![server("stun:stun.example.org:3478", "", "")],
+            host_only: true,
+            ..IceConfig::default()
+        };
+        assert!(config.ice_servers().is_empty());
+        assert_eq!(IceConfig { host_only: false, ..config }.ice_servers().len(), 1);
+    }
+}
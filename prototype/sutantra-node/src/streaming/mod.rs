@@ -7,9 +7,14 @@ pub mod engine;
 pub mod webrtc; // Mock WebRTC implementation
 pub mod real_webrtc_fixed; // Fixed Real WebRTC implementation
 pub mod discovery;
+pub mod ice; // ICE servers, NAT mapping and UDP ports
+pub mod sfu; // Forwarding of published media to viewers
+pub mod signaling; // WebRTC offer/answer and trickle ICE over the WebSocket
 pub mod test_trigger; // Auto-trigger streams for testing
 
 pub use engine::StreamingEngine;
+pub use ice::{IceConfig, IceServerConfig};
+pub use signaling::{IceGathering, LocalCandidate, Signaling};
 
 /// Configuration for the streaming engine
 #[derive(Debug, Clone)]
@@ -21,6 +26,7 @@ pub struct StreamingConfig {
     pub relay_capacity: u32, // Max concurrent streams a relay can handle
     pub discovery_interval_seconds: u64,
     pub use_real_webrtc: bool, // Toggle between mock and real WebRTC
+    pub ice: IceConfig,
 }
 
 /// Events emitted by the streaming layer
@@ -136,8 +142,8 @@ pub struct StreamMetrics {
 /// Stream quality settings for creators
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StreamQualitySettings {
-    pub video_codec: String, // "H264", "VP8", "VP9"
-    pub audio_codec: String, // "Opus", "AAC"
+    pub video_codec: String, // "H264", "VP8", "VP9", or "none" for audio-only
+    pub audio_codec: String, // "Opus", "AAC", or "none" for video-only
     pub max_bitrate_kbps: u32,
     pub target_fps: u32,
     pub resolution: String, // "1920x1080", "1280x720", etc.
@@ -157,6 +163,18 @@ impl Default for StreamQualitySettings {
     }
 }
 
+impl StreamQualitySettings {
+    /// Whether the stream carries video; audio-only streams set `video_codec` to "none"
+    pub fn has_video(&self) -> bool {
+        !self.video_codec.eq_ignore_ascii_case("none")
+    }
+
+    /// Whether the stream carries audio; video-only streams set `audio_codec` to "none"
+    pub fn has_audio(&self) -> bool {
+        !self.audio_codec.eq_ignore_ascii_case("none")
+    }
+}
+
 /// Active stream information
 #[derive(Debug, Clone)]
 pub struct ActiveStream {
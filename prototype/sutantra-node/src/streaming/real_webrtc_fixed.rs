@@ -4,28 +4,27 @@ use std::sync::Arc;
 use tokio::sync::{mpsc, RwLock};
 use tracing::{info, debug, warn, error};
 
-use webrtc::api::interceptor_registry::register_default_interceptors;
-use webrtc::api::media_engine::{MediaEngine, MIME_TYPE_VP8};
+use webrtc::api::interceptor_registry::{configure_nack, configure_twcc_receiver_only};
+use webrtc::api::media_engine::{MediaEngine, MIME_TYPE_OPUS, MIME_TYPE_VP8};
 use webrtc::api::APIBuilder;
 use webrtc::ice_transport::ice_connection_state::RTCIceConnectionState;
 use webrtc::ice_transport::ice_server::RTCIceServer;
-use webrtc::peer_connection::configuration::RTCConfiguration;
+use webrtc::interceptor::registry::Registry;
+use webrtc::interceptor::report::receiver::ReceiverReport;
 use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
-use webrtc::peer_connection::RTCPeerConnection;
 use webrtc::rtp_transceiver::rtp_codec::RTCRtpCodecCapability;
-use webrtc::track::track_local::track_local_static_rtp::TrackLocalStaticRTP;
-use webrtc::track::track_local::{TrackLocal, TrackLocalWriter};
-use bytes::Bytes;
 
-use super::{StreamQualitySettings, StreamMetrics};
+use super::{IceConfig, StreamQualitySettings, StreamMetrics, StreamingEvent};
+use super::sfu::Forwarder;
+use super::signaling::Signaling;
 
 /// Fixed WebRTC engine with proper threading support
 pub struct RealWebRTCEngine {
     port: u16,
-    api: webrtc::api::API,
     active_streams: Arc<RwLock<HashMap<String, StreamConnection>>>, // stream_id -> stream details
     peer_manager: Arc<PeerConnectionManager>,
     ice_servers: Vec<RTCIceServer>,
+    signaling: Signaling,
 }
 
 /// Thread-safe peer connection manager
@@ -57,7 +56,7 @@ pub struct StreamConnection {
     pub creator: String,
     pub viewers: HashMap<String, ViewerInfo>, // viewer_id -> viewer info
     pub quality: StreamQualitySettings,
-    pub track: Option<Arc<TrackLocalStaticRTP>>,
+    pub forwarder: Arc<Forwarder>,
     pub metrics: StreamMetrics,
     pub viewer_count: u32,
     pub active: bool,
@@ -77,6 +76,7 @@ impl PeerConnectionManager {
         }
     }
 
+    /// Track a viewer's connection, keeping the status of one already tracked
     pub async fn add_connection(&self, stream_id: String, viewer_id: String) -> Result<()> {
         let peer_id = format!("{}:{}", stream_id, viewer_id);
         let wrapper = PeerConnectionWrapper {
@@ -87,7 +87,7 @@ impl PeerConnectionManager {
             status: ConnectionStatus::Connecting,
         };
         
-        self.connections.write().await.insert(peer_id, wrapper);
+        self.connections.write().await.entry(peer_id).or_insert(wrapper);
         Ok(())
     }
 
@@ -116,62 +116,64 @@ impl PeerConnectionManager {
 }
 
 impl RealWebRTCEngine {
-    pub async fn new(port: u16) -> Result<Self> {
+    pub async fn new(port: u16, ice: &IceConfig, events: mpsc::Sender<StreamingEvent>) -> Result<Self> {
         info!("🎥 Initializing REAL WebRTC Engine on port {}", port);
         info!("🔧 WebRTC Mode: PRODUCTION (not simulation)");
 
         // Create a MediaEngine object to configure the supported codec
         let mut media_engine = MediaEngine::default();
 
-        // Add support for common video codecs
+        // Add support for common video and audio codecs
         media_engine.register_default_codecs()?;
 
-        // Create a InterceptorRegistry to configure interceptors
-        let mut registry = Default::default();
-        registry = register_default_interceptors(registry, &mut media_engine)?;
+        // The default interceptors without the sender report generator, since
+        // viewers get the publisher's own sender reports to keep audio and video in sync
+        let mut registry = Registry::new();
+        registry = configure_nack(registry, &mut media_engine);
+        registry.add(Box::new(ReceiverReport::builder()));
+        registry = configure_twcc_receiver_only(registry, &mut media_engine)?;
+
+        // Bind ICE to the configured UDP ports and NAT mapping
+        let setting_engine = ice.setting_engine(port)?;
+        let (first_port, last_port) = ice.port_range(port)?;
+        info!("🧊 ICE UDP ports {}-{}{}", first_port, last_port, if ice.host_only { " (host candidates only)" } else { "" });
+        if !ice.nat_1to1_ips.is_empty() {
+            info!("🧊 NAT 1:1 mapping to {:?}", ice.nat_1to1_ips);
+        }
 
         // Use the default set of Interceptors
         let api = APIBuilder::new()
             .with_media_engine(media_engine)
             .with_interceptor_registry(registry)
+            .with_setting_engine(setting_engine)
             .build();
 
-        // Configure ICE servers (STUN servers for NAT traversal) - Mix of providers for decentralization
-        let ice_servers = vec![
-            // Huddle STUN servers (more decentralized)
-            RTCIceServer {
-                urls: vec!["stun:global.stun.twilio.com:3478".to_string()],
-                ..Default::default()
-            },
-            RTCIceServer {
-                urls: vec!["stun:stun.relay.metered.ca:80".to_string()],
-                ..Default::default()
-            },
-            // Google STUN servers (fallback)
-            RTCIceServer {
-                urls: vec!["stun:stun.l.google.com:19302".to_string()],
-                ..Default::default()
-            },
-            // Cloudflare STUN (more decentralized option)
-            RTCIceServer {
-                urls: vec!["stun:stun.cloudflare.com:3478".to_string()],
-                ..Default::default()
-            },
-        ];
+        // STUN and TURN servers come from the operator; none are used unless configured
+        let ice_servers = ice.ice_servers();
+
+        let active_streams = Arc::new(RwLock::new(HashMap::new()));
+        let peer_manager = Arc::new(PeerConnectionManager::new());
+        let signaling = Signaling::new(
+            Arc::new(api),
+            ice_servers.clone(),
+            Arc::clone(&active_streams),
+            Arc::clone(&peer_manager),
+            events,
+        );
 
         Ok(Self {
             port,
-            api,
-            active_streams: Arc::new(RwLock::new(HashMap::new())),
-            peer_manager: Arc::new(PeerConnectionManager::new()),
+            active_streams,
+            peer_manager,
             ice_servers,
+            signaling,
         })
     }
 
     pub async fn start(&mut self) -> Result<()> {
         info!("🚀 Starting REAL WebRTC Engine");
-        info!("📡 STUN Servers: {:?}", self.ice_servers.iter().map(|s| &s.urls).collect::<Vec<_>>());
-        info!("🎬 Ready for VP8 video streaming");
+        info!("📡 ICE Servers: {:?}", self.ice_servers.iter().map(|s| &s.urls).collect::<Vec<_>>());
+        info!("🎬 Ready for VP8 video and Opus audio streaming");
         // Start the WebRTC connection manager
         self.start_connection_manager().await?;
         Ok(())
@@ -179,12 +181,16 @@ impl RealWebRTCEngine {
 
     async fn start_connection_manager(&self) -> Result<()> {
         info!("🔄 Starting WebRTC connection manager");
-        // In a real implementation, this would start listening for WebRTC signaling
-        // For now, we'll just mark it as started
+        info!("📡 Creators and viewers negotiate peer connections over the node WebSocket");
         Ok(())
     }
 
-    /// Create a new stream with real WebRTC track
+    /// Signaling handle creators and viewers negotiate their peer connections through
+    pub fn signaling(&self) -> Signaling {
+        self.signaling.clone()
+    }
+
+    /// Create a new stream with a VP8 video track, an Opus audio track, or both
     pub async fn create_stream(
         &mut self,
         stream_id: String,
@@ -192,29 +198,51 @@ impl RealWebRTCEngine {
         quality: StreamQualitySettings,
     ) -> Result<()> {
         info!("🎬 Creating REAL WebRTC stream: {} by {}", stream_id, creator);
-        info!("🎥 Codec: VP8 video at 90kHz clock rate");
 
-        // Create a video track with VP8 codec
-        let video_codec = RTCRtpCodecCapability {
+        // Viewers get VP8 and Opus tracks fed with whatever the creator publishes
+        let video_codec = quality.has_video().then(|| RTCRtpCodecCapability {
             mime_type: MIME_TYPE_VP8.to_string(),
             clock_rate: 90000,
             channels: 0,
             sdp_fmtp_line: String::new(),
             rtcp_feedback: Vec::new(),
-        };
+        });
+        let audio_codec = quality.has_audio().then(|| RTCRtpCodecCapability {
+            mime_type: MIME_TYPE_OPUS.to_string(),
+            clock_rate: 48000,
+            channels: 2,
+            sdp_fmtp_line: "minptime=10;useinbandfec=1".to_string(),
+            rtcp_feedback: Vec::new(),
+        });
+        match (&video_codec, &audio_codec) {
+            (None, None) => return Err(anyhow::anyhow!("Stream {} has neither video nor audio", stream_id)),
+            (Some(_), Some(_)) => info!("🎥 Codecs: VP8 video at 90kHz, Opus audio at 48kHz"),
+            (Some(_), None) => info!("🎥 Codec: VP8 video at 90kHz, no audio"),
+            (None, Some(_)) => info!("🎙️ Codec: Opus audio at 48kHz, audio only"),
+        }
+        if quality.has_audio() && !quality.audio_codec.eq_ignore_ascii_case("opus") {
+            warn!("Audio codec {} is not available over WebRTC, using Opus", quality.audio_codec);
+        }
         
-        let video_track = Arc::new(TrackLocalStaticRTP::new(
-            video_codec,
-            "video".to_string(),
-            format!("video-{}", stream_id),
-        ));
+        // Restarting a stream with the same codecs keeps its forwarder, so connected viewers stay on it
+        let forwarder = match self.active_streams.read().await.get(&stream_id) {
+            Some(existing) if existing.forwarder.carries(video_codec.as_ref(), audio_codec.as_ref()) => {
+                Arc::clone(&existing.forwarder)
+            }
+            existing => {
+                if existing.is_some() {
+                    warn!("Stream {} restarted with other codecs; its viewers must reconnect", stream_id);
+                }
+                Arc::new(Forwarder::new(stream_id.clone(), video_codec, audio_codec))
+            }
+        };
 
         let stream_connection = StreamConnection {
             stream_id: stream_id.clone(),
             creator: creator.clone(),
             viewers: HashMap::new(),
             quality: quality.clone(),
-            track: Some(video_track.clone()),
+            forwarder,
             metrics: StreamMetrics {
                 bandwidth_mbps: 0.0,
                 latency_ms: 0,
@@ -230,13 +258,7 @@ impl RealWebRTCEngine {
 
         self.active_streams.write().await.insert(stream_id.clone(), stream_connection);
 
-        // Start generating video frames in a separate task
-        let track_clone = video_track.clone();
-        let stream_id_clone = stream_id.clone();
-        tokio::spawn(async move {
-            Self::generate_video_frames(track_clone, stream_id_clone).await;
-        });
-
+        info!("📥 Stream {} waits for its creator to publish", stream_id);
         info!("✅ Fixed real WebRTC stream created: {}", stream_id);
         Ok(())
     }
@@ -244,7 +266,6 @@ impl RealWebRTCEngine {
     /// Connect a viewer to a stream using real WebRTC
     pub async fn connect_viewer(&mut self, stream_id: String, viewer_id: String) -> Result<()> {
         info!("🔗 Connecting viewer {} to REAL WebRTC stream {}", viewer_id, stream_id);
-        info!("📺 Establishing peer-to-peer connection with VP8 codec");
 
         // Check if stream exists
         let stream_exists = self.active_streams.read().await.contains_key(&stream_id);
@@ -256,19 +277,6 @@ impl RealWebRTCEngine {
         // Add connection to peer manager (thread-safe)
         self.peer_manager.add_connection(stream_id.clone(), viewer_id.clone()).await?;
 
-        // Setup peer connection directly (simplified approach)
-        match self.setup_peer_connection_direct(&stream_id, &viewer_id).await {
-            Ok(_) => {
-                info!("✅ Peer connection setup successful for viewer {} on stream {}", viewer_id, stream_id);
-                // Update status to connected
-                self.peer_manager.update_status(&stream_id, &viewer_id, ConnectionStatus::Connected).await?;
-            }
-            Err(e) => {
-                error!("Failed to setup peer connection for viewer {}: {}", viewer_id, e);
-                self.peer_manager.update_status(&stream_id, &viewer_id, ConnectionStatus::Failed).await?;
-            }
-        }
-
         // Update stream viewer info
         if let Some(stream) = self.active_streams.write().await.get_mut(&stream_id) {
             stream.viewers.insert(viewer_id.clone(), ViewerInfo {
@@ -279,35 +287,8 @@ impl RealWebRTCEngine {
             stream.viewer_count = self.peer_manager.get_connection_count(&stream_id).await;
         }
 
-        info!("✅ Viewer {} connection initiated for stream {}", viewer_id, stream_id);
-        Ok(())
-    }
-
-    async fn setup_peer_connection_direct(&self, stream_id: &str, viewer_id: &str) -> Result<()> {
-        // Create peer connection configuration
-        let config = RTCConfiguration {
-            ice_servers: self.ice_servers.clone(),
-            ..Default::default()
-        };
-
-        // Create a new RTCPeerConnection
-        let peer_connection = Arc::new(self.api.new_peer_connection(config).await?);
-
-        // Add the stream track to the peer connection
-        if let Some(stream) = self.active_streams.read().await.get(stream_id) {
-            if let Some(track) = &stream.track {
-                let _rtp_sender = peer_connection
-                    .add_track(track.clone() as Arc<dyn TrackLocal + Send + Sync>)
-                    .await?;
-
-                info!("🔗 Peer connection setup complete for viewer {} on stream {}", viewer_id, stream_id);
-            } else {
-                return Err(anyhow::anyhow!("No track available for stream {}", stream_id));
-            }
-        } else {
-            return Err(anyhow::anyhow!("Stream {} not found", stream_id));
-        }
-
+        // The peer connection itself is made when the viewer's offer arrives
+        info!("✅ Viewer {} admitted to stream {}, awaiting their WebRTC offer", viewer_id, stream_id);
         Ok(())
     }
 
@@ -315,7 +296,8 @@ impl RealWebRTCEngine {
     pub async fn disconnect_viewer(&mut self, stream_id: String, viewer_id: String) -> Result<()> {
         info!("❌ Disconnecting viewer {} from stream {}", viewer_id, stream_id);
 
-        // Remove from peer manager
+        // Hang up the viewer's peer connection, then stop tracking it
+        self.signaling.close(&stream_id, &viewer_id, "Disconnected by the node").await;
         self.peer_manager.remove_connection(&stream_id, &viewer_id).await?;
 
         // Remove viewer from stream
@@ -328,63 +310,6 @@ impl RealWebRTCEngine {
         Ok(())
     }
 
-    /// Generate video frames for the stream
-    async fn generate_video_frames(track: Arc<TrackLocalStaticRTP>, stream_id: String) {
-        info!("📹 Starting REAL video frame generation for stream {}", stream_id);
-        info!("🎥 Generating VP8 RTP packets at 30 FPS");
-        
-        let mut frame_count = 0u64;
-        let frame_interval = tokio::time::Duration::from_millis(33); // ~30 FPS
-
-        loop {
-            // Generate a test frame
-            let test_packet = Self::generate_test_frame(frame_count);
-            
-            if let Err(e) = track.write_rtp(&test_packet).await {
-                warn!("Error writing RTP packet for stream {}: {}", stream_id, e);
-                break;
-            }
-
-            frame_count += 1;
-            
-            // Log every 30th frame (once per second at 30 FPS)
-            if frame_count % 30 == 0 {
-                info!("📡 REAL WebRTC: Transmitted frame {} for stream {} ({}s)", 
-                      frame_count, stream_id, frame_count / 30);
-            }
-            
-            // Generate frames for 30 seconds (900 frames at 30 FPS)
-            if frame_count >= 900 {
-                info!("📹 Video generation completed for stream {} ({} frames)", stream_id, frame_count);
-                break;
-            }
-
-            tokio::time::sleep(frame_interval).await;
-        }
-    }
-
-    /// Generate a test video frame
-    fn generate_test_frame(frame_count: u64) -> webrtc::rtp::packet::Packet {
-        let payload = format!("Video frame {}", frame_count).into_bytes();
-        
-        webrtc::rtp::packet::Packet {
-            header: webrtc::rtp::header::Header {
-                version: 2,
-                padding: false,
-                extension: false,
-                marker: frame_count % 30 == 0, // Mark every 30th frame
-                payload_type: 96, // VP8 payload type
-                sequence_number: (frame_count % 65536) as u16,
-                timestamp: (frame_count * 3000) as u32, // 90kHz clock
-                ssrc: 12345,
-                csrc: vec![],
-                extension_profile: 0,
-                extensions: vec![],
-            },
-            payload: Bytes::from(payload),
-        }
-    }
-
     pub async fn get_stream_metrics(&self, stream_id: &str) -> Option<StreamMetrics> {
         self.active_streams.read().await.get(stream_id).map(|s| s.metrics.clone())
     }
@@ -414,3 +339,40 @@ pub struct ConnectionStats {
     pub jitter_ms: u32,
     pub packet_loss_percent: f64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn forwarder(engine: &RealWebRTCEngine) -> Arc<Forwarder> {
+        Arc::clone(&engine.active_streams.read().await["stream"].forwarder)
+    }
+
+    async fn start(engine: &mut RealWebRTCEngine, video: &str, audio: &str) -> Result<()> {
+        let quality = StreamQualitySettings {
+            video_codec: video.to_string(),
+            audio_codec: audio.to_string(),
+            ..StreamQualitySettings::default()
+        };
+        engine.create_stream("stream".to_string(), "creator".to_string(), quality).await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn restarting_a_stream_keeps_its_forwarder_unless_the_codecs_change() {
+        let ice = IceConfig { host_only: true, ..IceConfig::default() };
+        let mut engine = RealWebRTCEngine::new(47_600, &ice, mpsc::channel(16).0).await.unwrap();
+
+        start(&mut engine, "VP8", "Opus").await.unwrap();
+        let first = forwarder(&engine).await;
+        start(&mut engine, "VP8", "Opus").await.unwrap();
+        assert!(Arc::ptr_eq(&first, &forwarder(&engine).await));
+
+        // Dropping video rebuilds the forwarder with an audio lane only
+        start(&mut engine, "none", "Opus").await.unwrap();
+        let audio_only = forwarder(&engine).await;
+        assert!(!Arc::ptr_eq(&first, &audio_only));
+        assert_eq!(audio_only.add_viewer("viewer").await.len(), 1);
+
+        assert!(start(&mut engine, "none", "none").await.is_err());
+    }
+}
@@ -0,0 +1,410 @@
+// Selective forwarding
+//
+// A creator publishes their camera and microphone to the node over a
+// receive-only peer connection, and the node relays them without decoding.
+// A stream has a lane for each kind of media it carries, so an audio-only
+// stream such as a podcast has no video lane. Each RTP packet read from a
+// published track is copied to every viewer's own outbound track of the same
+// kind. Every viewer track has a rewriter that maps the publisher's sequence
+// numbers and timestamps onto the viewer's stream. When the creator
+// reconnects with a new SSRC, the new source is spliced on right after what
+// each viewer already has, so no viewer sees a jump. Players line up audio
+// and video using RTCP sender reports, which tie each track's RTP clock to
+// wall-clock time. The node relays the publisher's reports to each viewer,
+// translated through the viewer's rewriter, so both of the viewer's tracks
+// keep the publisher's timing. A viewer can only start decoding video at a
+// keyframe, so the node asks the publisher for one whenever a viewer connects.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+use tokio::sync::{Mutex, RwLock};
+use tracing::{debug, info};
+
+use webrtc::peer_connection::RTCPeerConnection;
+use webrtc::rtcp::payload_feedbacks::picture_loss_indication::PictureLossIndication;
+use webrtc::rtcp::sender_report::SenderReport;
+use webrtc::rtp::header::Header;
+use webrtc::rtp::packet::Packet;
+use webrtc::rtp_transceiver::rtp_codec::{RTCRtpCodecCapability, RTPCodecType};
+use webrtc::rtp_transceiver::rtp_receiver::RTCRtpReceiver;
+use webrtc::track::track_local::track_local_static_rtp::TrackLocalStaticRTP;
+use webrtc::track::track_local::TrackLocalWriter;
+use webrtc::track::track_remote::TrackRemote;
+
+/// Media time between the last packet of one source and the first of the next
+const SOURCE_SWITCH_GAP_MS: u32 = 20;
+
+/// Maps a publisher's sequence numbers and timestamps onto one viewer's stream
+#[derive(Debug)]
+pub struct RtpRewriter {
+    clock_rate: u32,
+    source_ssrc: Option<u32>,
+    seq_offset: u16,
+    ts_offset: u32,
+    /// Newest sequence number and timestamp the viewer has been sent
+    last: Option<(u16, u32)>,
+}
+
+impl RtpRewriter {
+    pub fn new(clock_rate: u32) -> Self {
+        Self {
+            clock_rate,
+            source_ssrc: None,
+            seq_offset: 0,
+            ts_offset: 0,
+            last: None,
+        }
+    }
+
+    /// Rewrite a forwarded packet's sequence number and timestamp for the viewer
+    pub fn rewrite(&mut self, header: &mut Header) {
+        if self.source_ssrc != Some(header.ssrc) {
+            if let Some((last_seq, last_ts)) = self.last {
+                let gap = self.clock_rate / 1000 * SOURCE_SWITCH_GAP_MS;
+                self.seq_offset = last_seq.wrapping_add(1).wrapping_sub(header.sequence_number);
+                self.ts_offset = last_ts.wrapping_add(gap).wrapping_sub(header.timestamp);
+            }
+            self.source_ssrc = Some(header.ssrc);
+        }
+
+        header.sequence_number = header.sequence_number.wrapping_add(self.seq_offset);
+        header.timestamp = header.timestamp.wrapping_add(self.ts_offset);
+
+        // Reordered packets keep their place without moving the newest mark back
+        let stale = self.last
+            .is_some_and(|(last_seq, _)| header.sequence_number.wrapping_sub(last_seq) as i16 <= 0);
+        if !stale {
+            self.last = Some((header.sequence_number, header.timestamp));
+        }
+    }
+
+    /// A publisher timestamp on the viewer's stream, if `ssrc` is the source being forwarded
+    pub fn translate_timestamp(&self, ssrc: u32, timestamp: u32) -> Option<u32> {
+        (self.source_ssrc == Some(ssrc)).then(|| timestamp.wrapping_add(self.ts_offset))
+    }
+}
+
+struct ViewerTrack {
+    track: Arc<TrackLocalStaticRTP>,
+    state: Mutex<ViewerState>,
+}
+
+struct ViewerState {
+    rewriter: RtpRewriter,
+    /// RTP packets and payload octets sent, as sender reports count them
+    packets: u32,
+    octets: u32,
+    /// Viewer's connection and the SSRC the track is sent with, once negotiated
+    sender: Option<(Weak<RTCPeerConnection>, u32)>,
+}
+
+/// One kind of media in a stream, relayed from the publisher to every viewer
+struct MediaLane {
+    kind: RTPCodecType,
+    codec: RTCRtpCodecCapability,
+    viewers: RwLock<HashMap<String, ViewerTrack>>,
+    /// Publisher's connection and media SSRC, for keyframe requests
+    publisher: RwLock<Option<(Weak<RTCPeerConnection>, u32)>>,
+}
+
+impl MediaLane {
+    fn new(kind: RTPCodecType, codec: RTCRtpCodecCapability) -> Self {
+        Self {
+            kind,
+            codec,
+            viewers: RwLock::new(HashMap::new()),
+            publisher: RwLock::new(None),
+        }
+    }
+}
+
+/// Fans the media a creator publishes to a stream out to its viewers
+pub struct Forwarder {
+    stream_id: String,
+    video: Option<MediaLane>,
+    audio: Option<MediaLane>,
+}
+
+impl std::fmt::Debug for Forwarder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Forwarder")
+            .field("stream_id", &self.stream_id)
+            .field("video", &self.video.as_ref().map(|lane| &lane.codec.mime_type))
+            .field("audio", &self.audio.as_ref().map(|lane| &lane.codec.mime_type))
+            .finish()
+    }
+}
+
+impl Forwarder {
+    /// Forwarder for a stream carrying the given video and audio codecs, at least one of them
+    pub fn new(
+        stream_id: String,
+        video: Option<RTCRtpCodecCapability>,
+        audio: Option<RTCRtpCodecCapability>,
+    ) -> Self {
+        Self {
+            stream_id,
+            video: video.map(|codec| MediaLane::new(RTPCodecType::Video, codec)),
+            audio: audio.map(|codec| MediaLane::new(RTPCodecType::Audio, codec)),
+        }
+    }
+
+    /// Whether the forwarder carries exactly the given video and audio codecs
+    pub fn carries(&self, video: Option<&RTCRtpCodecCapability>, audio: Option<&RTCRtpCodecCapability>) -> bool {
+        self.video.as_ref().map(|lane| &lane.codec) == video
+            && self.audio.as_ref().map(|lane| &lane.codec) == audio
+    }
+
+    fn lane(&self, kind: RTPCodecType) -> Option<&MediaLane> {
+        match kind {
+            RTPCodecType::Video => self.video.as_ref(),
+            RTPCodecType::Audio => self.audio.as_ref(),
+            _ => None,
+        }
+    }
+
+    fn lanes(&self) -> impl Iterator<Item = &MediaLane> {
+        self.video.iter().chain(self.audio.iter())
+    }
+
+    /// Give a viewer their own outbound track for each kind of media, replacing any they had
+    ///
+    /// All of a stream's tracks share one media stream id, so players keep
+    /// them in sync.
+    pub async fn add_viewer(&self, viewer_id: &str) -> Vec<Arc<TrackLocalStaticRTP>> {
+        let mut tracks = Vec::new();
+        for lane in self.lanes() {
+            let track = Arc::new(TrackLocalStaticRTP::new(
+                lane.codec.clone(),
+                lane.kind.to_string(),
+                format!("stream-{}", self.stream_id),
+            ));
+            lane.viewers.write().await.insert(viewer_id.to_string(), ViewerTrack {
+                track: Arc::clone(&track),
+                state: Mutex::new(ViewerState {
+                    rewriter: RtpRewriter::new(lane.codec.clock_rate),
+                    packets: 0,
+                    octets: 0,
+                    sender: None,
+                }),
+            });
+            tracks.push(track);
+        }
+        tracks
+    }
+
+    /// Record which connection and SSRC a viewer's track of one kind is sent with
+    pub async fn attach_viewer(&self, viewer_id: &str, kind: RTPCodecType, peer_connection: Weak<RTCPeerConnection>, ssrc: u32) {
+        let Some(lane) = self.lane(kind) else {
+            return;
+        };
+        if let Some(viewer) = lane.viewers.read().await.get(viewer_id) {
+            viewer.state.lock().await.sender = Some((peer_connection, ssrc));
+        }
+    }
+
+    /// Stop forwarding to a viewer, returning whether they had a track
+    pub async fn remove_viewer(&self, viewer_id: &str) -> bool {
+        let mut removed = false;
+        for lane in self.lanes() {
+            removed |= lane.viewers.write().await.remove(viewer_id).is_some();
+        }
+        removed
+    }
+
+    /// Forward a published track to every viewer until it ends, along with its sender reports
+    ///
+    /// A track of a kind the stream does not carry is ignored.
+    pub fn publish(self: &Arc<Self>, publisher: Weak<RTCPeerConnection>, track: Arc<TrackRemote>, receiver: Arc<RTCRtpReceiver>) {
+        let kind = track.kind();
+        if self.lane(kind).is_none() {
+            debug!("Ignoring published {} track on stream {}", kind, self.stream_id);
+            return;
+        }
+
+        let forwarder = Arc::clone(self);
+        tokio::spawn(async move {
+            while let Ok((packets, _)) = receiver.read_rtcp().await {
+                for packet in packets {
+                    if let Some(report) = packet.as_any().downcast_ref::<SenderReport>() {
+                        forwarder.relay_sender_report(kind, report).await;
+                    }
+                }
+            }
+        });
+
+        let forwarder = Arc::clone(self);
+        tokio::spawn(async move {
+            let Some(lane) = forwarder.lane(kind) else {
+                return;
+            };
+            let ssrc = track.ssrc();
+            *lane.publisher.write().await = Some((publisher, ssrc));
+            if kind == RTPCodecType::Video {
+                forwarder.request_keyframe().await;
+            }
+            info!("📥 Forwarding published {} track (SSRC {}) to viewers of stream {}", kind, ssrc, forwarder.stream_id);
+
+            let mut forwarded = 0u64;
+            while let Ok((packet, _)) = track.read_rtp().await {
+                forwarder.forward(lane, &packet).await;
+                forwarded += 1;
+            }
+
+            // Forget the publisher unless a newer one has taken over
+            let mut current = lane.publisher.write().await;
+            if current.as_ref().is_some_and(|(_, current_ssrc)| *current_ssrc == ssrc) {
+                *current = None;
+            }
+            info!("📴 Published {} track for stream {} ended after {} packets", kind, forwarder.stream_id, forwarded);
+        });
+    }
+
+    /// Ask the publisher for a keyframe so a viewer can start decoding
+    pub async fn request_keyframe(&self) {
+        let Some(lane) = &self.video else {
+            return;
+        };
+        let Some((publisher, media_ssrc)) = lane.publisher.read().await.clone() else {
+            return;
+        };
+        let Some(peer_connection) = publisher.upgrade() else {
+            return;
+        };
+
+        let pli = PictureLossIndication { sender_ssrc: 0, media_ssrc };
+        if let Err(e) = peer_connection.write_rtcp(&[Box::new(pli)]).await {
+            debug!("Failed to request a keyframe for stream {}: {}", self.stream_id, e);
+        }
+    }
+
+    async fn forward(&self, lane: &MediaLane, packet: &Packet) {
+        for (viewer_id, viewer) in lane.viewers.read().await.iter() {
+            let mut outgoing = packet.clone();
+            {
+                let mut state = viewer.state.lock().await;
+                state.rewriter.rewrite(&mut outgoing.header);
+                state.packets = state.packets.wrapping_add(1);
+                state.octets = state.octets.wrapping_add(outgoing.payload.len() as u32);
+            }
+            if let Err(e) = viewer.track.write_rtp(&outgoing).await {
+                debug!("Failed to forward RTP to viewer {} on stream {}: {}", viewer_id, self.stream_id, e);
+            }
+        }
+    }
+
+    /// Pass a publisher's sender report on to every viewer, on the viewer's own SSRC and timeline
+    async fn relay_sender_report(&self, kind: RTPCodecType, report: &SenderReport) {
+        let Some(lane) = self.lane(kind) else {
+            return;
+        };
+
+        for (viewer_id, viewer) in lane.viewers.read().await.iter() {
+            let (peer_connection, relayed) = {
+                let state = viewer.state.lock().await;
+                let Some(rtp_time) = state.rewriter.translate_timestamp(report.ssrc, report.rtp_time) else {
+                    continue;
+                };
+                let Some((peer_connection, ssrc)) = &state.sender else {
+                    continue;
+                };
+                let relayed = SenderReport {
+                    ssrc: *ssrc,
+                    ntp_time: report.ntp_time,
+                    rtp_time,
+                    packet_count: state.packets,
+                    octet_count: state.octets,
+                    ..Default::default()
+                };
+                (peer_connection.clone(), relayed)
+            };
+
+            let Some(peer_connection) = peer_connection.upgrade() else {
+                continue;
+            };
+            if let Err(e) = peer_connection.write_rtcp(&[Box::new(relayed)]).await {
+                debug!("Failed to relay a sender report to viewer {} on stream {}: {}", viewer_id, self.stream_id, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webrtc::track::track_local::TrackLocal;
+
+    fn rewritten(rewriter: &mut RtpRewriter, ssrc: u32, sequence_number: u16, timestamp: u32) -> (u16, u32) {
+        let mut header = Header { ssrc, sequence_number, timestamp, ..Header::default() };
+        rewriter.rewrite(&mut header);
+        (header.sequence_number, header.timestamp)
+    }
+
+    #[test]
+    fn first_source_passes_through() {
+        let mut rewriter = RtpRewriter::new(90_000);
+
+        assert_eq!(rewritten(&mut rewriter, 1, 100, 5_000), (100, 5_000));
+        assert_eq!(rewritten(&mut rewriter, 1, 101, 8_000), (101, 8_000));
+        assert_eq!(rewriter.translate_timestamp(1, 9_000), Some(9_000));
+        assert_eq!(rewriter.translate_timestamp(2, 9_000), None);
+    }
+
+    #[test]
+    fn new_source_continues_after_the_last_packet() {
+        let mut rewriter = RtpRewriter::new(90_000);
+        rewritten(&mut rewriter, 1, 100, 5_000);
+        rewritten(&mut rewriter, 1, 101, 8_000);
+
+        // 20 ms at 90 kHz after the last timestamp sent
+        assert_eq!(rewritten(&mut rewriter, 2, 40_000, 1_000_000), (102, 9_800));
+        assert_eq!(rewritten(&mut rewriter, 2, 40_001, 1_003_000), (103, 12_800));
+        assert_eq!(rewriter.translate_timestamp(2, 1_003_000), Some(12_800));
+        assert_eq!(rewriter.translate_timestamp(1, 8_000), None);
+    }
+
+    #[test]
+    fn sequence_and_timestamp_wrap_around() {
+        let mut rewriter = RtpRewriter::new(48_000);
+        rewritten(&mut rewriter, 1, u16::MAX, u32::MAX - 500);
+
+        assert_eq!(rewritten(&mut rewriter, 2, 7, 123), (0, 459));
+        assert_eq!(rewritten(&mut rewriter, 2, 8, 1_083), (1, 1_419));
+    }
+
+    #[test]
+    fn reordered_packets_do_not_move_the_splice_point_back() {
+        let mut rewriter = RtpRewriter::new(90_000);
+        rewritten(&mut rewriter, 1, 10, 3_000);
+        rewritten(&mut rewriter, 1, 12, 9_000);
+        assert_eq!(rewritten(&mut rewriter, 1, 11, 6_000), (11, 6_000));
+
+        // The next source starts after packet 12, not the late packet 11
+        assert_eq!(rewritten(&mut rewriter, 2, 500, 0), (13, 10_800));
+    }
+
+    fn codec(mime_type: &str, clock_rate: u32) -> RTCRtpCodecCapability {
+        RTCRtpCodecCapability { mime_type: mime_type.to_string(), clock_rate, ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn viewers_get_a_track_for_each_lane() {
+        let (vp8, opus) = (codec("video/VP8", 90_000), codec("audio/opus", 48_000));
+        let forwarder = Forwarder::new("stream".to_string(), Some(vp8.clone()), Some(opus.clone()));
+        let podcast = Forwarder::new("podcast".to_string(), None, Some(opus.clone()));
+
+        assert!(forwarder.carries(Some(&vp8), Some(&opus)));
+        assert!(!forwarder.carries(None, Some(&opus)));
+        assert!(!forwarder.carries(Some(&codec("video/VP9", 90_000)), Some(&opus)));
+        assert!(podcast.carries(None, Some(&opus)));
+
+        let tracks = forwarder.add_viewer("viewer").await;
+        let kinds: Vec<String> = tracks.iter().map(|track| track.kind().to_string()).collect();
+        assert_eq!(kinds, ["video", "audio"]);
+        assert!(tracks.iter().all(|track| track.stream_id() == "stream-stream"));
+        assert_eq!(podcast.add_viewer("viewer").await.len(), 1);
+
+        assert!(forwarder.remove_viewer("viewer").await);
+        assert!(!forwarder.remove_viewer("viewer").await);
+    }
+}
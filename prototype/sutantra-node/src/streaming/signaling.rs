@@ -0,0 +1,518 @@
+// WebRTC signaling
+//
+// Creators and viewers negotiate their media connections with the node over
+// its WebSocket. A viewer sends an SDP offer for a stream, and the node
+// answers it with a peer connection that carries the viewer's own tracks from
+// the stream's forwarder. A creator's offer is answered with a receive-only
+// connection whose audio and video the forwarder relays. One peer has one
+// connection per stream, so a creator publishes and views under different
+// ids. The node keeps each connection until the peer leaves, loses access or
+// the connection fails. ICE candidates trickle both ways. A peer's candidates
+// are added to its connection, or held until the offer arrives if they come
+// first. The node's candidates are handed to the peer's signaling channel as
+// they are gathered, or, for WHIP and WHEP clients that cannot hear them
+// later, gathered before answering and put in the answer. A peer whose
+// signaling channel closes is disconnected, and the node drops its end of the
+// channel when it closes the peer. Viewers' sessions opening and closing are
+// reported as streaming events, so they are metered and billed.
+
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot, RwLock};
+use tracing::{info, warn};
+
+use webrtc::api::API;
+use webrtc::ice_transport::ice_candidate::{RTCIceCandidate, RTCIceCandidateInit};
+use webrtc::ice_transport::ice_server::RTCIceServer;
+use webrtc::peer_connection::configuration::RTCConfiguration;
+use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
+use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
+use webrtc::peer_connection::RTCPeerConnection;
+use webrtc::rtp_transceiver::rtp_receiver::RTCRtpReceiver;
+use webrtc::rtp_transceiver::RTCRtpTransceiver;
+use webrtc::track::track_local::TrackLocal;
+use webrtc::track::track_remote::TrackRemote;
+
+use super::real_webrtc_fixed::{ConnectionStatus, PeerConnectionManager, StreamConnection};
+use super::sfu::Forwarder;
+use super::StreamingEvent;
+
+/// Most candidates held for an offer that has not arrived yet
+const MAX_PENDING_CANDIDATES: usize = 64;
+
+/// An ICE candidate gathered by the node, to pass on to a creator or viewer
+#[derive(Debug, Clone)]
+pub struct LocalCandidate {
+    pub stream_id: String,
+    pub candidate: RTCIceCandidateInit,
+}
+
+/// When the node's ICE candidates reach a peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IceGathering {
+    /// Answer at once and trickle candidates as they are gathered
+    Trickle,
+    /// Gather every candidate before answering and send them in the answer
+    Complete,
+}
+
+/// A peer's negotiated connection to a stream
+struct Session {
+    peer_connection: Arc<RTCPeerConnection>,
+    /// Whether the peer views the stream rather than publishing it
+    viewer: bool,
+    /// Dropped with the session, which stops watching the peer's signaling channel
+    _ended: oneshot::Sender<()>,
+}
+
+/// Handle for negotiating creators' and viewers' peer connections, shared with the web server
+#[derive(Clone)]
+pub struct Signaling {
+    api: Arc<API>,
+    ice_servers: Vec<RTCIceServer>,
+    streams: Arc<RwLock<HashMap<String, StreamConnection>>>,
+    peer_manager: Arc<PeerConnectionManager>,
+    events: mpsc::Sender<StreamingEvent>,
+    /// "stream_id:peer_id" → answering peer connection
+    sessions: Arc<RwLock<HashMap<String, Session>>>,
+    /// "stream_id:peer_id" → peer candidates that arrived before the offer
+    pending: Arc<RwLock<HashMap<String, Vec<RTCIceCandidateInit>>>>,
+}
+
+impl Signaling {
+    pub fn new(
+        api: Arc<API>,
+        ice_servers: Vec<RTCIceServer>,
+        streams: Arc<RwLock<HashMap<String, StreamConnection>>>,
+        peer_manager: Arc<PeerConnectionManager>,
+        events: mpsc::Sender<StreamingEvent>,
+    ) -> Self {
+        Self {
+            api,
+            ice_servers,
+            streams,
+            peer_manager,
+            events,
+            sessions: Arc::new(RwLock::new(HashMap::new())),
+            pending: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Answer a viewer's offer, replacing any connection they already have to the stream
+    ///
+    /// With `IceGathering::Trickle` the node's ICE candidates are sent to
+    /// `candidates` as they are gathered. The connection lasts until the
+    /// receiver of `candidates` is dropped.
+    pub async fn answer(
+        &self,
+        stream_id: &str,
+        viewer_id: &str,
+        offer: RTCSessionDescription,
+        gathering: IceGathering,
+        candidates: mpsc::UnboundedSender<LocalCandidate>,
+    ) -> Result<RTCSessionDescription> {
+        let forwarder = self.forwarder(stream_id).await
+            .ok_or_else(|| anyhow!("Stream {} not found", stream_id))?;
+        self.replace_session(stream_id, viewer_id).await;
+
+        let peer_connection = self.new_peer_connection().await?;
+        for track in forwarder.add_viewer(viewer_id).await {
+            let kind = track.kind();
+            let rtp_sender = match peer_connection.add_track(track as Arc<dyn TrackLocal + Send + Sync>).await {
+                Ok(rtp_sender) => rtp_sender,
+                Err(e) => {
+                    self.shut(stream_id, viewer_id, &peer_connection).await;
+                    return Err(e.into());
+                }
+            };
+
+            // Sender reports relayed from the publisher go out on the track's SSRC
+            if let Some(encoding) = rtp_sender.get_parameters().await.encodings.first() {
+                forwarder.attach_viewer(viewer_id, kind, Arc::downgrade(&peer_connection), encoding.ssrc).await;
+            }
+
+            // Read incoming RTCP so the interceptors see the viewer's feedback
+            tokio::spawn(async move {
+                let mut rtcp_buf = vec![0u8; 1500];
+                while rtp_sender.read(&mut rtcp_buf).await.is_ok() {}
+            });
+        }
+
+        let answer = self.open_session(stream_id, viewer_id, true, peer_connection, offer, gathering, candidates).await?;
+        self.peer_manager.add_connection(stream_id.to_string(), viewer_id.to_string()).await?;
+
+        info!("📡 Answered WebRTC offer from viewer {} for stream {}", viewer_id, stream_id);
+        self.emit(StreamingEvent::ViewerConnected {
+            stream_id: stream_id.to_string(),
+            viewer_id: viewer_id.to_string(),
+        }).await;
+        Ok(answer)
+    }
+
+    /// Answer a creator's offer to publish their camera, replacing their earlier connection
+    ///
+    /// Only the stream's creator may publish. The creator's audio and video are
+    /// forwarded to every viewer of the stream.
+    pub async fn publish(
+        &self,
+        stream_id: &str,
+        creator: &str,
+        offer: RTCSessionDescription,
+        gathering: IceGathering,
+        candidates: mpsc::UnboundedSender<LocalCandidate>,
+    ) -> Result<RTCSessionDescription> {
+        let forwarder = {
+            let streams = self.streams.read().await;
+            let stream = streams.get(stream_id).ok_or_else(|| anyhow!("Stream {} not found", stream_id))?;
+            if stream.creator != creator {
+                return Err(anyhow!("Only the creator of stream {} can publish to it", stream_id));
+            }
+            Arc::clone(&stream.forwarder)
+        };
+        self.replace_session(stream_id, creator).await;
+
+        let peer_connection = self.new_peer_connection().await?;
+        let publisher = Arc::downgrade(&peer_connection);
+        peer_connection.on_track(Box::new(
+            move |track: Arc<TrackRemote>, receiver: Arc<RTCRtpReceiver>, _transceiver: Arc<RTCRtpTransceiver>| {
+                forwarder.publish(publisher.clone(), track, receiver);
+                Box::pin(async {})
+            },
+        ));
+
+        let answer = self.open_session(stream_id, creator, false, peer_connection, offer, gathering, candidates).await?;
+        info!("📡 Creator {} publishing to stream {}", creator, stream_id);
+        Ok(answer)
+    }
+
+    /// Add a peer's trickled ICE candidate, holding it until their offer if need be
+    pub async fn add_ice_candidate(&self, stream_id: &str, peer_id: &str, candidate: RTCIceCandidateInit) -> Result<()> {
+        let key = session_key(stream_id, peer_id);
+        let session = self.sessions.read().await.get(&key).map(|session| Arc::clone(&session.peer_connection));
+        if let Some(peer_connection) = session {
+            peer_connection.add_ice_candidate(candidate).await?;
+            return Ok(());
+        }
+
+        if !self.streams.read().await.contains_key(stream_id) {
+            return Err(anyhow!("Stream {} not found", stream_id));
+        }
+        let mut pending = self.pending.write().await;
+        let held = pending.entry(key).or_default();
+        if held.len() >= MAX_PENDING_CANDIDATES {
+            return Err(anyhow!("Too many ICE candidates before an offer for stream {}", stream_id));
+        }
+        held.push(candidate);
+        Ok(())
+    }
+
+    /// Close a peer's connection to a stream, returning whether they had one
+    pub async fn close(&self, stream_id: &str, peer_id: &str, reason: &str) -> bool {
+        let key = session_key(stream_id, peer_id);
+        self.pending.write().await.remove(&key);
+        let session = self.sessions.write().await.remove(&key);
+        let Some(session) = session else {
+            return false;
+        };
+
+        self.shut(stream_id, peer_id, &session.peer_connection).await;
+        if session.viewer {
+            self.emit(StreamingEvent::ViewerDisconnected {
+                stream_id: stream_id.to_string(),
+                viewer_id: peer_id.to_string(),
+                reason: reason.to_string(),
+            }).await;
+        }
+        true
+    }
+
+    /// Forwarder of a stream the engine is serving
+    async fn forwarder(&self, stream_id: &str) -> Option<Arc<Forwarder>> {
+        self.streams.read().await.get(stream_id).map(|stream| Arc::clone(&stream.forwarder))
+    }
+
+    async fn new_peer_connection(&self) -> Result<Arc<RTCPeerConnection>> {
+        let config = RTCConfiguration {
+            ice_servers: self.ice_servers.clone(),
+            ..Default::default()
+        };
+        Ok(Arc::new(self.api.new_peer_connection(config).await?))
+    }
+
+    /// Close a peer's current connection to a stream before they negotiate a new one
+    async fn replace_session(&self, stream_id: &str, peer_id: &str) {
+        let previous = self.sessions.write().await.remove(&session_key(stream_id, peer_id));
+        if let Some(previous) = previous {
+            info!("🔁 {} renegotiating stream {}", peer_id, stream_id);
+            self.shut(stream_id, peer_id, &previous.peer_connection).await;
+        }
+    }
+
+    /// Report a viewer joining or leaving to the streaming event channel
+    async fn emit(&self, event: StreamingEvent) {
+        if self.events.send(event).await.is_err() {
+            warn!("Streaming event channel closed");
+        }
+    }
+
+    /// Negotiate a peer's connection and keep it until they hang up
+    #[allow(clippy::too_many_arguments)]
+    async fn open_session(
+        &self,
+        stream_id: &str,
+        peer_id: &str,
+        viewer: bool,
+        peer_connection: Arc<RTCPeerConnection>,
+        offer: RTCSessionDescription,
+        gathering: IceGathering,
+        candidates: mpsc::UnboundedSender<LocalCandidate>,
+    ) -> Result<RTCSessionDescription> {
+        if gathering == IceGathering::Trickle {
+            let trickle_stream = stream_id.to_string();
+            let trickle = candidates.clone();
+            peer_connection.on_ice_candidate(Box::new(move |candidate: Option<RTCIceCandidate>| {
+                let stream_id = trickle_stream.clone();
+                let candidates = trickle.clone();
+                Box::pin(async move {
+                    // `None` ends gathering, which the peer does not need to hear about
+                    let Some(candidate) = candidate else {
+                        return;
+                    };
+                    match candidate.to_json() {
+                        Ok(candidate) => {
+                            let _ = candidates.send(LocalCandidate { stream_id, candidate });
+                        }
+                        Err(e) => warn!("Failed to encode ICE candidate for stream {}: {}", stream_id, e),
+                    }
+                })
+            }));
+        }
+
+        let signaling = self.clone();
+        let state_stream = stream_id.to_string();
+        let state_peer = peer_id.to_string();
+        peer_connection.on_peer_connection_state_change(Box::new(move |state: RTCPeerConnectionState| {
+            let signaling = signaling.clone();
+            let stream_id = state_stream.clone();
+            let peer_id = state_peer.clone();
+            Box::pin(async move {
+                signaling.state_changed(&stream_id, &peer_id, state).await;
+            })
+        }));
+
+        let answer = match negotiate(&peer_connection, offer, gathering).await {
+            Ok(answer) => answer,
+            Err(e) => {
+                self.shut(stream_id, peer_id, &peer_connection).await;
+                return Err(e);
+            }
+        };
+
+        let key = session_key(stream_id, peer_id);
+        let early = self.pending.write().await.remove(&key).unwrap_or_default();
+        for candidate in early {
+            if let Err(e) = peer_connection.add_ice_candidate(candidate).await {
+                warn!("Dropped early ICE candidate from {}: {}", peer_id, e);
+            }
+        }
+        let (ended_tx, ended) = oneshot::channel();
+        self.sessions.write().await.insert(key.clone(), Session {
+            peer_connection: Arc::clone(&peer_connection),
+            viewer,
+            _ended: ended_tx,
+        });
+
+        // Hang up once nobody is left to signal the peer
+        let watcher = self.clone();
+        let watched = Arc::downgrade(&peer_connection);
+        let (watch_stream, watch_peer) = (stream_id.to_string(), peer_id.to_string());
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = candidates.closed() => {}
+                // The node closed or replaced the session; dropping `candidates` tells the peer's channel
+                _ = ended => return,
+            }
+            let Some(peer_connection) = watched.upgrade() else {
+                return;
+            };
+            let current = watcher.sessions.read().await.get(&key)
+                .is_some_and(|session| Arc::ptr_eq(&session.peer_connection, &peer_connection));
+            if current {
+                info!("🔌 Signaling closed for {} on stream {}", watch_peer, watch_stream);
+                watcher.close(&watch_stream, &watch_peer, "Signaling closed").await;
+            }
+        });
+
+        Ok(answer)
+    }
+
+    /// Close a peer connection that is already out of the session map
+    async fn shut(&self, stream_id: &str, peer_id: &str, peer_connection: &Arc<RTCPeerConnection>) {
+        if let Err(e) = close_peer_connection(Arc::clone(peer_connection)).await {
+            warn!("Error closing peer connection: {}", e);
+        }
+        if let Some(forwarder) = self.forwarder(stream_id).await {
+            forwarder.remove_viewer(peer_id).await;
+        }
+        let _ = self.peer_manager.remove_connection(stream_id, peer_id).await;
+    }
+
+    async fn state_changed(&self, stream_id: &str, peer_id: &str, state: RTCPeerConnectionState) {
+        let status = match state {
+            RTCPeerConnectionState::Connected => {
+                info!("✅ {} connected to stream {} via WebRTC", peer_id, stream_id);
+                // A viewer can only start decoding at a keyframe
+                if let Some(forwarder) = self.forwarder(stream_id).await {
+                    forwarder.request_keyframe().await;
+                }
+                ConnectionStatus::Connected
+            }
+            RTCPeerConnectionState::Disconnected => {
+                info!("❌ {} disconnected from WebRTC stream {}", peer_id, stream_id);
+                ConnectionStatus::Disconnected
+            }
+            RTCPeerConnectionState::Failed => {
+                warn!("💥 WebRTC connection failed for {} on stream {}", peer_id, stream_id);
+                self.close(stream_id, peer_id, "Connection failed").await;
+                return;
+            }
+            _ => return,
+        };
+        let _ = self.peer_manager.update_status(stream_id, peer_id, status).await;
+    }
+}
+
+/// Apply the peer's offer and create the node's answer
+async fn negotiate(
+    peer_connection: &RTCPeerConnection,
+    offer: RTCSessionDescription,
+    gathering: IceGathering,
+) -> Result<RTCSessionDescription> {
+    peer_connection.set_remote_description(offer).await?;
+    let answer = peer_connection.create_answer(None).await?;
+    if gathering == IceGathering::Complete {
+        let mut gathered = peer_connection.gathering_complete_promise().await;
+        peer_connection.set_local_description(answer).await?;
+        let _ = gathered.recv().await;
+    } else {
+        peer_connection.set_local_description(answer).await?;
+    }
+    peer_connection.local_description().await
+        .ok_or_else(|| anyhow!("Peer connection has no local description"))
+}
+
+/// Close a peer connection on a blocking thread
+///
+/// In webrtc 0.7 the future returned by `RTCPeerConnection::close` holds a
+/// std mutex guard across an await, so it is not `Send` and cannot be awaited
+/// from spawned tasks. Driving it with `block_on` on a blocking thread keeps
+/// it on the node's runtime.
+async fn close_peer_connection(peer_connection: Arc<RTCPeerConnection>) -> Result<()> {
+    let runtime = tokio::runtime::Handle::current();
+    tokio::task::spawn_blocking(move || runtime.block_on(peer_connection.close())).await??;
+    Ok(())
+}
+
+fn session_key(stream_id: &str, peer_id: &str) -> String {
+    format!("{}:{}", stream_id, peer_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::streaming::real_webrtc_fixed::RealWebRTCEngine;
+    use crate::streaming::{IceConfig, StreamQualitySettings};
+    use std::time::Duration;
+    use webrtc::api::media_engine::MediaEngine;
+    use webrtc::api::APIBuilder;
+    use webrtc::rtp_transceiver::rtp_codec::RTPCodecType;
+    use webrtc::rtp_transceiver::rtp_transceiver_direction::RTCRtpTransceiverDirection;
+    use webrtc::rtp_transceiver::RTCRtpTransceiverInit;
+
+    /// Signaling for a node serving one stream, "stream" by "creator", and the events it reports
+    async fn serving(port: u16) -> (Signaling, mpsc::Receiver<StreamingEvent>) {
+        let (events, received) = mpsc::channel(16);
+        let ice = IceConfig { host_only: true, ..IceConfig::default() };
+        let mut engine = RealWebRTCEngine::new(port, &ice, events).await.unwrap();
+        engine.create_stream("stream".to_string(), "creator".to_string(), StreamQualitySettings::default()).await.unwrap();
+        (engine.signaling(), received)
+    }
+
+    /// A viewer's receive-only offer for video and audio
+    async fn offer() -> (RTCPeerConnection, RTCSessionDescription) {
+        let mut media_engine = MediaEngine::default();
+        media_engine.register_default_codecs().unwrap();
+        let api = APIBuilder::new().with_media_engine(media_engine).build();
+        let peer_connection = api.new_peer_connection(RTCConfiguration::default()).await.unwrap();
+        for kind in [RTPCodecType::Video, RTPCodecType::Audio] {
+            let init = RTCRtpTransceiverInit { direction: RTCRtpTransceiverDirection::Recvonly, send_encodings: Vec::new() };
+            peer_connection.add_transceiver_from_kind(kind, Some(init)).await.unwrap();
+        }
+        let offer = peer_connection.create_offer(None).await.unwrap();
+        peer_connection.set_local_description(offer.clone()).await.unwrap();
+        (peer_connection, offer)
+    }
+
+    async fn next_event(events: &mut mpsc::Receiver<StreamingEvent>) -> StreamingEvent {
+        tokio::time::timeout(Duration::from_secs(5), events.recv()).await
+            .expect("no streaming event within 5 seconds")
+            .expect("streaming event channel closed")
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn reports_viewers_joining_and_leaving() {
+        let (signaling, mut events) = serving(47_000).await;
+        let (_viewer, offer) = offer().await;
+        let (candidates, _trickled) = mpsc::unbounded_channel();
+
+        let answer = signaling.answer("stream", "viewer", offer, IceGathering::Trickle, candidates).await.unwrap();
+        assert!(!answer.sdp.is_empty());
+        assert!(matches!(
+            next_event(&mut events).await,
+            StreamingEvent::ViewerConnected { stream_id, viewer_id } if stream_id == "stream" && viewer_id == "viewer"
+        ));
+
+        assert!(signaling.close("stream", "viewer", "Access expired").await);
+        assert!(matches!(
+            next_event(&mut events).await,
+            StreamingEvent::ViewerDisconnected { viewer_id, reason, .. } if viewer_id == "viewer" && reason == "Access expired"
+        ));
+        assert!(!signaling.close("stream", "viewer", "Access expired").await);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn hangs_up_viewers_whose_signaling_closes() {
+        let (signaling, mut events) = serving(47_200).await;
+        let (_viewer, offer) = offer().await;
+        let (candidates, trickled) = mpsc::unbounded_channel();
+
+        signaling.answer("stream", "viewer", offer, IceGathering::Trickle, candidates).await.unwrap();
+        assert!(matches!(next_event(&mut events).await, StreamingEvent::ViewerConnected { .. }));
+
+        drop(trickled);
+        assert!(matches!(
+            next_event(&mut events).await,
+            StreamingEvent::ViewerDisconnected { reason, .. } if reason == "Signaling closed"
+        ));
+        assert!(!signaling.close("stream", "viewer", "Left").await);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn only_the_creator_publishes_and_candidates_need_a_stream() {
+        let (signaling, mut events) = serving(47_400).await;
+        let (_viewer, offer) = offer().await;
+        let (candidates, _trickled) = mpsc::unbounded_channel();
+
+        assert!(signaling.publish("stream", "viewer", offer.clone(), IceGathering::Trickle, candidates.clone()).await.is_err());
+        assert!(signaling.answer("other", "viewer", offer, IceGathering::Trickle, candidates).await.is_err());
+
+        let candidate = RTCIceCandidateInit { candidate: "candidate:1 1 udp 1 127.0.0.1 9 typ host".to_string(), ..Default::default() };
+        assert!(signaling.add_ice_candidate("other", "viewer", candidate.clone()).await.is_err());
+        for _ in 0..MAX_PENDING_CANDIDATES {
+            signaling.add_ice_candidate("stream", "viewer", candidate.clone()).await.unwrap();
+        }
+        assert!(signaling.add_ice_candidate("stream", "viewer", candidate).await.is_err());
+        assert!(events.try_recv().is_err());
+    }
+}
@@ -1,13 +1,23 @@
 // Simple web server with WebSocket support
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{broadcast, mpsc, RwLock};
 use warp::Filter;
 use warp::ws::{WebSocket, Message, Ws};
 use futures_util::{SinkExt, StreamExt};
 
+use crate::blockchain::channels::ChannelUpdate;
+use crate::blockchain::slashing::QualityMeasurement;
+use crate::blockchain::state::StateTree;
+use crate::blockchain::{ChainReader, Transaction};
 use crate::integration::SutantraEvent;
-use crate::streaming::StreamingCommand;
+use crate::streaming::{IceGathering, LocalCandidate, Signaling, StreamingCommand};
+use crate::whip::{self, Role};
+use webrtc::ice_transport::ice_candidate::RTCIceCandidateInit;
+use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
+
+/// Relays the API picks to serve each stream
+const RELAYS_PER_STREAM: usize = 3;
 
 // Global stream state management
 static ACTIVE_STREAMS: tokio::sync::OnceCell<Arc<RwLock<HashMap<String, StreamInfo>>>> = tokio::sync::OnceCell::const_new();
@@ -65,6 +75,9 @@ pub struct SimpleWebServer {
     port: u16,
     event_sender: mpsc::UnboundedSender<SutantraEvent>,
     streaming_sender: mpsc::UnboundedSender<StreamingCommand>,
+    chain: Arc<tokio::sync::OnceCell<ChainReader>>,
+    signaling: Arc<tokio::sync::OnceCell<Signaling>>,
+    viewer_events: broadcast::Sender<SutantraEvent>,
     clients: Arc<RwLock<HashMap<String, mpsc::UnboundedSender<Result<Message, warp::Error>>>>>,
 }
 
@@ -73,11 +86,17 @@ impl SimpleWebServer {
         port: u16,
         event_sender: mpsc::UnboundedSender<SutantraEvent>,
         streaming_sender: mpsc::UnboundedSender<StreamingCommand>,
+        chain: Arc<tokio::sync::OnceCell<ChainReader>>,
+        signaling: Arc<tokio::sync::OnceCell<Signaling>>,
+        viewer_events: broadcast::Sender<SutantraEvent>,
     ) -> Self {
         Self {
             port,
             event_sender,
             streaming_sender,
+            chain,
+            signaling,
+            viewer_events,
             clients: Arc::new(RwLock::new(HashMap::new())),
         }
     }
@@ -100,6 +119,9 @@ impl SimpleWebServer {
         let ws_clients = clients.clone();
         let ws_event_sender = event_sender.clone();
         let ws_streaming_sender = streaming_sender.clone();
+        let ws_chain = self.chain.clone();
+        let ws_signaling = self.signaling.clone();
+        let ws_viewer_events = self.viewer_events.clone();
         let ws_port = self.port;
         
         let websocket = warp::path("ws")
@@ -108,10 +130,13 @@ impl SimpleWebServer {
                 let clients = ws_clients.clone();
                 let event_sender = ws_event_sender.clone();
                 let streaming_sender = ws_streaming_sender.clone();
+                let chain = ws_chain.clone();
+                let signaling = ws_signaling.clone();
+                let viewer_events = ws_viewer_events.subscribe();
                 let port = ws_port;
                 
                 ws.on_upgrade(move |websocket| {
-                    handle_websocket(websocket, clients, event_sender, streaming_sender, port)
+                    handle_websocket(websocket, clients, event_sender, streaming_sender, chain, signaling, viewer_events, port)
                 })
             });
 
@@ -156,16 +181,304 @@ impl SimpleWebServer {
                 Ok::<_, warp::Rejection>(warp::reply::json(&streams))
             });
 
+        // API endpoint to list the transaction pool
+        let chain = self.chain.clone();
+        let mempool_api = warp::path!("api" / "mempool")
+            .and(warp::get())
+            .and_then(move || {
+                let chain = chain.clone();
+                async move {
+                    let Some(reader) = chain.get() else {
+                        return Ok::<_, warp::Rejection>(warp::reply::with_status(
+                            warp::reply::json(&serde_json::json!({"error": "Blockchain not running"})),
+                            warp::http::StatusCode::SERVICE_UNAVAILABLE,
+                        ));
+                    };
+                    
+                    let pending = reader.pending_transactions().await;
+                    let ready = pending.iter().filter(|tx| tx.ready).count();
+                    Ok(warp::reply::with_status(
+                        warp::reply::json(&serde_json::json!({
+                            "count": pending.len(),
+                            "ready": ready,
+                            "bytes": pending.iter().map(|tx| tx.size).sum::<usize>(),
+                            "transactions": pending,
+                        })),
+                        warp::http::StatusCode::OK,
+                    ))
+                }
+            });
+
+        // API endpoints to quote transaction fees
+        let chain = self.chain.clone();
+        let fees_api = warp::path!("api" / "fees")
+            .and(warp::get())
+            .and_then(move || {
+                let chain = chain.clone();
+                async move {
+                    let Some(reader) = chain.get() else {
+                        return Ok::<_, warp::Rejection>(warp::reply::with_status(
+                            warp::reply::json(&serde_json::json!({"error": "Blockchain not running"})),
+                            warp::http::StatusCode::SERVICE_UNAVAILABLE,
+                        ));
+                    };
+                    
+                    Ok(warp::reply::with_status(
+                        warp::reply::json(&reader.fee_quote().await),
+                        warp::http::StatusCode::OK,
+                    ))
+                }
+            });
+
+        let chain = self.chain.clone();
+        let fee_quote_api = warp::path!("api" / "fees" / "quote")
+            .and(warp::post())
+            .and(warp::body::json())
+            .and_then(move |transaction: Transaction| {
+                let chain = chain.clone();
+                async move {
+                    let Some(reader) = chain.get() else {
+                        return Ok::<_, warp::Rejection>(warp::reply::with_status(
+                            warp::reply::json(&serde_json::json!({"error": "Blockchain not running"})),
+                            warp::http::StatusCode::SERVICE_UNAVAILABLE,
+                        ));
+                    };
+                    
+                    Ok(warp::reply::with_status(
+                        warp::reply::json(&serde_json::json!({
+                            "weight": transaction.weight(),
+                            "min_fee": reader.min_fee(&transaction).await,
+                        })),
+                        warp::http::StatusCode::OK,
+                    ))
+                }
+            });
+
+        // API endpoint to follow the relay reward epoch
+        let chain = self.chain.clone();
+        let rewards_api = warp::path!("api" / "rewards")
+            .and(warp::get())
+            .and_then(move || {
+                let chain = chain.clone();
+                async move {
+                    let Some(reader) = chain.get() else {
+                        return Ok::<_, warp::Rejection>(warp::reply::with_status(
+                            warp::reply::json(&serde_json::json!({"error": "Blockchain not running"})),
+                            warp::http::StatusCode::SERVICE_UNAVAILABLE,
+                        ));
+                    };
+                    
+                    Ok(warp::reply::with_status(
+                        warp::reply::json(&reader.reward_status().await),
+                        warp::http::StatusCode::OK,
+                    ))
+                }
+            });
+
+        // API endpoint to list bonded stakes and the elected validators
+        let chain = self.chain.clone();
+        let staking_api = warp::path!("api" / "staking")
+            .and(warp::get())
+            .and_then(move || {
+                let chain = chain.clone();
+                async move {
+                    let Some(reader) = chain.get() else {
+                        return Ok::<_, warp::Rejection>(warp::reply::with_status(
+                            warp::reply::json(&serde_json::json!({"error": "Blockchain not running"})),
+                            warp::http::StatusCode::SERVICE_UNAVAILABLE,
+                        ));
+                    };
+                    
+                    Ok(warp::reply::with_status(
+                        warp::reply::json(&reader.staking().await),
+                        warp::http::StatusCode::OK,
+                    ))
+                }
+            });
+
+        // API endpoint to pick the staked relays serving a stream this epoch
+        let chain = self.chain.clone();
+        let relays_api = warp::path!("api" / "streams" / String / "relays")
+            .and(warp::get())
+            .and_then(move |stream_id: String| {
+                let chain = chain.clone();
+                async move {
+                    let Some(reader) = chain.get() else {
+                        return Ok::<_, warp::Rejection>(warp::reply::with_status(
+                            warp::reply::json(&serde_json::json!({"error": "Blockchain not running"})),
+                            warp::http::StatusCode::SERVICE_UNAVAILABLE,
+                        ));
+                    };
+                    
+                    if reader.stream(&stream_id).await.is_none() {
+                        return Ok(warp::reply::with_status(
+                            warp::reply::json(&serde_json::json!({"error": "Stream not registered"})),
+                            warp::http::StatusCode::NOT_FOUND,
+                        ));
+                    }
+                    Ok(warp::reply::with_status(
+                        warp::reply::json(&serde_json::json!({
+                            "stream_id": stream_id,
+                            "relays": reader.pick_relays(&stream_id, RELAYS_PER_STREAM).await,
+                        })),
+                        warp::http::StatusCode::OK,
+                    ))
+                }
+            });
+
+        // API endpoint to list a stream's broadcast sessions
+        let chain = self.chain.clone();
+        let sessions_api = warp::path!("api" / "streams" / String / "sessions")
+            .and(warp::get())
+            .and_then(move |stream_id: String| {
+                let chain = chain.clone();
+                async move {
+                    let Some(reader) = chain.get() else {
+                        return Ok::<_, warp::Rejection>(warp::reply::with_status(
+                            warp::reply::json(&serde_json::json!({"error": "Blockchain not running"})),
+                            warp::http::StatusCode::SERVICE_UNAVAILABLE,
+                        ));
+                    };
+                    
+                    let Some(stream) = reader.stream(&stream_id).await else {
+                        return Ok(warp::reply::with_status(
+                            warp::reply::json(&serde_json::json!({"error": "Stream not registered"})),
+                            warp::http::StatusCode::NOT_FOUND,
+                        ));
+                    };
+                    let history = reader.stream_history(&stream_id).await;
+                    let sessions: Vec<_> = history.sessions.iter()
+                        .map(|session| serde_json::json!({
+                            "started_at": session.started_at,
+                            "ended_at": session.ended_at,
+                            "duration_seconds": session.duration_seconds,
+                            "earnings": session.earnings_so_far(stream.total_earnings),
+                            "viewers": session.viewers.len(),
+                        }))
+                        .collect();
+                    Ok(warp::reply::with_status(
+                        warp::reply::json(&serde_json::json!({
+                            "stream_id": stream.stream_id,
+                            "is_active": history.is_active,
+                            "total_earnings": stream.total_earnings,
+                            "total_viewers": history.total_viewers,
+                            "total_duration_minutes": history.total_duration_minutes,
+                            "sessions": sessions,
+                        })),
+                        warp::http::StatusCode::OK,
+                    ))
+                }
+            });
+
+        // API endpoint to show a stream's revenue split and payout history
+        let chain = self.chain.clone();
+        let payouts_api = warp::path!("api" / "streams" / String / "payouts")
+            .and(warp::get())
+            .and_then(move |stream_id: String| {
+                let chain = chain.clone();
+                async move {
+                    let Some(reader) = chain.get() else {
+                        return Ok::<_, warp::Rejection>(warp::reply::with_status(
+                            warp::reply::json(&serde_json::json!({"error": "Blockchain not running"})),
+                            warp::http::StatusCode::SERVICE_UNAVAILABLE,
+                        ));
+                    };
+                    
+                    match reader.payouts(&stream_id).await {
+                        Some(history) => Ok(warp::reply::with_status(
+                            warp::reply::json(&history),
+                            warp::http::StatusCode::OK,
+                        )),
+                        None => Ok(warp::reply::with_status(
+                            warp::reply::json(&serde_json::json!({"error": "Stream not registered"})),
+                            warp::http::StatusCode::NOT_FOUND,
+                        )),
+                    }
+                }
+            });
+
+        // API endpoint to look up a payment channel and its latest update
+        let chain = self.chain.clone();
+        let channel_api = warp::path!("api" / "channels" / String)
+            .and(warp::get())
+            .and_then(move |channel_id: String| {
+                let chain = chain.clone();
+                async move {
+                    let Some(reader) = chain.get() else {
+                        return Ok::<_, warp::Rejection>(warp::reply::with_status(
+                            warp::reply::json(&serde_json::json!({"error": "Blockchain not running"})),
+                            warp::http::StatusCode::SERVICE_UNAVAILABLE,
+                        ));
+                    };
+                    
+                    let Some((channel, known)) = reader.channel(&channel_id).await else {
+                        return Ok(warp::reply::with_status(
+                            warp::reply::json(&serde_json::json!({"error": "Channel not open"})),
+                            warp::http::StatusCode::NOT_FOUND,
+                        ));
+                    };
+                    Ok(warp::reply::with_status(
+                        warp::reply::json(&serde_json::json!({
+                            "channel": channel,
+                            "latest_update": known.as_ref().map(|known| &known.latest),
+                            "paid_until": known.as_ref().map(|known| known.paid_until),
+                        })),
+                        warp::http::StatusCode::OK,
+                    ))
+                }
+            });
+
+        // API endpoints to prove state entries against the head's state root
+        let chain = self.chain.clone();
+        let proof_api = warp::path!("api" / "proofs" / String / String)
+            .and(warp::get())
+            .and_then(move |tree: String, key: String| {
+                let tree = match tree.as_str() {
+                    "accounts" => Some(StateTree::Accounts),
+                    "streams" => Some(StateTree::Streams),
+                    "channels" => Some(StateTree::Channels),
+                    "stakes" => Some(StateTree::Stakes),
+                    _ => None,
+                };
+                state_proof_reply(chain.clone(), tree, key, String::new())
+            });
+        let chain = self.chain.clone();
+        let access_proof_api = warp::path!("api" / "proofs" / "access" / String / String)
+            .and(warp::get())
+            .and_then(move |viewer: String, stream_id: String| {
+                state_proof_reply(chain.clone(), Some(StateTree::StreamAccess), viewer, stream_id)
+            });
+
+        // WHIP ingest and WHEP playback
+        let whip = crate::whip::routes(self.chain.clone(), self.signaling.clone());
+
         // Combine all routes
         let routes = static_files
             .or(websocket)
             .or(health)
             .or(node_info)
             .or(streams_api)
-            .with(warp::cors().allow_any_origin().allow_headers(vec!["content-type"]).allow_methods(vec!["GET", "POST"]));
+            .or(mempool_api)
+            .or(fees_api)
+            .or(fee_quote_api)
+            .or(rewards_api)
+            .or(staking_api)
+            .or(relays_api)
+            .or(sessions_api)
+            .or(payouts_api)
+            .or(channel_api)
+            .or(access_proof_api)
+            .or(proof_api)
+            .or(whip)
+            .with(warp::cors()
+                .allow_any_origin()
+                .allow_headers(vec!["content-type", "authorization"])
+                .allow_methods(vec!["GET", "POST", "PATCH", "DELETE"])
+                .expose_headers(vec!["location"]));
 
         tracing::info!("✅ Web server ready on http://localhost:{}", self.port);
         tracing::info!("🔌 WebSocket endpoint: ws://localhost:{}/ws", self.port);
+        tracing::info!("📡 WHIP/WHEP endpoints: http://localhost:{}/whip/{{stream_id}}, /whep/{{stream_id}}", self.port);
 
         warp::serve(routes)
             .run(([127, 0, 0, 1], self.port))
@@ -175,11 +488,80 @@ impl SimpleWebServer {
     }
 }
 
+/// Reply with a proof of `key` in `tree`, the decoded entry and the head header it is checked against
+///
+/// `stream_id` is only used for access grants, where `key` is the viewer.
+async fn state_proof_reply(
+    chain: Arc<tokio::sync::OnceCell<ChainReader>>,
+    tree: Option<StateTree>,
+    key: String,
+    stream_id: String,
+) -> Result<warp::reply::WithStatus<warp::reply::Json>, warp::Rejection> {
+    let Some(reader) = chain.get() else {
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({"error": "Blockchain not running"})),
+            warp::http::StatusCode::SERVICE_UNAVAILABLE,
+        ));
+    };
+    let Some(tree) = tree else {
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({"error": "Unknown state tree"})),
+            warp::http::StatusCode::NOT_FOUND,
+        ));
+    };
+
+    let proven = reader.prove(|state| match tree {
+        StateTree::Accounts => state.prove_account(&key),
+        StateTree::Streams => state.prove_stream(&key),
+        StateTree::StreamAccess => state.prove_stream_access(&key, &stream_id),
+        StateTree::Channels => state.prove_channel(&key),
+        StateTree::Stakes => state.prove_stake(&key),
+    }).await;
+    let (header, proof) = match proven {
+        Ok(proven) => proven,
+        Err(e) => {
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({"error": e.to_string()})),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+    };
+
+    let value = match tree {
+        StateTree::Accounts => serde_json::json!(proof.account()),
+        StateTree::Streams => serde_json::json!(proof.stream()),
+        StateTree::StreamAccess => serde_json::json!(proof.stream_access()),
+        StateTree::Channels => serde_json::json!(proof.channel()),
+        StateTree::Stakes => serde_json::json!(proof.stake()),
+    };
+    Ok(warp::reply::with_status(
+        warp::reply::json(&serde_json::json!({
+            "header": header,
+            "value": value,
+            "proof": proof,
+        })),
+        warp::http::StatusCode::OK,
+    ))
+}
+
+/// A WebSocket client's side of WebRTC signaling
+struct ClientSignaling {
+    engine: Arc<tokio::sync::OnceCell<Signaling>>,
+    /// Node ICE candidates for the client's peer connections
+    candidates: mpsc::UnboundedSender<LocalCandidate>,
+    /// (stream, verified viewer) pairs the client is watching
+    viewing: Arc<RwLock<HashSet<(String, String)>>>,
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn handle_websocket(
     websocket: WebSocket,
     clients: Arc<RwLock<HashMap<String, mpsc::UnboundedSender<Result<Message, warp::Error>>>>>,
     event_sender: mpsc::UnboundedSender<SutantraEvent>,
     streaming_sender: mpsc::UnboundedSender<StreamingCommand>,
+    chain: Arc<tokio::sync::OnceCell<ChainReader>>,
+    signaling: Arc<tokio::sync::OnceCell<Signaling>>,
+    mut viewer_events: broadcast::Receiver<SutantraEvent>,
     port: u16,
 ) {
     let client_id = format!("client_{}", chrono::Utc::now().timestamp_millis());
@@ -209,6 +591,54 @@ async fn handle_websocket(
         tracing::info!("🔌 WebSocket connection closed: {}", client_id_for_cleanup);
     });
 
+    // Trickle the node's ICE candidates to the client
+    let (candidate_tx, mut candidate_rx) = mpsc::unbounded_channel::<LocalCandidate>();
+    let candidate_clients = clients.clone();
+    let candidate_client_id = client_id.clone();
+    let candidate_forwarder = tokio::spawn(async move {
+        while let Some(local) = candidate_rx.recv().await {
+            let message = serde_json::json!({
+                "type": "ice-candidate",
+                "streamId": local.stream_id,
+                "candidate": local.candidate
+            });
+            if send_to_client(&candidate_client_id, &candidate_clients, message).await.is_err() {
+                break;
+            }
+        }
+    });
+    let viewing = Arc::new(RwLock::new(HashSet::new()));
+    let signaling = ClientSignaling { engine: signaling, candidates: candidate_tx, viewing: Arc::clone(&viewing) };
+
+    // Ask the client's viewers to pay before their paid time runs out
+    let payment_clients = clients.clone();
+    let payment_client_id = client_id.clone();
+    let payment_forwarder = tokio::spawn(async move {
+        loop {
+            let (stream_id, viewer, amount) = match viewer_events.recv().await {
+                Ok(SutantraEvent::PaymentDue { stream_id, viewer, amount }) => (stream_id, viewer, amount),
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    tracing::warn!("⏰ {} missed {} viewer events", payment_client_id, missed);
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            if !viewing.read().await.contains(&(stream_id.clone(), viewer.clone())) {
+                continue;
+            }
+            let message = serde_json::json!({
+                "type": "paymentDue",
+                "streamId": stream_id,
+                "viewer": viewer,
+                "amount": amount
+            });
+            if send_to_client(&payment_client_id, &payment_clients, message).await.is_err() {
+                break;
+            }
+        }
+    });
+
     // Handle incoming messages from client
     while let Some(result) = ws_rx.next().await {
         match result {
@@ -219,6 +649,8 @@ async fn handle_websocket(
                     &clients,
                     &event_sender,
                     &streaming_sender,
+                    &chain,
+                    &signaling,
                     port,
                 ).await {
                     tracing::error!("Error handling message from {}: {}", client_id, e);
@@ -232,28 +664,36 @@ async fn handle_websocket(
         }
     }
 
-    // Clean up client
+    // Clean up client; dropping the candidate receiver hangs up its peer connections
+    candidate_forwarder.abort();
+    payment_forwarder.abort();
     clients.write().await.remove(&client_id);
     tracing::info!("🔌 WebSocket connection terminated: {}", client_id);
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn handle_message(
     msg: Message,
     client_id: &str,
     clients: &Arc<RwLock<HashMap<String, mpsc::UnboundedSender<Result<Message, warp::Error>>>>>,
     _event_sender: &mpsc::UnboundedSender<SutantraEvent>,
     _streaming_sender: &mpsc::UnboundedSender<StreamingCommand>,
+    chain: &tokio::sync::OnceCell<ChainReader>,
+    signaling: &ClientSignaling,
     port: u16,
 ) -> anyhow::Result<()> {
     if msg.is_text() {
         let text = msg.to_str().map_err(|_| anyhow::anyhow!("Failed to convert message to string"))?;
-        tracing::info!("📨 Received message from {}: {}", client_id, text);
 
         // Parse the message
         let ui_message: serde_json::Value = serde_json::from_str(text)?;
         
+        // Messages carry media tokens and SDP, so only their type is logged
+        let message_type = ui_message.get("type").and_then(|v| v.as_str());
+        tracing::info!("📨 Received {} message from {}", message_type.unwrap_or("untyped"), client_id);
+        
         // Handle different message types
-        match ui_message.get("type").and_then(|v| v.as_str()) {
+        match message_type {
             Some("handshake") => {
                 // Determine node type based on port
                 let node_type = if port == 8080 {
@@ -365,42 +805,102 @@ async fn handle_message(
             Some("joinStream") => {
                 tracing::info!("👥 Stream join request from {}", client_id);
                 
-                let stream_id = ui_message.get("stream_id")
-                    .and_then(|s| s.as_str())
-                    .unwrap_or("unknown");
-                
-                let offer = ui_message.get("offer");
-                
-                tracing::info!("👥 Joining stream: {} with offer: {}", stream_id, offer.is_some());
-                
-                // For now, simulate a successful join
-                // In a full implementation, we would:
-                // 1. Find the streamer for this stream_id
-                // 2. Forward the offer to the streamer
-                // 3. Wait for the streamer's answer
-                // 4. Send the answer back to the viewer
+                // Answer the viewer's offer with a peer connection carrying the stream
+                match answer_offer(chain, signaling, &ui_message).await {
+                    Ok((stream_id, answer)) => {
+                        let answer = serde_json::json!({
+                            "type": "answer",
+                            "streamId": stream_id,
+                            "answer": answer
+                        });
+                        send_to_client(client_id, clients, answer).await?;
+                        
+                        let response = serde_json::json!({
+                            "type": "joinStreamResponse",
+                            "data": {
+                                "success": true,
+                                "stream_id": stream_id,
+                                "message": "Joined stream - WebRTC answer sent"
+                            }
+                        });
+                        send_to_client(client_id, clients, response).await?;
+                    }
+                    Err(e) => {
+                        tracing::warn!("👥 Failed to join {} to stream: {}", client_id, e);
+                        let response = serde_json::json!({
+                            "type": "joinStreamResponse",
+                            "data": {
+                                "success": false,
+                                "error": e.to_string()
+                            }
+                        });
+                        send_to_client(client_id, clients, response).await?;
+                    }
+                }
+            }
+            Some("publishStream") => {
+                tracing::info!("📤 Stream publish request from {}", client_id);
                 
-                let response = serde_json::json!({
-                    "type": "joinStreamResponse",
-                    "data": {
-                        "success": true,
-                        "stream_id": stream_id,
-                        "message": "Successfully joined stream - WebRTC handshake initiated"
+                // The creator's camera is forwarded to every viewer of the stream
+                let response = match publish_offer(chain, signaling, &ui_message).await {
+                    Ok((stream_id, answer)) => serde_json::json!({
+                        "type": "publishStreamResponse",
+                        "data": {
+                            "success": true,
+                            "stream_id": stream_id,
+                            "answer": answer
+                        }
+                    }),
+                    Err(e) => {
+                        tracing::warn!("📤 Failed to publish stream for {}: {}", client_id, e);
+                        serde_json::json!({
+                            "type": "publishStreamResponse",
+                            "data": {
+                                "success": false,
+                                "message": e.to_string()
+                            }
+                        })
                     }
-                });
+                };
                 
                 send_to_client(client_id, clients, response).await?;
             }
             Some("leaveStream") => {
                 tracing::info!("🚪 Stream leave request from {}", client_id);
                 
-                let response = serde_json::json!({
-                    "type": "leaveStreamResponse",
-                    "data": {
-                        "success": true,
-                        "message": "Successfully left stream"
+                // Only the account holding the peer connection can hang it up
+                let result = match (signaling.engine.get(), message_stream_id(&ui_message)) {
+                    (None, _) => Err(anyhow::anyhow!("WebRTC engine not running")),
+                    (_, None) => Err(anyhow::anyhow!("Missing stream_id")),
+                    (Some(engine), Some(stream_id)) => match message_identity(chain, &ui_message, stream_id).await {
+                        Ok(peer) => {
+                            engine.close(stream_id, &peer, "Left the stream").await;
+                            signaling.viewing.write().await.remove(&(stream_id.to_string(), peer));
+                            Ok(())
+                        }
+                        Err(e) => Err(e),
+                    },
+                };
+                
+                let response = match result {
+                    Ok(()) => serde_json::json!({
+                        "type": "leaveStreamResponse",
+                        "data": {
+                            "success": true,
+                            "message": "Successfully left stream"
+                        }
+                    }),
+                    Err(e) => {
+                        tracing::warn!("🚪 Rejected stream leave from {}: {}", client_id, e);
+                        serde_json::json!({
+                            "type": "leaveStreamResponse",
+                            "data": {
+                                "success": false,
+                                "message": e.to_string()
+                            }
+                        })
                     }
-                });
+                };
                 
                 send_to_client(client_id, clients, response).await?;
             }
@@ -442,22 +942,26 @@ async fn handle_message(
             Some("webrtcOffer") => {
                 tracing::info!("📡 WebRTC offer from {}", client_id);
                 
-                let offer = ui_message.get("offer");
-                let stream_id = ui_message.get("stream_id")
-                    .and_then(|s| s.as_str())
-                    .unwrap_or("unknown");
-                
-                tracing::info!("📡 Offer for stream: {}", stream_id);
-                
-                // For now, just acknowledge the offer
-                let response = serde_json::json!({
-                    "type": "webrtcOfferResponse",
-                    "data": {
-                        "success": true,
-                        "stream_id": stream_id,
-                        "message": "Offer received"
+                let response = match answer_offer(chain, signaling, &ui_message).await {
+                    Ok((stream_id, answer)) => serde_json::json!({
+                        "type": "webrtcOfferResponse",
+                        "data": {
+                            "success": true,
+                            "stream_id": stream_id,
+                            "answer": answer
+                        }
+                    }),
+                    Err(e) => {
+                        tracing::warn!("📡 Failed to answer offer from {}: {}", client_id, e);
+                        serde_json::json!({
+                            "type": "webrtcOfferResponse",
+                            "data": {
+                                "success": false,
+                                "message": e.to_string()
+                            }
+                        })
                     }
-                });
+                };
                 
                 send_to_client(client_id, clients, response).await?;
             }
@@ -482,24 +986,114 @@ async fn handle_message(
                 
                 send_to_client(client_id, clients, response).await?;
             }
-            Some("iceCandidate") => {
-                tracing::info!("🧊 ICE candidate from {}", client_id);
+            Some("iceCandidate") | Some("ice-candidate") => {
+                tracing::debug!("🧊 ICE candidate from {}", client_id);
                 
-                let candidate = ui_message.get("candidate");
-                let stream_id = ui_message.get("stream_id")
-                    .and_then(|s| s.as_str())
-                    .unwrap_or("unknown");
+                let result = match (signaling.engine.get(), message_stream_id(&ui_message), ui_message.get("candidate")) {
+                    (None, _, _) => Err(anyhow::anyhow!("WebRTC engine not running")),
+                    (_, None, _) => Err(anyhow::anyhow!("Missing stream_id")),
+                    (_, _, None) => Err(anyhow::anyhow!("Missing ICE candidate")),
+                    (Some(engine), Some(stream_id), Some(candidate)) => {
+                        // Viewers must still have paid time; a stream's creator always passes
+                        match (
+                            serde_json::from_value::<RTCIceCandidateInit>(candidate.clone()),
+                            message_account(chain, &ui_message, stream_id, Role::Viewer).await,
+                        ) {
+                            (Ok(candidate), Ok(peer)) => engine
+                                .add_ice_candidate(stream_id, &peer, candidate)
+                                .await
+                                .map(|()| stream_id),
+                            (Err(e), _) => Err(e.into()),
+                            (_, Err(e)) => Err(e),
+                        }
+                    }
+                };
                 
-                tracing::info!("🧊 ICE candidate for stream: {}", stream_id);
+                let response = match result {
+                    Ok(stream_id) => serde_json::json!({
+                        "type": "iceCandidateResponse",
+                        "data": {
+                            "success": true,
+                            "stream_id": stream_id,
+                            "message": "ICE candidate received"
+                        }
+                    }),
+                    Err(e) => {
+                        tracing::warn!("🧊 Rejected ICE candidate from {}: {}", client_id, e);
+                        serde_json::json!({
+                            "type": "iceCandidateResponse",
+                            "data": {
+                                "success": false,
+                                "message": e.to_string()
+                            }
+                        })
+                    }
+                };
                 
-                let response = serde_json::json!({
-                    "type": "iceCandidateResponse",
-                    "data": {
-                        "success": true,
-                        "stream_id": stream_id,
-                        "message": "ICE candidate received"
+                send_to_client(client_id, clients, response).await?;
+            }
+            Some("channelUpdate") => {
+                // Viewers pay as they watch with signed cumulative channel updates
+                let result = match (chain.get(), ui_message.get("data")) {
+                    (None, _) => Err(anyhow::anyhow!("Blockchain not running")),
+                    (_, None) => Err(anyhow::anyhow!("Missing channel update")),
+                    (Some(reader), Some(data)) => match serde_json::from_value::<ChannelUpdate>(data.clone()) {
+                        Ok(update) => reader.submit_channel_update(update).await,
+                        Err(e) => Err(e.into()),
+                    },
+                };
+                
+                let response = match result {
+                    Ok(paid_until) => serde_json::json!({
+                        "type": "channelUpdateResponse",
+                        "data": {
+                            "success": true,
+                            "paid_until": paid_until
+                        }
+                    }),
+                    Err(e) => {
+                        tracing::warn!("🧾 Rejected channel update from {}: {}", client_id, e);
+                        serde_json::json!({
+                            "type": "channelUpdateResponse",
+                            "data": {
+                                "success": false,
+                                "message": e.to_string()
+                            }
+                        })
                     }
-                });
+                };
+                
+                send_to_client(client_id, clients, response).await?;
+            }
+            Some("qualityMeasurement") => {
+                // Viewers' own measurements keep validators' quality reports honest
+                let result = match (chain.get(), ui_message.get("data")) {
+                    (None, _) => Err(anyhow::anyhow!("Blockchain not running")),
+                    (_, None) => Err(anyhow::anyhow!("Missing quality measurement")),
+                    (Some(reader), Some(data)) => match serde_json::from_value::<QualityMeasurement>(data.clone()) {
+                        Ok(measurement) => reader.submit_quality_measurement(measurement).await,
+                        Err(e) => Err(e.into()),
+                    },
+                };
+                
+                let response = match result {
+                    Ok(()) => serde_json::json!({
+                        "type": "qualityMeasurementResponse",
+                        "data": {
+                            "success": true
+                        }
+                    }),
+                    Err(e) => {
+                        tracing::warn!("📏 Rejected quality measurement from {}: {}", client_id, e);
+                        serde_json::json!({
+                            "type": "qualityMeasurementResponse",
+                            "data": {
+                                "success": false,
+                                "message": e.to_string()
+                            }
+                        })
+                    }
+                };
                 
                 send_to_client(client_id, clients, response).await?;
             }
@@ -526,6 +1120,82 @@ async fn send_to_client(
     Ok(())
 }
 
+/// Stream a signaling message is for, in any of the shapes clients send it
+fn message_stream_id(ui_message: &serde_json::Value) -> Option<&str> {
+    ui_message.get("stream_id")
+        .or_else(|| ui_message.get("streamId"))
+        .or_else(|| ui_message.get("data").and_then(|d| d.get("stream_id")))
+        .and_then(|s| s.as_str())
+}
+
+/// Media token a client's signaling message carries, as WHIP clients send in their bearer token
+fn message_token(ui_message: &serde_json::Value) -> Option<&str> {
+    ui_message.get("token")
+        .or_else(|| ui_message.get("data").and_then(|d| d.get("token")))
+        .and_then(|t| t.as_str())
+}
+
+/// Account whose media token a signaling message carries
+async fn message_identity(
+    chain: &tokio::sync::OnceCell<ChainReader>,
+    ui_message: &serde_json::Value,
+    stream_id: &str,
+) -> anyhow::Result<String> {
+    let chain = chain.get().ok_or_else(|| anyhow::anyhow!("Blockchain not running"))?;
+    let token = message_token(ui_message).ok_or_else(|| anyhow::anyhow!("Missing media token"))?;
+    whip::identify(chain, stream_id, token).await
+}
+
+/// Account whose media token a signaling message carries, once the chain lets them take `role` on the stream
+async fn message_account(
+    chain: &tokio::sync::OnceCell<ChainReader>,
+    ui_message: &serde_json::Value,
+    stream_id: &str,
+    role: Role,
+) -> anyhow::Result<String> {
+    let chain = chain.get().ok_or_else(|| anyhow::anyhow!("Blockchain not running"))?;
+    let token = message_token(ui_message).ok_or_else(|| anyhow::anyhow!("Missing media token"))?;
+    whip::authenticate(chain, stream_id, role, token).await
+}
+
+/// Stream and SDP offer a client's signaling message carries
+fn message_offer(ui_message: &serde_json::Value) -> anyhow::Result<(&str, RTCSessionDescription)> {
+    let stream_id = message_stream_id(ui_message).ok_or_else(|| anyhow::anyhow!("Missing stream_id"))?;
+    let offer = ui_message.get("offer").ok_or_else(|| anyhow::anyhow!("Missing SDP offer"))?;
+    Ok((stream_id, serde_json::from_value(offer.clone())?))
+}
+
+/// Answer a viewer's SDP offer, returning the stream it is for and the node's answer
+async fn answer_offer(
+    chain: &tokio::sync::OnceCell<ChainReader>,
+    signaling: &ClientSignaling,
+    ui_message: &serde_json::Value,
+) -> anyhow::Result<(String, RTCSessionDescription)> {
+    let engine = signaling.engine.get().ok_or_else(|| anyhow::anyhow!("WebRTC engine not running"))?;
+    let (stream_id, offer) = message_offer(ui_message)?;
+    
+    // Viewers watch as the account their token proves, and only with paid time unless the stream is free
+    let viewer = message_account(chain, ui_message, stream_id, Role::Viewer).await?;
+    let answer = engine.answer(stream_id, &viewer, offer, IceGathering::Trickle, signaling.candidates.clone()).await?;
+    signaling.viewing.write().await.insert((stream_id.to_string(), viewer));
+    Ok((stream_id.to_string(), answer))
+}
+
+/// Answer a creator's offer to publish their camera to a stream
+async fn publish_offer(
+    chain: &tokio::sync::OnceCell<ChainReader>,
+    signaling: &ClientSignaling,
+    ui_message: &serde_json::Value,
+) -> anyhow::Result<(String, RTCSessionDescription)> {
+    let engine = signaling.engine.get().ok_or_else(|| anyhow::anyhow!("WebRTC engine not running"))?;
+    let (stream_id, offer) = message_offer(ui_message)?;
+    
+    // Only the stream's on-chain creator, proven by their signed token, may publish
+    let creator = message_account(chain, ui_message, stream_id, Role::Publisher).await?;
+    let answer = engine.publish(stream_id, &creator, offer, IceGathering::Trickle, signaling.candidates.clone()).await?;
+    Ok((stream_id.to_string(), answer))
+}
+
 async fn broadcast_stream_list(
     clients: &Arc<RwLock<HashMap<String, mpsc::UnboundedSender<Result<Message, warp::Error>>>>>,
     _port: u16,
@@ -0,0 +1,514 @@
+// WHIP ingest and WHEP playback
+//
+// Broadcasting tools publish to a stream with WHIP and players watch it with
+// WHEP. Both are plain HTTP alternatives to the node's WebSocket signaling. A
+// client POSTs its SDP offer to `/whip/{stream_id}` or `/whep/{stream_id}`.
+// The node replies with its answer, which already carries all of the node's
+// ICE candidates, and with the URL of a session resource. The client PATCHes
+// that resource with an SDP fragment to trickle its own candidates and
+// DELETEs it to hang up. A session also ends when the node hangs up the peer
+// itself. Every request carries a bearer token that the client's account key
+// signed for the stream, valid for at most a day. Only the stream's on-chain
+// creator may publish. Viewers need paid time on the stream unless the
+// stream is free.
+
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{mpsc, OnceCell, RwLock};
+use warp::http::{header, Response, StatusCode};
+use warp::hyper::Body;
+use warp::Filter;
+
+use crate::blockchain::crypto;
+use crate::blockchain::pricing::PricingModel;
+use crate::blockchain::ChainReader;
+use crate::streaming::{IceGathering, LocalCandidate, Signaling};
+use webrtc::ice_transport::ice_candidate::RTCIceCandidateInit;
+use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
+
+/// Domain separator for media bearer token signatures
+const TOKEN_SIGNING_DOMAIN: &str = "sutantra-media-token-v1";
+
+/// Largest SDP offer or fragment accepted
+const MAX_SDP_BYTES: u64 = 64 * 1024;
+
+/// Longest a media token may stay valid, in seconds
+const MAX_TOKEN_LIFETIME_SECS: i64 = 24 * 60 * 60;
+
+/// A failed request's status and explanation
+type Failure = (StatusCode, String);
+
+/// Which side of a stream a client is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Role {
+    /// WHIP: the creator sending their media
+    Publisher,
+    /// WHEP: a viewer playing the stream
+    Viewer,
+}
+
+impl Role {
+    fn from_path(path: &str) -> Option<Self> {
+        match path {
+            "whip" => Some(Role::Publisher),
+            "whep" => Some(Role::Viewer),
+            _ => None,
+        }
+    }
+
+    fn path(self) -> &'static str {
+        match self {
+            Role::Publisher => "whip",
+            Role::Viewer => "whep",
+        }
+    }
+}
+
+/// Token proving a client holds an account key, scoped to one stream
+///
+/// Written `ADDRESS.EXPIRES.SIGNATURE`, where `EXPIRES` is a unix time in
+/// seconds and `SIGNATURE` is a hex ed25519 signature by `ADDRESS` over
+/// [`MediaToken::signing_payload`]. HTTP clients send it as a bearer token and
+/// WebSocket clients in their signaling messages.
+#[derive(Debug, Clone)]
+struct MediaToken {
+    address: String,
+    expires_at: i64,
+    signature: String,
+}
+
+impl MediaToken {
+    /// Parse an `Authorization` header value
+    fn from_header(value: &str) -> Result<Self> {
+        let token = value.strip_prefix("Bearer ")
+            .ok_or_else(|| anyhow!("Expected a bearer token"))?;
+        Self::parse(token)
+    }
+
+    /// Parse a token written `ADDRESS.EXPIRES.SIGNATURE`
+    fn parse(token: &str) -> Result<Self> {
+        let mut parts = token.trim().splitn(3, '.');
+        let (Some(address), Some(expires_at), Some(signature)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(anyhow!("Expected a token of the form ADDRESS.EXPIRES.SIGNATURE"));
+        };
+
+        Ok(Self {
+            address: address.to_string(),
+            expires_at: expires_at.parse()?,
+            signature: signature.to_string(),
+        })
+    }
+
+    /// Canonical bytes covered by the signature
+    fn signing_payload(address: &str, stream_id: &str, expires_at: i64, chain_id: &str) -> Vec<u8> {
+        bincode::serialize(&(TOKEN_SIGNING_DOMAIN, chain_id, address, stream_id, expires_at))
+            .expect("media token serialization cannot fail")
+    }
+
+    /// Check the token is signed by its address for the stream and is valid at unix time `now`
+    fn verify(&self, stream_id: &str, chain_id: &str, now: i64) -> Result<()> {
+        if self.expires_at <= now {
+            return Err(anyhow!("Token expired"));
+        }
+        if self.expires_at - now > MAX_TOKEN_LIFETIME_SECS {
+            return Err(anyhow!("Token must expire within {} seconds", MAX_TOKEN_LIFETIME_SECS));
+        }
+        crypto::verify(
+            &self.address,
+            &Self::signing_payload(&self.address, stream_id, self.expires_at, chain_id),
+            &self.signature,
+        )
+    }
+}
+
+/// A WHIP or WHEP client's peer connection to a stream
+struct Session {
+    role: Role,
+    stream_id: String,
+    peer: String,
+}
+
+/// State shared by the WHIP and WHEP routes
+#[derive(Clone)]
+struct MediaEndpoints {
+    chain: Arc<OnceCell<ChainReader>>,
+    signaling: Arc<OnceCell<Signaling>>,
+    /// Session id → session
+    sessions: Arc<RwLock<HashMap<String, Session>>>,
+}
+
+/// WHIP and WHEP routes: offers to `/{whip,whep}/{stream_id}`, trickle and teardown on the session they return
+pub fn routes(
+    chain: Arc<OnceCell<ChainReader>>,
+    signaling: Arc<OnceCell<Signaling>>,
+) -> impl Filter<Extract = (Response<Body>,), Error = warp::Rejection> + Clone {
+    let endpoints = MediaEndpoints {
+        chain,
+        signaling,
+        sessions: Arc::new(RwLock::new(HashMap::new())),
+    };
+    let with_endpoints = warp::any().map(move || endpoints.clone());
+
+    let role = warp::path::param::<String>().and_then(|path: String| async move {
+        Role::from_path(&path).ok_or_else(warp::reject::not_found)
+    });
+    let authorization = warp::header::optional::<String>("authorization");
+    let content_type = warp::header::optional::<String>("content-type");
+
+    let offer = warp::post()
+        .and(role)
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(authorization)
+        .and(content_type)
+        .and(warp::body::content_length_limit(MAX_SDP_BYTES))
+        .and(warp::body::bytes())
+        .and(with_endpoints.clone())
+        .and_then(|role, stream_id, authorization, content_type, body: bytes::Bytes, endpoints: MediaEndpoints| async move {
+            let result = endpoints.offer(role, stream_id, authorization, content_type, &body).await;
+            Ok::<_, warp::Rejection>(respond(result))
+        });
+
+    let trickle = warp::patch()
+        .and(role)
+        .and(warp::path::param::<String>())
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(authorization)
+        .and(content_type)
+        .and(warp::body::content_length_limit(MAX_SDP_BYTES))
+        .and(warp::body::bytes())
+        .and(with_endpoints.clone())
+        .and_then(|role, stream_id, session_id, authorization, content_type, body: bytes::Bytes, endpoints: MediaEndpoints| async move {
+            let result = endpoints.trickle(role, stream_id, session_id, authorization, content_type, &body).await;
+            Ok::<_, warp::Rejection>(respond(result))
+        });
+
+    let teardown = warp::delete()
+        .and(role)
+        .and(warp::path::param::<String>())
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(authorization)
+        .and(with_endpoints)
+        .and_then(|role, stream_id, session_id, authorization, endpoints: MediaEndpoints| async move {
+            let result = endpoints.teardown(role, stream_id, session_id, authorization).await;
+            Ok::<_, warp::Rejection>(respond(result))
+        });
+
+    offer.or(trickle).unify().or(teardown).unify()
+}
+
+impl MediaEndpoints {
+    /// Answer a WHIP or WHEP offer and open a session for it
+    async fn offer(
+        &self,
+        role: Role,
+        stream_id: String,
+        authorization: Option<String>,
+        content_type: Option<String>,
+        body: &[u8],
+    ) -> Result<Response<Body>, Failure> {
+        expect_content_type(content_type.as_deref(), "application/sdp")?;
+        let chain = self.chain.get()
+            .ok_or_else(|| (StatusCode::SERVICE_UNAVAILABLE, "Blockchain not running".to_string()))?;
+        let signaling = self.signaling.get()
+            .ok_or_else(|| (StatusCode::SERVICE_UNAVAILABLE, "WebRTC engine not running".to_string()))?;
+
+        let token = verify_token(chain, &stream_id, authorization.as_deref()).await?;
+        authorize(chain, &stream_id, role, &token.address).await?;
+
+        let sdp = String::from_utf8(body.to_vec())
+            .map_err(|_| (StatusCode::BAD_REQUEST, "SDP offer is not UTF-8".to_string()))?;
+        let offer = RTCSessionDescription::offer(sdp)
+            .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid SDP offer: {}", e)))?;
+
+        let (hangup_tx, mut hangup) = mpsc::unbounded_channel::<LocalCandidate>();
+        let answered = match role {
+            Role::Publisher => signaling.publish(&stream_id, &token.address, offer, IceGathering::Complete, hangup_tx).await,
+            Role::Viewer => signaling.answer(&stream_id, &token.address, offer, IceGathering::Complete, hangup_tx).await,
+        };
+        let answer = answered.map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+        // A new offer replaces the peer's earlier connection, and with it their earlier session
+        let session_id = uuid::Uuid::new_v4().simple().to_string();
+        let mut sessions = self.sessions.write().await;
+        sessions.retain(|_, session| session.stream_id != stream_id || session.peer != token.address);
+        sessions.insert(session_id.clone(), Session {
+            role,
+            stream_id: stream_id.clone(),
+            peer: token.address.clone(),
+        });
+        drop(sessions);
+
+        // Signaling drops its end of the channel once it hangs up the peer
+        let ended_sessions = Arc::clone(&self.sessions);
+        let ended_id = session_id.clone();
+        tokio::spawn(async move {
+            while hangup.recv().await.is_some() {}
+            ended_sessions.write().await.remove(&ended_id);
+        });
+
+        tracing::info!("🌐 {} session {} opened by {} on stream {}", role.path().to_uppercase(), session_id, token.address, stream_id);
+
+        Response::builder()
+            .status(StatusCode::CREATED)
+            .header(header::CONTENT_TYPE, "application/sdp")
+            .header(header::LOCATION, format!("/{}/{}/{}", role.path(), stream_id, session_id))
+            .body(Body::from(answer.sdp))
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+    }
+
+    /// Add the ICE candidates in a client's trickle ICE fragment
+    async fn trickle(
+        &self,
+        role: Role,
+        stream_id: String,
+        session_id: String,
+        authorization: Option<String>,
+        content_type: Option<String>,
+        body: &[u8],
+    ) -> Result<Response<Body>, Failure> {
+        expect_content_type(content_type.as_deref(), "application/trickle-ice-sdpfrag")?;
+        let peer = self.session_peer(role, &stream_id, &session_id, authorization.as_deref()).await?;
+        let signaling = self.signaling.get()
+            .ok_or_else(|| (StatusCode::SERVICE_UNAVAILABLE, "WebRTC engine not running".to_string()))?;
+
+        let fragment = std::str::from_utf8(body)
+            .map_err(|_| (StatusCode::BAD_REQUEST, "SDP fragment is not UTF-8".to_string()))?;
+        for candidate in fragment_candidates(fragment) {
+            signaling.add_ice_candidate(&stream_id, &peer, candidate).await
+                .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+        }
+
+        empty_response(StatusCode::NO_CONTENT)
+    }
+
+    /// Hang up a client's session
+    async fn teardown(
+        &self,
+        role: Role,
+        stream_id: String,
+        session_id: String,
+        authorization: Option<String>,
+    ) -> Result<Response<Body>, Failure> {
+        let peer = self.session_peer(role, &stream_id, &session_id, authorization.as_deref()).await?;
+        let signaling = self.signaling.get()
+            .ok_or_else(|| (StatusCode::SERVICE_UNAVAILABLE, "WebRTC engine not running".to_string()))?;
+
+        self.sessions.write().await.remove(&session_id);
+        signaling.close(&stream_id, &peer, "Hung up").await;
+        tracing::info!("🌐 {} session {} closed by {} on stream {}", role.path().to_uppercase(), session_id, peer, stream_id);
+
+        empty_response(StatusCode::OK)
+    }
+
+    /// Peer of a session, once the request's token shows it comes from them
+    async fn session_peer(
+        &self,
+        role: Role,
+        stream_id: &str,
+        session_id: &str,
+        authorization: Option<&str>,
+    ) -> Result<String, Failure> {
+        let chain = self.chain.get()
+            .ok_or_else(|| (StatusCode::SERVICE_UNAVAILABLE, "Blockchain not running".to_string()))?;
+        let token = verify_token(chain, stream_id, authorization).await?;
+
+        let sessions = self.sessions.read().await;
+        let session = sessions.get(session_id)
+            .filter(|session| session.role == role && session.stream_id == stream_id)
+            .ok_or_else(|| (StatusCode::NOT_FOUND, "Session not found".to_string()))?;
+        if session.peer != token.address {
+            return Err((StatusCode::FORBIDDEN, "Session belongs to another account".to_string()));
+        }
+        Ok(session.peer.clone())
+    }
+}
+
+/// Parse and check the bearer token of a request for a stream
+async fn verify_token(chain: &ChainReader, stream_id: &str, authorization: Option<&str>) -> Result<MediaToken, Failure> {
+    let authorization = authorization
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing bearer token".to_string()))?;
+    let token = MediaToken::from_header(authorization)
+        .map_err(|e| (StatusCode::UNAUTHORIZED, e.to_string()))?;
+    check_token(chain, stream_id, token).await
+}
+
+/// Account a WebSocket client's media token for a stream proves they hold
+pub(crate) async fn identify(chain: &ChainReader, stream_id: &str, token: &str) -> Result<String> {
+    let token = MediaToken::parse(token)?;
+    let token = check_token(chain, stream_id, token).await
+        .map_err(|(_, message)| anyhow!(message))?;
+    Ok(token.address)
+}
+
+/// Account a WebSocket client's media token proves they hold, once the chain lets them take `role` on the stream
+pub(crate) async fn authenticate(chain: &ChainReader, stream_id: &str, role: Role, token: &str) -> Result<String> {
+    let address = identify(chain, stream_id, token).await?;
+    authorize(chain, stream_id, role, &address).await
+        .map_err(|(_, message)| anyhow!(message))?;
+    Ok(address)
+}
+
+/// Check a token's signature and expiry for a stream on this chain
+async fn check_token(chain: &ChainReader, stream_id: &str, token: MediaToken) -> Result<MediaToken, Failure> {
+    let chain_id = chain.status().await
+        .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e.to_string()))?
+        .chain_id;
+    token.verify(stream_id, &chain_id, chrono::Utc::now().timestamp())
+        .map_err(|e| (StatusCode::UNAUTHORIZED, e.to_string()))?;
+    Ok(token)
+}
+
+/// Check an account may publish to or watch a stream, going by the chain
+async fn authorize(chain: &ChainReader, stream_id: &str, role: Role, address: &str) -> Result<(), Failure> {
+    let stream = chain.stream(stream_id).await
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Stream not registered".to_string()))?;
+
+    let allowed = match role {
+        Role::Publisher => stream.creator == address,
+        Role::Viewer => {
+            stream.creator == address
+                || stream.pricing == PricingModel::Free
+                || chain.access_until(address, stream_id).await
+                    .is_some_and(|paid_until| paid_until > chrono::Utc::now())
+        }
+    };
+    if !allowed {
+        let reason = match role {
+            Role::Publisher => "Only the stream's creator can publish to it",
+            Role::Viewer => "Payment required",
+        };
+        return Err((StatusCode::FORBIDDEN, reason.to_string()));
+    }
+    Ok(())
+}
+
+fn expect_content_type(content_type: Option<&str>, expected: &str) -> Result<(), Failure> {
+    let matches = content_type
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case(expected));
+    if !matches {
+        return Err((StatusCode::UNSUPPORTED_MEDIA_TYPE, format!("Expected {}", expected)));
+    }
+    Ok(())
+}
+
+/// ICE candidates in a trickle ICE SDP fragment (RFC 8840)
+fn fragment_candidates(fragment: &str) -> Vec<RTCIceCandidateInit> {
+    let mut username_fragment = None;
+    let mut sdp_mline_index: Option<u16> = None;
+    let mut sdp_mid = None;
+    let mut candidates = Vec::new();
+
+    for line in fragment.lines().map(str::trim) {
+        if let Some(ufrag) = line.strip_prefix("a=ice-ufrag:") {
+            username_fragment = Some(ufrag.to_string());
+        } else if line.starts_with("m=") {
+            sdp_mline_index = Some(sdp_mline_index.map_or(0, |index| index + 1));
+            sdp_mid = None;
+        } else if let Some(mid) = line.strip_prefix("a=mid:") {
+            sdp_mid = Some(mid.to_string());
+        } else if let Some(candidate) = line.strip_prefix("a=").filter(|rest| rest.starts_with("candidate:")) {
+            candidates.push(RTCIceCandidateInit {
+                candidate: candidate.to_string(),
+                sdp_mid: sdp_mid.clone(),
+                sdp_mline_index,
+                username_fragment: username_fragment.clone(),
+            });
+        }
+    }
+
+    candidates
+}
+
+fn empty_response(status: StatusCode) -> Result<Response<Body>, Failure> {
+    Response::builder()
+        .status(status)
+        .body(Body::empty())
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// Turn a handler's outcome into a response, explaining failures in plain text
+fn respond(result: Result<Response<Body>, Failure>) -> Response<Body> {
+    result.unwrap_or_else(|(status, message)| {
+        tracing::warn!("🌐 WHIP/WHEP request failed ({}): {}", status, message);
+        let mut response = Response::new(Body::from(message));
+        *response.status_mut() = status;
+        if status == StatusCode::UNAUTHORIZED {
+            response.headers_mut().insert(header::WWW_AUTHENTICATE, header::HeaderValue::from_static("Bearer"));
+        }
+        response
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+
+    fn token(key: &SigningKey, stream_id: &str, expires_at: i64) -> MediaToken {
+        let address = crypto::address_from_public_key(&key.verifying_key());
+        let signature = crypto::sign(key, &MediaToken::signing_payload(&address, stream_id, expires_at, "chain"));
+        MediaToken::parse(&format!("{}.{}.{}", address, expires_at, signature)).unwrap()
+    }
+
+    #[test]
+    fn tokens_are_valid_for_their_stream_until_they_expire() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let now = 1_700_000_000;
+
+        let valid = token(&key, "stream", now + 60);
+        valid.verify("stream", "chain", now).unwrap();
+        assert!(valid.verify("other", "chain", now).is_err());
+        assert!(valid.verify("stream", "other-chain", now).is_err());
+        assert!(valid.verify("stream", "chain", now + 60).is_err());
+    }
+
+    #[test]
+    fn tokens_cannot_outlive_the_maximum_lifetime() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let now = 1_700_000_000;
+
+        token(&key, "stream", now + MAX_TOKEN_LIFETIME_SECS).verify("stream", "chain", now).unwrap();
+        assert!(token(&key, "stream", now + MAX_TOKEN_LIFETIME_SECS + 1).verify("stream", "chain", now).is_err());
+        assert!(token(&key, "stream", i64::MAX).verify("stream", "chain", now).is_err());
+    }
+
+    #[test]
+    fn fragment_candidates_follow_their_media_section() {
+        let fragment = "a=ice-ufrag:EsAw\r\n\
+                        a=ice-pwd:P2uYro0UCOQ4zxjKXaWCBui1\r\n\
+                        m=audio 9 RTP/AVP 0\r\n\
+                        a=mid:0\r\n\
+                        a=candidate:1387637174 1 udp 2122260223 192.0.2.1 61764 typ host generation 0\r\n\
+                        a=end-of-candidates\r\n\
+                        m=video 9 RTP/AVP 96\r\n\
+                        a=mid:1\r\n\
+                        a=candidate:3471623853 1 udp 2122194687 198.51.100.2 61765 typ host generation 0\r\n";
+
+        let candidates = fragment_candidates(fragment);
+        assert_eq!(candidates.len(), 2);
+
+        assert_eq!(candidates[0].candidate, "candidate:1387637174 1 udp 2122260223 192.0.2.1 61764 typ host generation 0");
+        assert_eq!(candidates[0].sdp_mid.as_deref(), Some("0"));
+        assert_eq!(candidates[0].sdp_mline_index, Some(0));
+        assert_eq!(candidates[0].username_fragment.as_deref(), Some("EsAw"));
+
+        assert_eq!(candidates[1].sdp_mid.as_deref(), Some("1"));
+        assert_eq!(candidates[1].sdp_mline_index, Some(1));
+    }
+
+    #[test]
+    fn fragment_without_candidates_or_media_sections() {
+        assert!(fragment_candidates("a=ice-ufrag:EsAw\r\na=end-of-candidates\r\n").is_empty());
+
+        let bare = fragment_candidates("a=candidate:1 1 udp 1 192.0.2.1 9 typ host");
+        assert_eq!(bare.len(), 1);
+        assert_eq!(bare[0].sdp_mid, None);
+        assert_eq!(bare[0].sdp_mline_index, None);
+        assert_eq!(bare[0].username_fragment, None);
+    }
+}
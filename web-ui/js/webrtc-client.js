@@ -13,6 +13,8 @@ class SutantraWebRTCClient {
         const currentPort = window.location.port || '8080';
         this.nodeUrl = `ws://localhost:${currentPort}/ws`;
         this.streamId = null;
+        // Signed media token (ADDRESS.EXPIRES.SIGNATURE) for the stream being watched
+        this.mediaToken = null;
         this.stats = {
             uploadSpeed: 0,
             latency: 0,
@@ -120,6 +122,9 @@ class SutantraWebRTCClient {
                 case 'stream-stats':
                     this.handleStreamStats(message);
                     break;
+                case 'paymentDue':
+                    this.handlePaymentDue(message);
+                    break;
                 case 'pong':
                     console.log('🏓 Received pong from server');
                     break;
@@ -246,7 +251,8 @@ class SutantraWebRTCClient {
                 this.sendMessage({
                     type: 'ice-candidate',
                     candidate: event.candidate,
-                    streamId: this.streamId
+                    streamId: this.streamId,
+                    token: this.mediaToken
                 });
             }
         };
@@ -333,6 +339,15 @@ class SutantraWebRTCClient {
         this.updateStatsUI();
     }
 
+    handlePaymentDue(message) {
+        // The viewer keeps watching only if they pay before their paid time runs out
+        console.warn(`⏰ Payment due: ${message.amount} STREAM for ${message.streamId}`);
+        if (window.uiController) {
+            window.uiController.showNotification(
+                `Payment of ${message.amount} STREAM due for stream ${message.streamId}`, 'warning');
+        }
+    }
+
     handleError(message) {
         console.error('❌ Received error:', message);
         this.updateConnectionStatus('error');
@@ -673,6 +688,11 @@ class SutantraWebRTCClient {
         });
     }
 
+    setMediaToken(token) {
+        // Token the viewer's wallet signed for the stream; the node checks it against paid access
+        this.mediaToken = token;
+    }
+
     async joinStream(streamId) {
         console.log('🚀 Joining stream:', streamId);
         this.streamId = streamId;
@@ -680,7 +700,11 @@ class SutantraWebRTCClient {
         try {
             // Set up peer connection for receiving stream
             await this.createPeerConnection();
-            
+
+            // Ask for the stream's video and audio; the node answers with the tracks the stream has
+            this.peerConnection.addTransceiver('video', { direction: 'recvonly' });
+            this.peerConnection.addTransceiver('audio', { direction: 'recvonly' });
+
             // Create offer to start WebRTC handshake
             const offer = await this.peerConnection.createOffer();
             await this.peerConnection.setLocalDescription(offer);
@@ -692,6 +716,7 @@ class SutantraWebRTCClient {
                 type: 'joinStream',
                 stream_id: streamId,
                 offer: offer,
+                token: this.mediaToken,
                 data: {}
             });
             
@@ -709,6 +734,7 @@ class SutantraWebRTCClient {
             this.sendMessage({
                 type: 'leaveStream',
                 stream_id: this.streamId,
+                token: this.mediaToken,
                 data: {}
             });
             this.streamId = null;